@@ -0,0 +1,19 @@
+use std::env;
+use std::process::Command;
+
+fn main() {
+    println!("cargo:rerun-if-changed=build.rs");
+
+    // `loom` is set through `RUSTFLAGS` for model checking. Declaring it needs Rust 1.80, before
+    // that Cargo warns about the instruction.
+    if rustc_minor_version().is_some_and(|minor| minor >= 80) {
+        println!("cargo:rustc-check-cfg=cfg(loom)");
+    }
+}
+
+fn rustc_minor_version() -> Option<u32> {
+    let rustc = env::var_os("RUSTC")?;
+    let output = Command::new(rustc).arg("--version").output().ok()?;
+    let version = String::from_utf8(output.stdout).ok()?;
+    version.split('.').nth(1)?.parse().ok()
+}
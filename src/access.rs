@@ -0,0 +1,109 @@
+use core::cell::RefCell;
+
+use critical_section::Mutex;
+
+use crate::lock::LockPolicy;
+use crate::{BusHandle, CeilingMutex, CsRefCell, MutexRefCellExt, PriorityThreshold};
+
+/// Brief exclusive access to a `T`, however it is protected.
+///
+/// Library code that only needs `&mut T` for a moment can take an `impl ExclusiveAccess<T>`
+/// instead of a particular mutex, and work the same with a `&Mutex<RefCell<T>>` shared with
+/// an interrupt handler, a plain `&mut T` owned by the caller, or an RTIC resource. The trait is
+/// implemented for `&mut T`, `&RefCell<T>`, and shared references to the mutexes of this crate;
+/// other frameworks' resources can implement it by forwarding to their own `lock` method.
+///
+/// ```
+/// use bare_metal::ExclusiveAccess;
+/// use core::cell::RefCell;
+/// use critical_section::Mutex;
+///
+/// struct Stats {
+///     samples: u32,
+/// }
+///
+/// fn record(mut stats: impl ExclusiveAccess<Stats>) {
+///     stats.with(|stats| stats.samples += 1);
+/// }
+///
+/// static SHARED: Mutex<RefCell<Stats>> = Mutex::new(RefCell::new(Stats { samples: 0 }));
+/// record(&SHARED);
+///
+/// let mut local = Stats { samples: 0 };
+/// record(&mut local);
+/// assert_eq!(local.samples, 1);
+/// ```
+pub trait ExclusiveAccess<T: ?Sized> {
+    /// Calls `f` with exclusive access to the data.
+    ///
+    /// # Panics
+    ///
+    /// Implementations that track borrows at runtime panic if the data is already borrowed,
+    /// e.g. if `f` calls `with` again through another accessor to the same data.
+    fn with<R>(&mut self, f: impl FnOnce(&mut T) -> R) -> R;
+}
+
+impl<T: ?Sized> ExclusiveAccess<T> for &mut T {
+    #[inline]
+    fn with<R>(&mut self, f: impl FnOnce(&mut T) -> R) -> R {
+        f(self)
+    }
+}
+
+impl<T: ?Sized> ExclusiveAccess<T> for &RefCell<T> {
+    #[inline]
+    #[track_caller]
+    fn with<R>(&mut self, f: impl FnOnce(&mut T) -> R) -> R {
+        f(&mut self.borrow_mut())
+    }
+}
+
+impl<T> ExclusiveAccess<T> for &Mutex<RefCell<T>> {
+    #[inline]
+    #[track_caller]
+    fn with<R>(&mut self, f: impl FnOnce(&mut T) -> R) -> R {
+        MutexRefCellExt::lock(*self, f)
+    }
+}
+
+impl<T> ExclusiveAccess<T> for &CsRefCell<T> {
+    #[inline]
+    #[track_caller]
+    fn with<R>(&mut self, f: impl FnOnce(&mut T) -> R) -> R {
+        crate::with_cs(|cs| f(&mut self.borrow_mut(cs)))
+    }
+}
+
+impl<T, P: LockPolicy> ExclusiveAccess<T> for &crate::lock::Mutex<T, P> {
+    #[inline]
+    #[track_caller]
+    fn with<R>(&mut self, f: impl FnOnce(&mut T) -> R) -> R {
+        self.lock(f)
+    }
+}
+
+#[cfg(target_has_atomic = "8")]
+impl<T> ExclusiveAccess<T> for &crate::SpinLock<T> {
+    #[inline]
+    fn with<R>(&mut self, f: impl FnOnce(&mut T) -> R) -> R {
+        f(&mut self.lock())
+    }
+}
+
+impl<T, P: PriorityThreshold, const CEILING: u8> ExclusiveAccess<T>
+    for &CeilingMutex<T, P, CEILING>
+{
+    #[inline]
+    #[track_caller]
+    fn with<R>(&mut self, f: impl FnOnce(&mut T) -> R) -> R {
+        self.lock(f)
+    }
+}
+
+impl<T> ExclusiveAccess<T> for BusHandle<'_, T> {
+    #[inline]
+    #[track_caller]
+    fn with<R>(&mut self, f: impl FnOnce(&mut T) -> R) -> R {
+        self.lock(f)
+    }
+}
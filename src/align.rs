@@ -0,0 +1,56 @@
+//! Alignments for [`CachePadded`](crate::CachePadded) and
+//! [`DmaBuffer`](crate::dma::DmaBuffer).
+//!
+//! Each `AlignN` type in this module is a zero-sized marker whose alignment is given by its
+//! name. [`ConstAlign`] maps an alignment given as a number to its marker.
+
+mod sealed {
+    pub trait Sealed {}
+}
+
+/// A marker type for an alignment, implemented by the types in this module.
+///
+/// This trait is sealed.
+pub trait Alignment: sealed::Sealed {}
+
+/// An alignment of `ALIGN` bytes, given as a number.
+///
+/// [`SupportedAlignment`] is implemented for each `ALIGN` that has a marker type in this module.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct ConstAlign<const ALIGN: usize>;
+
+/// Implemented by every [`ConstAlign`] with a marker type in this module.
+///
+/// This trait is sealed.
+pub trait SupportedAlignment: sealed::Sealed {
+    /// The marker type of the alignment.
+    type Marker: Alignment + Copy;
+}
+
+macro_rules! alignments {
+    ($($name:ident = $align:literal),*) => {$(
+        #[doc = concat!("An alignment of ", stringify!($align), " bytes.")]
+        #[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+        #[repr(align($align))]
+        pub struct $name;
+
+        impl sealed::Sealed for $name {}
+        impl Alignment for $name {}
+
+        impl sealed::Sealed for ConstAlign<$align> {}
+        impl SupportedAlignment for ConstAlign<$align> {
+            type Marker = $name;
+        }
+    )*};
+}
+
+alignments!(
+    Align1 = 1,
+    Align2 = 2,
+    Align4 = 4,
+    Align8 = 8,
+    Align16 = 16,
+    Align32 = 32,
+    Align64 = 64,
+    Align128 = 128
+);
@@ -0,0 +1,168 @@
+use core::alloc::Layout;
+use core::cell::UnsafeCell;
+use core::fmt;
+use core::mem::MaybeUninit;
+
+use crate::CsCell;
+
+#[derive(Clone, Copy)]
+struct State {
+    // number of bytes handed out so far, including padding
+    used: usize,
+    sealed: bool,
+}
+
+/// A bump allocator over a buffer of `SIZE` bytes, for allocations made during start-up.
+///
+/// Each [`alloc`](BumpArena::alloc) moves a value into the next free, suitably aligned part of
+/// the buffer and returns a reference to it, which lives as long as the arena; for an arena in
+/// a `static` that's a `&'static mut T`. Memory is never reused, and the values are never
+/// dropped. Once start-up is done, [`seal`](BumpArena::seal) rejects all further allocations,
+/// so that forgotten ones at run time fail loudly instead of slowly using up the buffer.
+///
+/// This replaces the `static mut BUF: MaybeUninit<_>` pattern for objects that are created once
+/// during board bring-up. The bookkeeping of each allocation is done inside a critical section.
+///
+/// ```
+/// use bare_metal::BumpArena;
+///
+/// static ARENA: BumpArena<256> = BumpArena::new();
+///
+/// let rx_buffer: &'static mut [u8; 64] = ARENA.alloc([0; 64]);
+/// let counter: &'static mut u32 = ARENA.alloc(0);
+/// rx_buffer[0] = 1;
+/// *counter += 1;
+///
+/// ARENA.seal();
+/// assert_eq!(ARENA.try_alloc(0u32), Err(0));
+/// ```
+pub struct BumpArena<const SIZE: usize> {
+    state: CsCell<State>,
+    buffer: UnsafeCell<MaybeUninit<[u8; SIZE]>>,
+}
+
+impl<const SIZE: usize> BumpArena<SIZE> {
+    /// Creates an empty, unsealed arena.
+    #[inline]
+    pub const fn new() -> Self {
+        BumpArena {
+            state: CsCell::new(State {
+                used: 0,
+                sealed: false,
+            }),
+            buffer: UnsafeCell::new(MaybeUninit::uninit()),
+        }
+    }
+
+    /// Moves `val` into the arena and returns a reference to it.
+    ///
+    /// # Panics
+    ///
+    /// Panics if the arena is sealed or doesn't have enough space left.
+    #[inline]
+    #[track_caller]
+    #[allow(clippy::mut_from_ref)]
+    pub fn alloc<T>(&self, val: T) -> &mut T {
+        match self.try_alloc(val) {
+            Ok(val) => val,
+            Err(_) => alloc_failed(),
+        }
+    }
+
+    /// Moves `val` into the arena and returns a reference to it, or gives `val` back if the
+    /// arena is sealed or doesn't have enough space left.
+    #[allow(clippy::mut_from_ref)]
+    pub fn try_alloc<T>(&self, val: T) -> Result<&mut T, T> {
+        let Some(ptr) = self.reserve(Layout::new::<T>()) else {
+            return Err(val);
+        };
+        // SAFETY: the reserved bytes are suitably aligned and handed out only once
+        unsafe {
+            let ptr = ptr.cast::<T>();
+            ptr.write(val);
+            Ok(&mut *ptr)
+        }
+    }
+
+    // reserves the next free, suitably aligned bytes for `layout`
+    pub(crate) fn reserve(&self, layout: Layout) -> Option<*mut u8> {
+        let base = self.buffer.get().cast::<u8>();
+        let start = crate::with_cs(|cs| {
+            let mut state = self.state.get(cs);
+            if state.sealed {
+                return None;
+            }
+            // SAFETY: `used` is at most `SIZE`, so the pointer stays inside the buffer
+            let padding = unsafe { base.add(state.used) }.align_offset(layout.align());
+            let start = state.used.checked_add(padding)?;
+            let end = start.checked_add(layout.size())?;
+            if end > SIZE {
+                return None;
+            }
+            state.used = end;
+            self.state.set(cs, state);
+            Some(start)
+        })?;
+        // SAFETY: `start` is at most `SIZE`
+        Some(unsafe { base.add(start) })
+    }
+
+    /// Rejects all further allocations.
+    #[inline]
+    pub fn seal(&self) {
+        crate::with_cs(|cs| {
+            let state = self.state.get(cs);
+            self.state.set(
+                cs,
+                State {
+                    sealed: true,
+                    ..state
+                },
+            );
+        })
+    }
+
+    /// Returns `true` if the arena has been sealed.
+    #[inline]
+    pub fn is_sealed(&self) -> bool {
+        crate::with_cs(|cs| self.state.get(cs).sealed)
+    }
+
+    /// Returns the number of bytes handed out so far, including padding for alignment.
+    #[inline]
+    pub fn used(&self) -> usize {
+        crate::with_cs(|cs| self.state.get(cs).used)
+    }
+
+    /// Returns the size of the buffer in bytes.
+    #[inline]
+    pub const fn capacity(&self) -> usize {
+        SIZE
+    }
+}
+
+#[cold]
+#[track_caller]
+fn alloc_failed() -> ! {
+    panic!("BumpArena is sealed or out of space")
+}
+
+impl<const SIZE: usize> Default for BumpArena<SIZE> {
+    fn default() -> Self {
+        BumpArena::new()
+    }
+}
+
+impl<const SIZE: usize> fmt::Debug for BumpArena<SIZE> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let state = crate::with_cs(|cs| self.state.get(cs));
+        f.debug_struct("BumpArena")
+            .field("used", &state.used)
+            .field("sealed", &state.sealed)
+            .finish_non_exhaustive()
+    }
+}
+
+// NOTE every allocation hands out a distinct part of the buffer to the context that made it,
+// and the bookkeeping is only accessed inside critical sections
+unsafe impl<const SIZE: usize> Sync for BumpArena<SIZE> {}
@@ -0,0 +1,263 @@
+use core::cell::UnsafeCell;
+use core::fmt;
+use core::future::Future;
+use core::marker::PhantomData;
+use core::ops::{Deref, DerefMut};
+use core::pin::Pin;
+use core::task::{Context, Poll};
+
+use crate::waiters::{Waiter, WaiterList};
+use crate::CsRefCell;
+
+/// An async mutex for resources that are held for a long time.
+///
+/// Instead of masking interrupts for as long as the resource is in use, [`lock`](AsyncMutex::lock)
+/// returns a future that resolves once the mutex is free, so other tasks keep running while one
+/// of them, e.g., waits for a flash erase to finish. The mutex keeps the
+/// [`Waker`](core::task::Waker)s of up to `WAITERS` contending tasks in a fixed-size list, and
+/// wakes the next one when the mutex is unlocked. More contenders are supported, but the ones
+/// that don't fit into the list are polled again right away instead of being parked.
+///
+/// The state of the mutex is only accessed inside short critical sections, so it works with any
+/// executor and can be unlocked from any context.
+///
+/// ```
+/// use bare_metal::AsyncMutex;
+///
+/// pub struct Flash;
+///
+/// impl Flash {
+///     pub async fn erase(&mut self, _sector: u32) {}
+/// }
+///
+/// static FLASH: AsyncMutex<Flash> = AsyncMutex::new(Flash);
+///
+/// async fn clear_settings() {
+///     let mut flash = FLASH.lock().await;
+///     flash.erase(7).await;
+/// }
+/// ```
+///
+/// Polling the future by hand shows that it waits for the holder of the mutex:
+///
+/// ```
+/// use bare_metal::AsyncMutex;
+/// use core::future::Future;
+/// use core::pin::pin;
+/// use core::task::{Context, Poll};
+/// # use core::task::{RawWaker, RawWakerVTable, Waker};
+/// # const VTABLE: RawWakerVTable = RawWakerVTable::new(|_| RAW, |_| {}, |_| {}, |_| {});
+/// # const RAW: RawWaker = RawWaker::new(core::ptr::null(), &VTABLE);
+/// # let waker = unsafe { Waker::from_raw(RAW) };
+///
+/// let mutex: AsyncMutex<u32> = AsyncMutex::new(0);
+/// let mut cx = Context::from_waker(&waker);
+///
+/// let guard = mutex.try_lock().unwrap();
+/// let mut lock = pin!(mutex.lock());
+/// assert!(lock.as_mut().poll(&mut cx).is_pending());
+///
+/// drop(guard);
+/// match lock.poll(&mut cx) {
+///     Poll::Ready(mut value) => *value += 1,
+///     Poll::Pending => unreachable!(),
+/// };
+/// ```
+pub struct AsyncMutex<T, const WAITERS: usize = 4> {
+    state: CsRefCell<State<WAITERS>>,
+    value: UnsafeCell<T>,
+}
+
+struct State<const WAITERS: usize> {
+    locked: bool,
+    waiters: WaiterList<WAITERS>,
+}
+
+impl<T, const WAITERS: usize> AsyncMutex<T, WAITERS> {
+    /// Creates a new, unlocked mutex.
+    #[inline]
+    pub const fn new(value: T) -> Self {
+        AsyncMutex {
+            state: CsRefCell::new(State {
+                locked: false,
+                waiters: WaiterList::new(),
+            }),
+            value: UnsafeCell::new(value),
+        }
+    }
+
+    /// Returns a future that locks the mutex once it is free.
+    #[inline]
+    pub fn lock(&self) -> AsyncMutexLock<'_, T, WAITERS> {
+        AsyncMutexLock {
+            mutex: self,
+            slot: None,
+        }
+    }
+
+    /// Locks the mutex if it is free, returning `None` otherwise.
+    #[inline]
+    pub fn try_lock(&self) -> Option<AsyncMutexGuard<'_, T, WAITERS>> {
+        let locked = crate::with_cs(|cs| {
+            let mut state = self.state.borrow_mut(cs);
+            core::mem::replace(&mut state.locked, true)
+        });
+        if locked {
+            None
+        } else {
+            Some(AsyncMutexGuard {
+                mutex: self,
+                _value: PhantomData,
+            })
+        }
+    }
+
+    /// Returns a mutable reference to the data.
+    ///
+    /// This does not need to lock the mutex since `&mut self` already guarantees exclusive
+    /// access.
+    #[inline]
+    pub fn get_mut(&mut self) -> &mut T {
+        self.value.get_mut()
+    }
+
+    /// Consumes the mutex, returning the data.
+    #[inline]
+    pub fn into_inner(self) -> T {
+        self.value.into_inner()
+    }
+
+    fn unlock(&self) {
+        let waker = crate::with_cs(|cs| {
+            let mut state = self.state.borrow_mut(cs);
+            state.locked = false;
+            state.waiters.wake_next()
+        });
+        if let Some(waker) = waker {
+            waker.wake();
+        }
+    }
+}
+
+impl<T: Default, const WAITERS: usize> Default for AsyncMutex<T, WAITERS> {
+    fn default() -> Self {
+        AsyncMutex::new(T::default())
+    }
+}
+
+impl<T, const WAITERS: usize> fmt::Debug for AsyncMutex<T, WAITERS> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("AsyncMutex").finish_non_exhaustive()
+    }
+}
+
+// NOTE the data is only accessed through the guard, and the flag in the state ensures that there
+// is at most one guard at a time. As with `Mutex`, the data must be `Send` because the guard can
+// be held by a task in a different context.
+unsafe impl<T, const WAITERS: usize> Sync for AsyncMutex<T, WAITERS> where T: Send {}
+
+/// The future returned by [`AsyncMutex::lock`].
+#[must_use = "futures do nothing unless polled"]
+pub struct AsyncMutexLock<'a, T, const WAITERS: usize = 4> {
+    mutex: &'a AsyncMutex<T, WAITERS>,
+    // the waiter slot holding our waker, if any
+    slot: Option<usize>,
+}
+
+impl<'a, T, const WAITERS: usize> Future for AsyncMutexLock<'a, T, WAITERS> {
+    type Output = AsyncMutexGuard<'a, T, WAITERS>;
+
+    fn poll(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Self::Output> {
+        let this = self.get_mut();
+        let (acquired, parked, old) = crate::with_cs(|cs| {
+            let mut state = this.mutex.state.borrow_mut(cs);
+            if !state.locked {
+                state.locked = true;
+                let old = this.slot.take().map(|slot| state.waiters.remove(slot));
+                return (true, true, old);
+            }
+            let (parked, old) = state.waiters.register(&mut this.slot, cx.waker());
+            (false, parked, old)
+        });
+        // drop the previous waker outside of the critical section
+        drop(old);
+        if acquired {
+            Poll::Ready(AsyncMutexGuard {
+                mutex: this.mutex,
+                _value: PhantomData,
+            })
+        } else {
+            if !parked {
+                // the waiter list is full, so try again on the next poll
+                cx.waker().wake_by_ref();
+            }
+            Poll::Pending
+        }
+    }
+}
+
+impl<T, const WAITERS: usize> Drop for AsyncMutexLock<'_, T, WAITERS> {
+    fn drop(&mut self) {
+        let Some(slot) = self.slot else { return };
+        let (old, next) = crate::with_cs(|cs| {
+            let mut state = self.mutex.state.borrow_mut(cs);
+            let old = state.waiters.remove(slot);
+            // if we were woken to take over the mutex, pass that on to the next waiter
+            let next = match old {
+                Waiter::Woken if !state.locked => state.waiters.wake_next(),
+                _ => None,
+            };
+            (old, next)
+        });
+        drop(old);
+        if let Some(next) = next {
+            next.wake();
+        }
+    }
+}
+
+impl<T, const WAITERS: usize> fmt::Debug for AsyncMutexLock<'_, T, WAITERS> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("AsyncMutexLock").finish_non_exhaustive()
+    }
+}
+
+/// A guard that gives access to the data in an [`AsyncMutex`].
+///
+/// The mutex is unlocked, and the next waiting task woken, when this guard is dropped.
+pub struct AsyncMutexGuard<'a, T, const WAITERS: usize = 4> {
+    mutex: &'a AsyncMutex<T, WAITERS>,
+    // the guard hands out `&mut T`, so it is only `Send` and `Sync` if that is
+    _value: PhantomData<&'a mut T>,
+}
+
+impl<T, const WAITERS: usize> Deref for AsyncMutexGuard<'_, T, WAITERS> {
+    type Target = T;
+
+    #[inline]
+    fn deref(&self) -> &T {
+        // SAFETY: the guard proves that the mutex is locked by us
+        unsafe { &*self.mutex.value.get() }
+    }
+}
+
+impl<T, const WAITERS: usize> DerefMut for AsyncMutexGuard<'_, T, WAITERS> {
+    #[inline]
+    fn deref_mut(&mut self) -> &mut T {
+        // SAFETY: the guard proves that the mutex is locked by us
+        unsafe { &mut *self.mutex.value.get() }
+    }
+}
+
+impl<T, const WAITERS: usize> Drop for AsyncMutexGuard<'_, T, WAITERS> {
+    #[inline]
+    fn drop(&mut self) {
+        self.mutex.unlock();
+    }
+}
+
+impl<T: fmt::Debug, const WAITERS: usize> fmt::Debug for AsyncMutexGuard<'_, T, WAITERS> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        fmt::Debug::fmt(&**self, f)
+    }
+}
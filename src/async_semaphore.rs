@@ -0,0 +1,202 @@
+use core::fmt;
+use core::future::Future;
+use core::pin::Pin;
+use core::task::{Context, Poll};
+
+use crate::waiters::WaiterList;
+use crate::CsRefCell;
+
+/// An async counting semaphore with up to `MAX` permits.
+///
+/// This is the async counterpart of [`Semaphore`](crate::Semaphore), for flow control between
+/// async tasks and interrupt handlers: [`acquire`](AsyncSemaphore::acquire) returns a future
+/// that resolves once enough permits are available, and [`release`](AsyncSemaphore::release)
+/// can be called from any context, e.g. by the interrupt handler that finished with a buffer.
+/// Up to `WAITERS` waiting tasks are parked; more are supported, but polled again right away.
+///
+/// ```
+/// use bare_metal::{executor, AsyncSemaphore};
+///
+/// // four transmit buffers
+/// static TX_BUFFERS: AsyncSemaphore<4> = AsyncSemaphore::new(4);
+///
+/// async fn send_packet() {
+///     TX_BUFFERS.acquire(1).await;
+///     // fill a buffer and start the transfer
+/// }
+///
+/// // In the transfer complete interrupt handler
+/// fn on_transfer_complete() {
+///     TX_BUFFERS.release(1);
+/// }
+///
+/// executor::block_on(send_packet());
+/// assert_eq!(TX_BUFFERS.available(), 3);
+/// on_transfer_complete();
+/// assert_eq!(TX_BUFFERS.available(), 4);
+/// ```
+pub struct AsyncSemaphore<const MAX: usize, const WAITERS: usize = 4> {
+    state: CsRefCell<State<WAITERS>>,
+}
+
+struct State<const WAITERS: usize> {
+    permits: usize,
+    waiters: WaiterList<WAITERS>,
+}
+
+impl<const MAX: usize, const WAITERS: usize> AsyncSemaphore<MAX, WAITERS> {
+    /// Creates a new semaphore with `permits` available permits.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `permits` is greater than `MAX`.
+    #[inline]
+    pub const fn new(permits: usize) -> Self {
+        assert!(permits <= MAX, "more permits than the maximum");
+        AsyncSemaphore {
+            state: CsRefCell::new(State {
+                permits,
+                waiters: WaiterList::new(),
+            }),
+        }
+    }
+
+    /// Returns a future that acquires `n` permits at once, once they are available.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `n` is greater than `MAX`, since the future could never complete.
+    #[inline]
+    #[track_caller]
+    pub fn acquire(&self, n: usize) -> AsyncSemaphoreAcquire<'_, MAX, WAITERS> {
+        if n > MAX {
+            too_many_permits();
+        }
+        AsyncSemaphoreAcquire {
+            semaphore: self,
+            n,
+            slot: None,
+        }
+    }
+
+    /// Acquires `n` permits at once, returning `false` if fewer are available.
+    ///
+    /// Either all `n` permits are acquired or none.
+    #[inline]
+    pub fn try_acquire(&self, n: usize) -> bool {
+        crate::with_cs(|cs| {
+            let mut state = self.state.borrow_mut(cs);
+            match state.permits.checked_sub(n) {
+                Some(permits) => {
+                    state.permits = permits;
+                    true
+                }
+                None => false,
+            }
+        })
+    }
+
+    /// Releases `n` permits at once and wakes the waiting tasks, returning `false` if that would
+    /// exceed `MAX` permits.
+    ///
+    /// Either all `n` permits are released or none.
+    pub fn release(&self, n: usize) -> bool {
+        let released = crate::with_cs(|cs| {
+            let mut state = self.state.borrow_mut(cs);
+            match state.permits.checked_add(n) {
+                Some(permits) if permits <= MAX => {
+                    state.permits = permits;
+                    true
+                }
+                _ => false,
+            }
+        });
+        if released {
+            // every waiter may want a different number of permits, so wake all of them, one
+            // critical section each
+            while let Some(waker) =
+                crate::with_cs(|cs| self.state.borrow_mut(cs).waiters.wake_next())
+            {
+                waker.wake();
+            }
+        }
+        released
+    }
+
+    /// Returns the number of available permits.
+    #[inline]
+    pub fn available(&self) -> usize {
+        crate::with_cs(|cs| self.state.borrow_mut(cs).permits)
+    }
+}
+
+#[cold]
+#[track_caller]
+fn too_many_permits() -> ! {
+    panic!("more permits requested than the maximum")
+}
+
+impl<const MAX: usize, const WAITERS: usize> fmt::Debug for AsyncSemaphore<MAX, WAITERS> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("AsyncSemaphore")
+            .field("available", &self.available())
+            .finish_non_exhaustive()
+    }
+}
+
+/// The future returned by [`AsyncSemaphore::acquire`].
+#[must_use = "futures do nothing unless polled"]
+pub struct AsyncSemaphoreAcquire<'a, const MAX: usize, const WAITERS: usize = 4> {
+    semaphore: &'a AsyncSemaphore<MAX, WAITERS>,
+    n: usize,
+    // the waiter slot holding our waker, if any
+    slot: Option<usize>,
+}
+
+impl<const MAX: usize, const WAITERS: usize> Future for AsyncSemaphoreAcquire<'_, MAX, WAITERS> {
+    type Output = ();
+
+    fn poll(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<()> {
+        let this = self.get_mut();
+        let (acquired, parked, old) = crate::with_cs(|cs| {
+            let mut state = this.semaphore.state.borrow_mut(cs);
+            if let Some(permits) = state.permits.checked_sub(this.n) {
+                state.permits = permits;
+                let old = this.slot.take().map(|slot| state.waiters.remove(slot));
+                return (true, true, old);
+            }
+            let (parked, old) = state.waiters.register(&mut this.slot, cx.waker());
+            (false, parked, old)
+        });
+        // drop the previous waker outside of the critical section
+        drop(old);
+        if acquired {
+            Poll::Ready(())
+        } else {
+            if !parked {
+                // the waiter list is full, so try again on the next poll
+                cx.waker().wake_by_ref();
+            }
+            Poll::Pending
+        }
+    }
+}
+
+impl<const MAX: usize, const WAITERS: usize> Drop for AsyncSemaphoreAcquire<'_, MAX, WAITERS> {
+    fn drop(&mut self) {
+        let Some(slot) = self.slot else { return };
+        let old = crate::with_cs(|cs| self.semaphore.state.borrow_mut(cs).waiters.remove(slot));
+        // drop our waker outside of the critical section
+        drop(old);
+    }
+}
+
+impl<const MAX: usize, const WAITERS: usize> fmt::Debug
+    for AsyncSemaphoreAcquire<'_, MAX, WAITERS>
+{
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("AsyncSemaphoreAcquire")
+            .field("permits", &self.n)
+            .finish_non_exhaustive()
+    }
+}
@@ -0,0 +1,122 @@
+use core::fmt;
+use core::future::Future;
+use core::pin::Pin;
+use core::task::{Context, Poll};
+
+use crate::{Exchange, WakerSlot};
+
+/// An awaitable signal for handing values from an interrupt handler to an async task.
+///
+/// The interrupt handler calls [`signal`](AsyncSignal::signal), and the task awaits
+/// [`wait`](AsyncSignal::wait), which resolves to the value. Like [`Exchange`], the latest value
+/// wins: a value that hasn't been received yet is replaced by the next one. This is the async
+/// counterpart of [`Signal`](crate::Signal), and works with any executor.
+///
+/// Only one task should wait on a signal at a time; if several do, only the one that polled
+/// last is woken.
+///
+/// ```
+/// use bare_metal::AsyncSignal;
+///
+/// static CONVERSION: AsyncSignal<u16> = AsyncSignal::new();
+///
+/// // In the interrupt handler
+/// fn on_conversion_done(sample: u16) {
+///     CONVERSION.signal(sample);
+/// }
+///
+/// async fn sample() -> u16 {
+///     CONVERSION.wait().await
+/// }
+/// # on_conversion_done(512);
+/// # assert_eq!(CONVERSION.try_take(), Some(512));
+/// ```
+pub struct AsyncSignal<T> {
+    value: Exchange<T>,
+    waker: WakerSlot,
+}
+
+impl<T> AsyncSignal<T> {
+    const_fn! {
+        /// Creates a signal without a value.
+        #[inline]
+        pub const fn new() -> Self {
+            AsyncSignal {
+                value: Exchange::new(),
+                waker: WakerSlot::new(),
+            }
+        }
+    }
+
+    /// Stores `value`, replacing any value that hasn't been received yet, and wakes the waiting
+    /// task.
+    #[inline]
+    pub fn signal(&self, value: T) {
+        crate::with_cs(|cs| self.value.publish(cs, value));
+        self.waker.wake();
+    }
+
+    /// Returns a future that resolves to the next value.
+    ///
+    /// If a value has already been signaled, the future resolves to it right away.
+    #[inline]
+    pub fn wait(&self) -> AsyncSignalWait<'_, T> {
+        AsyncSignalWait { signal: self }
+    }
+
+    /// Takes the value, returning `None` if there is none.
+    #[inline]
+    pub fn try_take(&self) -> Option<T> {
+        crate::with_cs(|cs| self.value.take(cs))
+    }
+
+    /// Returns `true` if there is a value that hasn't been received yet.
+    #[inline]
+    pub fn is_signaled(&self) -> bool {
+        crate::with_cs(|cs| self.value.has_value(cs))
+    }
+
+    /// Drops the value, if any.
+    #[inline]
+    pub fn reset(&self) {
+        // drop the value outside of the critical section
+        drop(self.try_take());
+    }
+}
+
+impl<T> Default for AsyncSignal<T> {
+    fn default() -> Self {
+        AsyncSignal::new()
+    }
+}
+
+impl<T> fmt::Debug for AsyncSignal<T> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("AsyncSignal").finish_non_exhaustive()
+    }
+}
+
+/// The future returned by [`AsyncSignal::wait`].
+#[must_use = "futures do nothing unless polled"]
+pub struct AsyncSignalWait<'a, T> {
+    signal: &'a AsyncSignal<T>,
+}
+
+impl<T> Future for AsyncSignalWait<'_, T> {
+    type Output = T;
+
+    fn poll(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<T> {
+        self.signal.waker.register(cx.waker());
+        // check after registering, so a signal in between isn't missed
+        match self.signal.try_take() {
+            Some(value) => Poll::Ready(value),
+            None => Poll::Pending,
+        }
+    }
+}
+
+impl<T> fmt::Debug for AsyncSignalWait<'_, T> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("AsyncSignalWait").finish_non_exhaustive()
+    }
+}
@@ -0,0 +1,105 @@
+//! The atomic types used by the lock-free primitives of this crate.
+//!
+//! With the `portable-atomic` feature enabled these come from the `portable-atomic` crate.
+//! Otherwise they are the `core` atomics on targets with compare-and-swap operations, and the
+//! critical-section based [`CsAtomicBool`](crate::CsAtomicBool) and friends everywhere else. This
+//! way every primitive has a single implementation that is lock-free where the hardware allows
+//! it and still correct on targets like `thumbv6m`.
+//!
+//! When the crate is built with `--cfg loom`, all of them, the [`native`] atomics and
+//! [`UnsafeCell`] come from `loom` instead, so that the primitives can be model checked.
+
+use core::mem::MaybeUninit;
+
+pub(crate) use core::sync::atomic::Ordering;
+
+#[cfg(all(not(loom), feature = "portable-atomic"))]
+pub(crate) use portable_atomic::{AtomicBool, AtomicU8, AtomicUsize};
+
+#[cfg(all(
+    not(loom),
+    not(feature = "portable-atomic"),
+    not(target_has_atomic = "8")
+))]
+pub(crate) use crate::cs_atomic::{CsAtomicBool as AtomicBool, CsAtomicU8 as AtomicU8};
+#[cfg(all(not(loom), not(feature = "portable-atomic"), target_has_atomic = "8"))]
+pub(crate) use core::sync::atomic::{AtomicBool, AtomicU8};
+
+#[cfg(all(
+    not(loom),
+    not(feature = "portable-atomic"),
+    not(target_has_atomic = "ptr")
+))]
+pub(crate) use crate::cs_atomic::CsAtomicUsize as AtomicUsize;
+#[cfg(all(not(loom), not(feature = "portable-atomic"), target_has_atomic = "ptr"))]
+pub(crate) use core::sync::atomic::AtomicUsize;
+
+#[cfg(loom)]
+pub(crate) use loom::sync::atomic::{AtomicBool, AtomicU8, AtomicUsize};
+
+/// The atomics of the target, for primitives that only need loads and stores, or are only
+/// available on targets with compare-and-swap operations.
+pub(crate) mod native {
+    #[cfg(not(loom))]
+    pub(crate) use core::hint::spin_loop;
+    #[cfg(not(loom))]
+    pub(crate) use core::sync::atomic::{fence, AtomicBool, AtomicUsize};
+
+    #[cfg(loom)]
+    pub(crate) use loom::hint::spin_loop;
+    #[cfg(loom)]
+    pub(crate) use loom::sync::atomic::{fence, AtomicBool, AtomicUsize};
+}
+
+/// A `core::cell::UnsafeCell` with the closure-based interface of `loom`'s `UnsafeCell`.
+#[cfg(not(loom))]
+#[derive(Debug)]
+#[repr(transparent)]
+pub(crate) struct UnsafeCell<T>(core::cell::UnsafeCell<T>);
+
+#[cfg(not(loom))]
+impl<T> UnsafeCell<T> {
+    #[inline(always)]
+    pub(crate) const fn new(value: T) -> Self {
+        UnsafeCell(core::cell::UnsafeCell::new(value))
+    }
+
+    #[inline(always)]
+    pub(crate) fn with<R>(&self, f: impl FnOnce(*const T) -> R) -> R {
+        f(self.0.get())
+    }
+
+    #[inline(always)]
+    pub(crate) fn with_mut<R>(&self, f: impl FnOnce(*mut T) -> R) -> R {
+        f(self.0.get())
+    }
+
+    // only used by `SpinLock`
+    #[cfg_attr(not(target_has_atomic = "8"), allow(dead_code))]
+    #[inline(always)]
+    pub(crate) fn into_inner(self) -> T {
+        self.0.into_inner()
+    }
+}
+
+#[cfg(loom)]
+pub(crate) use loom::cell::UnsafeCell;
+
+/// Returns `N` cells holding uninitialized values.
+#[cfg(not(loom))]
+pub(crate) const fn uninit_cells<T, const N: usize>() -> [UnsafeCell<MaybeUninit<T>>; N] {
+    struct Uninit<T>(core::marker::PhantomData<T>);
+
+    impl<T> Uninit<T> {
+        #[allow(clippy::declare_interior_mutable_const)]
+        const CELL: UnsafeCell<MaybeUninit<T>> = UnsafeCell::new(MaybeUninit::uninit());
+    }
+
+    [Uninit::<T>::CELL; N]
+}
+
+/// Returns `N` cells holding uninitialized values.
+#[cfg(loom)]
+pub(crate) fn uninit_cells<T, const N: usize>() -> [UnsafeCell<MaybeUninit<T>>; N] {
+    core::array::from_fn(|_| UnsafeCell::new(MaybeUninit::uninit()))
+}
@@ -0,0 +1,99 @@
+use crate::atomic::{AtomicUsize, Ordering};
+use core::fmt;
+use core::marker::PhantomData;
+
+/// How a core waits for an event from another core.
+///
+/// The default, [`Spin`], busy-waits. An architecture crate can instead put the core to sleep,
+/// e.g. with `WFE` in [`wait`](WaitHook::wait) and `SEV` in [`notify`](WaitHook::notify).
+pub trait WaitHook {
+    /// Called repeatedly while waiting.
+    fn wait();
+
+    /// Called after the event other cores are waiting for has happened.
+    fn notify();
+}
+
+/// A [`WaitHook`] that busy-waits.
+#[derive(Debug)]
+pub struct Spin;
+
+impl WaitHook for Spin {
+    #[inline]
+    fn wait() {
+        core::hint::spin_loop();
+    }
+
+    #[inline]
+    fn notify() {}
+}
+
+/// A rendezvous point for `N` cores.
+///
+/// Every participant calls [`wait`](Barrier::wait), which returns once all `N` of them have done
+/// so. The barrier can then be reused for the next rendezvous, e.g. for every stage of a
+/// multi-core bring-up.
+///
+/// ```
+/// use bare_metal::Barrier;
+///
+/// static STAGE: Barrier<1> = Barrier::new();
+///
+/// // Exactly one participant of each rendezvous is the leader
+/// assert!(STAGE.wait());
+/// assert!(STAGE.wait());
+/// ```
+pub struct Barrier<const N: usize, W = Spin> {
+    arrived: AtomicUsize,
+    generation: AtomicUsize,
+    _hook: PhantomData<fn() -> W>,
+}
+
+impl<const N: usize, W> Barrier<N, W> {
+    const_fn! {
+        /// Creates a new barrier for `N` participants.
+        #[inline]
+        pub const fn new() -> Self {
+            Barrier {
+                arrived: AtomicUsize::new(0),
+                generation: AtomicUsize::new(0),
+                _hook: PhantomData,
+            }
+        }
+    }
+}
+
+impl<const N: usize, W: WaitHook> Barrier<N, W> {
+    /// Blocks until all `N` participants have called this method.
+    ///
+    /// Returns `true` for exactly one participant, the last one to arrive.
+    pub fn wait(&self) -> bool {
+        let generation = self.generation.load(Ordering::Acquire);
+        if self.arrived.fetch_add(1, Ordering::AcqRel) + 1 >= N {
+            self.arrived.store(0, Ordering::Relaxed);
+            self.generation
+                .store(generation.wrapping_add(1), Ordering::Release);
+            W::notify();
+            true
+        } else {
+            while self.generation.load(Ordering::Acquire) == generation {
+                W::wait();
+            }
+            false
+        }
+    }
+}
+
+impl<const N: usize, W> Default for Barrier<N, W> {
+    fn default() -> Self {
+        Barrier::new()
+    }
+}
+
+impl<const N: usize, W> fmt::Debug for Barrier<N, W> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("Barrier")
+            .field("participants", &N)
+            .finish_non_exhaustive()
+    }
+}
@@ -0,0 +1,357 @@
+//! A lock-free single-producer single-consumer byte buffer with contiguous grants.
+//!
+//! Unlike [`spsc::Queue`](crate::spsc::Queue), which moves one element at a time, a
+//! [`BipBuffer`] hands out contiguous slices of its memory: the producer asks for a *write
+//! grant* of some bytes, fills them, e.g. with a DMA transfer or in a UART interrupt handler,
+//! and commits the bytes it wrote. The consumer then gets a *read grant* of all committed bytes
+//! up to the end of the buffer, and releases the bytes it has processed. This is the algorithm
+//! of the `bbqueue` crate, which wraps around by leaving the end of the buffer unused whenever a
+//! grant doesn't fit.
+//!
+//! The producer and the consumer can run in different contexts without any critical section.
+//! The buffer only uses atomic loads and stores, so it also works on targets without atomic
+//! compare-and-swap operations.
+//!
+//! ```
+//! use bare_metal::bip::BipBuffer;
+//!
+//! let mut buffer: BipBuffer<8> = BipBuffer::new();
+//! let (mut producer, mut consumer) = buffer.split();
+//!
+//! // In the UART receive interrupt handler
+//! let mut grant = producer.grant_exact(4).unwrap();
+//! grant.copy_from_slice(b"ping");
+//! grant.commit(4);
+//!
+//! // In the main loop
+//! let grant = consumer.read().unwrap();
+//! assert_eq!(&*grant, b"ping");
+//! grant.release(4);
+//! assert!(consumer.read().is_none());
+//! ```
+
+use core::cell::UnsafeCell;
+use core::fmt;
+use core::marker::PhantomData;
+use core::ops::{Deref, DerefMut};
+use core::slice;
+
+use crate::atomic::native::AtomicUsize;
+use crate::atomic::Ordering;
+
+/// A lock-free single-producer single-consumer buffer of `N` bytes.
+pub struct BipBuffer<const N: usize> {
+    // index of the next byte to write; only written by the producer
+    write: AtomicUsize,
+    // index of the next byte to read; only written by the consumer
+    read: AtomicUsize,
+    // end of the committed bytes while the end of the buffer is skipped, `N` otherwise; only
+    // written by the producer
+    last: AtomicUsize,
+    // a plain cell, since the grants are disjoint slices of it that are used at the same time,
+    // which `loom` would report as a race on the whole cell
+    buffer: UnsafeCell<[u8; N]>,
+}
+
+impl<const N: usize> BipBuffer<N> {
+    const_fn! {
+        /// Creates an empty buffer.
+        #[inline]
+        pub const fn new() -> Self {
+            BipBuffer {
+                write: AtomicUsize::new(0),
+                read: AtomicUsize::new(0),
+                last: AtomicUsize::new(0),
+                buffer: UnsafeCell::new([0; N]),
+            }
+        }
+    }
+
+    /// Returns the size of the buffer in bytes.
+    #[inline]
+    pub const fn capacity(&self) -> usize {
+        N
+    }
+
+    /// Splits the buffer into its producer and consumer halves.
+    #[inline]
+    pub fn split(&mut self) -> (Producer<'_, N>, Consumer<'_, N>) {
+        (
+            Producer {
+                buffer: self,
+                _not_sync: PhantomData,
+            },
+            Consumer {
+                buffer: self,
+                _not_sync: PhantomData,
+            },
+        )
+    }
+
+    // SAFETY: the bytes must not be accessed through any other slice while this one is alive
+    #[allow(clippy::mut_from_ref)]
+    unsafe fn bytes(&self, start: usize, len: usize) -> &mut [u8] {
+        slice::from_raw_parts_mut(self.buffer.get().cast::<u8>().add(start), len)
+    }
+
+    // returns the start of a write grant of `len` bytes, or at most `len` bytes if `exact` is
+    // false
+    //
+    // SAFETY: must only be called by the single producer, while it has no write grant
+    unsafe fn inner_grant(&self, len: usize, exact: bool) -> Option<(usize, usize)> {
+        let write = self.write.load(Ordering::Relaxed);
+        let read = self.read.load(Ordering::Acquire);
+        if write < read {
+            // the grant must end before `read`, or a full buffer would look empty
+            let free = read - write - 1;
+            match exact {
+                true if len <= free => Some((write, len)),
+                false if free > 0 => Some((write, len.min(free))),
+                _ => None,
+            }
+        } else if exact {
+            if len <= N - write {
+                Some((write, len))
+            } else if len < read {
+                // wrap around, leaving the end of the buffer unused
+                Some((0, len))
+            } else {
+                None
+            }
+        } else if write != N {
+            Some((write, len.min(N - write)))
+        } else if read > 1 {
+            Some((0, len.min(read - 1)))
+        } else {
+            None
+        }
+    }
+
+    // SAFETY: must only be called by the single producer, for its current write grant
+    unsafe fn inner_commit(&self, start: usize, used: usize) {
+        let write = self.write.load(Ordering::Relaxed);
+        let end = start + used;
+        if end < write && write != N {
+            // the grant wrapped around, so the bytes from `write` to the end are skipped
+            self.last.store(write, Ordering::Release);
+        } else if end > self.last.load(Ordering::Relaxed) {
+            // the skipped bytes have been read, and the end of the buffer is in use again
+            self.last.store(N, Ordering::Release);
+        }
+        self.write.store(end, Ordering::Release);
+    }
+
+    // returns the start and length of a read grant
+    //
+    // SAFETY: must only be called by the single consumer, while it has no read grant
+    unsafe fn inner_read(&self) -> Option<(usize, usize)> {
+        let write = self.write.load(Ordering::Acquire);
+        let last = self.last.load(Ordering::Acquire);
+        let mut read = self.read.load(Ordering::Relaxed);
+        if read == last && write < read {
+            // all bytes up to the skipped end have been read, continue at the start
+            read = 0;
+            self.read.store(0, Ordering::Release);
+        }
+        let end = if write < read { last } else { write };
+        match end - read {
+            0 => None,
+            len => Some((read, len)),
+        }
+    }
+}
+
+impl<const N: usize> Default for BipBuffer<N> {
+    fn default() -> Self {
+        BipBuffer::new()
+    }
+}
+
+impl<const N: usize> fmt::Debug for BipBuffer<N> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("BipBuffer").finish_non_exhaustive()
+    }
+}
+
+/// The producer half of a [`BipBuffer`].
+pub struct Producer<'a, const N: usize> {
+    buffer: &'a BipBuffer<N>,
+    _not_sync: PhantomData<*mut ()>,
+}
+
+impl<'a, const N: usize> Producer<'a, N> {
+    /// Grants exactly `len` contiguous bytes to write to, or returns `None` if there isn't
+    /// enough contiguous free space.
+    #[inline]
+    pub fn grant_exact(&mut self, len: usize) -> Option<GrantW<'_, 'a, N>> {
+        // SAFETY: there is only one producer, and it is borrowed mutably by the grant
+        let (start, len) = unsafe { self.buffer.inner_grant(len, true) }?;
+        Some(GrantW {
+            producer: self,
+            start,
+            len,
+        })
+    }
+
+    /// Grants up to `len` contiguous bytes to write to, or returns `None` if there is no free
+    /// space.
+    #[inline]
+    pub fn grant_max(&mut self, len: usize) -> Option<GrantW<'_, 'a, N>> {
+        // SAFETY: there is only one producer, and it is borrowed mutably by the grant
+        let (start, len) = unsafe { self.buffer.inner_grant(len, false) }?;
+        Some(GrantW {
+            producer: self,
+            start,
+            len,
+        })
+    }
+}
+
+impl<const N: usize> fmt::Debug for Producer<'_, N> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("Producer").finish_non_exhaustive()
+    }
+}
+
+// NOTE the producer only writes to granted bytes, which the consumer doesn't access until they
+// are committed
+unsafe impl<const N: usize> Send for Producer<'_, N> {}
+
+/// Bytes of a [`BipBuffer`] granted to the [`Producer`].
+///
+/// The grant dereferences to the granted bytes. Dropping it without calling
+/// [`commit`](GrantW::commit) commits none of them.
+pub struct GrantW<'p, 'a, const N: usize> {
+    producer: &'p mut Producer<'a, N>,
+    start: usize,
+    len: usize,
+}
+
+impl<const N: usize> GrantW<'_, '_, N> {
+    /// Makes the first `used` bytes of the grant available to the consumer.
+    ///
+    /// `used` is capped at the length of the grant.
+    #[inline]
+    pub fn commit(self, used: usize) {
+        let used = used.min(self.len);
+        // SAFETY: there is only one producer, and this is its grant
+        unsafe { self.producer.buffer.inner_commit(self.start, used) };
+        core::mem::forget(self);
+    }
+}
+
+impl<const N: usize> Deref for GrantW<'_, '_, N> {
+    type Target = [u8];
+
+    #[inline]
+    fn deref(&self) -> &[u8] {
+        // SAFETY: the consumer doesn't access the granted bytes
+        unsafe { self.producer.buffer.bytes(self.start, self.len) }
+    }
+}
+
+impl<const N: usize> DerefMut for GrantW<'_, '_, N> {
+    #[inline]
+    fn deref_mut(&mut self) -> &mut [u8] {
+        // SAFETY: the consumer doesn't access the granted bytes
+        unsafe { self.producer.buffer.bytes(self.start, self.len) }
+    }
+}
+
+impl<const N: usize> Drop for GrantW<'_, '_, N> {
+    fn drop(&mut self) {
+        // SAFETY: there is only one producer, and this is its grant
+        unsafe { self.producer.buffer.inner_commit(self.start, 0) };
+    }
+}
+
+impl<const N: usize> fmt::Debug for GrantW<'_, '_, N> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("GrantW")
+            .field("len", &self.len)
+            .finish_non_exhaustive()
+    }
+}
+
+/// The consumer half of a [`BipBuffer`].
+pub struct Consumer<'a, const N: usize> {
+    buffer: &'a BipBuffer<N>,
+    _not_sync: PhantomData<*mut ()>,
+}
+
+impl<'a, const N: usize> Consumer<'a, N> {
+    /// Grants the committed bytes up to the end of the buffer, or returns `None` if there are
+    /// none.
+    ///
+    /// Bytes that were committed after wrapping around are granted once the bytes before the
+    /// end of the buffer have been released.
+    #[inline]
+    pub fn read(&mut self) -> Option<GrantR<'_, 'a, N>> {
+        // SAFETY: there is only one consumer, and it is borrowed mutably by the grant
+        let (start, len) = unsafe { self.buffer.inner_read() }?;
+        Some(GrantR {
+            consumer: self,
+            start,
+            len,
+        })
+    }
+}
+
+impl<const N: usize> fmt::Debug for Consumer<'_, N> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("Consumer").finish_non_exhaustive()
+    }
+}
+
+// NOTE see `Producer`
+unsafe impl<const N: usize> Send for Consumer<'_, N> {}
+
+/// Committed bytes of a [`BipBuffer`] granted to the [`Consumer`].
+///
+/// The grant dereferences to the granted bytes. Dropping it without calling
+/// [`release`](GrantR::release) releases none of them.
+pub struct GrantR<'c, 'a, const N: usize> {
+    consumer: &'c mut Consumer<'a, N>,
+    start: usize,
+    len: usize,
+}
+
+impl<const N: usize> GrantR<'_, '_, N> {
+    /// Gives the first `used` bytes of the grant back to the producer.
+    ///
+    /// `used` is capped at the length of the grant.
+    #[inline]
+    pub fn release(self, used: usize) {
+        let used = used.min(self.len);
+        self.consumer
+            .buffer
+            .read
+            .store(self.start + used, Ordering::Release);
+    }
+}
+
+impl<const N: usize> Deref for GrantR<'_, '_, N> {
+    type Target = [u8];
+
+    #[inline]
+    fn deref(&self) -> &[u8] {
+        // SAFETY: the producer doesn't write to committed bytes until they are released
+        unsafe { self.consumer.buffer.bytes(self.start, self.len) }
+    }
+}
+
+impl<const N: usize> DerefMut for GrantR<'_, '_, N> {
+    #[inline]
+    fn deref_mut(&mut self) -> &mut [u8] {
+        // SAFETY: the producer doesn't access committed bytes until they are released
+        unsafe { self.consumer.buffer.bytes(self.start, self.len) }
+    }
+}
+
+impl<const N: usize> fmt::Debug for GrantR<'_, '_, N> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("GrantR")
+            .field("len", &self.len)
+            .finish_non_exhaustive()
+    }
+}
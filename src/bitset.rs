@@ -0,0 +1,204 @@
+use core::fmt;
+
+use crate::atomic::{AtomicUsize, Ordering};
+
+const BITS: usize = usize::BITS as usize;
+
+/// A set of `WORDS * usize::BITS` bits that can be modified from any context.
+///
+/// This is the bookkeeping for "which channels need servicing": interrupt handlers
+/// [`set`](AtomicBitSet::set) the bit of a channel, and the main loop or a task
+/// [`drain`](AtomicBitSet::drain)s the set to service them. Every operation is lock-free on
+/// targets with atomic compare-and-swap operations; on other targets each word is only accessed
+/// inside short critical sections, unless the `portable-atomic` feature is enabled.
+///
+/// ```
+/// use bare_metal::AtomicBitSet;
+///
+/// static PENDING: AtomicBitSet<2> = AtomicBitSet::new();
+///
+/// // In the interrupt handlers
+/// PENDING.set(3);
+/// PENDING.set(40);
+///
+/// // In the main loop
+/// assert!(PENDING.test(40));
+/// let mut serviced = [0; 2];
+/// for (slot, channel) in serviced.iter_mut().zip(PENDING.drain()) {
+///     *slot = channel;
+/// }
+/// assert_eq!(serviced, [3, 40]);
+/// assert!(PENDING.is_empty());
+/// ```
+pub struct AtomicBitSet<const WORDS: usize> {
+    words: [AtomicUsize; WORDS],
+}
+
+impl<const WORDS: usize> AtomicBitSet<WORDS> {
+    /// Creates an empty set.
+    #[cfg(not(loom))]
+    #[inline]
+    pub const fn new() -> Self {
+        #[allow(clippy::declare_interior_mutable_const)]
+        const EMPTY: AtomicUsize = AtomicUsize::new(0);
+        AtomicBitSet {
+            words: [EMPTY; WORDS],
+        }
+    }
+
+    /// Creates an empty set.
+    #[cfg(loom)]
+    pub fn new() -> Self {
+        AtomicBitSet {
+            words: core::array::from_fn(|_| AtomicUsize::new(0)),
+        }
+    }
+
+    /// Returns the number of bits in the set.
+    #[inline]
+    pub const fn capacity(&self) -> usize {
+        WORDS * BITS
+    }
+
+    /// Sets bit `index`, returning whether it was already set.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `index` is out of range.
+    #[inline]
+    #[track_caller]
+    pub fn set(&self, index: usize) -> bool {
+        let (word, mask) = self.locate(index);
+        word.fetch_or(mask, Ordering::AcqRel) & mask != 0
+    }
+
+    /// Clears bit `index`, returning whether it was set.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `index` is out of range.
+    #[inline]
+    #[track_caller]
+    pub fn clear(&self, index: usize) -> bool {
+        let (word, mask) = self.locate(index);
+        word.fetch_and(!mask, Ordering::AcqRel) & mask != 0
+    }
+
+    /// Returns whether bit `index` is set.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `index` is out of range.
+    #[inline]
+    #[track_caller]
+    pub fn test(&self, index: usize) -> bool {
+        let (word, mask) = self.locate(index);
+        word.load(Ordering::Acquire) & mask != 0
+    }
+
+    /// Returns `true` if no bit is set.
+    #[inline]
+    pub fn is_empty(&self) -> bool {
+        self.words
+            .iter()
+            .all(|word| word.load(Ordering::Acquire) == 0)
+    }
+
+    /// Clears all bits.
+    #[inline]
+    pub fn clear_all(&self) {
+        for word in &self.words {
+            word.store(0, Ordering::Release);
+        }
+    }
+
+    /// Returns an iterator that clears the set bits and yields their indices in ascending order.
+    ///
+    /// Each word is cleared in one atomic operation when the iterator reaches it, so bits that
+    /// are set in words that haven't been reached yet are yielded as well. If the iterator is
+    /// dropped early, the bits it hasn't yielded stay set.
+    #[inline]
+    pub fn drain(&self) -> BitSetDrain<'_, WORDS> {
+        BitSetDrain {
+            set: self,
+            next_word: 0,
+            bits: 0,
+        }
+    }
+
+    #[inline]
+    #[track_caller]
+    fn locate(&self, index: usize) -> (&AtomicUsize, usize) {
+        match self.words.get(index / BITS) {
+            Some(word) => (word, 1 << (index % BITS)),
+            None => index_out_of_range(index, self.capacity()),
+        }
+    }
+}
+
+#[cold]
+#[track_caller]
+fn index_out_of_range(index: usize, capacity: usize) -> ! {
+    panic!(
+        "bit index {} out of range for a set of {} bits",
+        index, capacity
+    )
+}
+
+impl<const WORDS: usize> Default for AtomicBitSet<WORDS> {
+    fn default() -> Self {
+        AtomicBitSet::new()
+    }
+}
+
+impl<const WORDS: usize> fmt::Debug for AtomicBitSet<WORDS> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let mut set = f.debug_set();
+        for (i, word) in self.words.iter().enumerate() {
+            let mut bits = word.load(Ordering::Relaxed);
+            while bits != 0 {
+                set.entry(&(i * BITS + bits.trailing_zeros() as usize));
+                bits &= bits - 1;
+            }
+        }
+        set.finish()
+    }
+}
+
+/// An iterator that clears the bits of an [`AtomicBitSet`], created by
+/// [`drain`](AtomicBitSet::drain).
+pub struct BitSetDrain<'a, const WORDS: usize> {
+    set: &'a AtomicBitSet<WORDS>,
+    next_word: usize,
+    // bits of the word before `next_word` that have been cleared but not yielded yet
+    bits: usize,
+}
+
+impl<const WORDS: usize> Iterator for BitSetDrain<'_, WORDS> {
+    type Item = usize;
+
+    fn next(&mut self) -> Option<usize> {
+        while self.bits == 0 {
+            let word = self.set.words.get(self.next_word)?;
+            self.bits = word.swap(0, Ordering::AcqRel);
+            self.next_word += 1;
+        }
+        let bit = self.bits.trailing_zeros() as usize;
+        self.bits &= self.bits - 1;
+        Some((self.next_word - 1) * BITS + bit)
+    }
+}
+
+impl<const WORDS: usize> Drop for BitSetDrain<'_, WORDS> {
+    fn drop(&mut self) {
+        if self.bits != 0 {
+            self.set.words[self.next_word - 1].fetch_or(self.bits, Ordering::AcqRel);
+        }
+    }
+}
+
+impl<const WORDS: usize> fmt::Debug for BitSetDrain<'_, WORDS> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("BitSetDrain").finish_non_exhaustive()
+    }
+}
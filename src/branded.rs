@@ -0,0 +1,148 @@
+use core::cell::UnsafeCell;
+use core::fmt;
+use core::marker::PhantomData;
+
+use critical_section::CriticalSection;
+
+// invariant in `'brand`, so that brands can't be converted into each other
+type Brand<'brand> = PhantomData<fn(&'brand ()) -> &'brand ()>;
+
+/// The unique key to all [`BrandedMutex`]es of a brand.
+///
+/// There is exactly one token per brand, and it can't be copied, so borrowing the token
+/// mutably proves that nothing else accesses the mutexes of its brand. Brands are created by
+/// [`scope`](BrandToken::scope), like a `GhostToken`.
+pub struct BrandToken<'brand> {
+    _brand: Brand<'brand>,
+}
+
+impl BrandToken<'_> {
+    /// Calls `f` with the token of a new, unique brand.
+    #[inline]
+    pub fn scope<R>(f: impl for<'brand> FnOnce(BrandToken<'brand>) -> R) -> R {
+        f(BrandToken {
+            _brand: PhantomData,
+        })
+    }
+}
+
+impl BrandToken<'static> {
+    /// Creates the token of the `'static` brand, for [`BrandedMutex`]es in `static`s.
+    ///
+    /// # Safety
+    ///
+    /// At most one `'static` token may exist in the whole program; e.g. keep it in a
+    /// [`Singleton`](crate::Singleton).
+    #[inline]
+    pub const unsafe fn new_static() -> Self {
+        BrandToken {
+            _brand: PhantomData,
+        }
+    }
+}
+
+impl fmt::Debug for BrandToken<'_> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str("BrandToken")
+    }
+}
+
+/// A mutex that is unlocked by a critical section together with a [`BrandToken`].
+///
+/// `Mutex<RefCell<T>>` needs a run-time borrow flag because critical section tokens can be
+/// copied. Here, mutable access also requires the token of the mutex's brand borrowed mutably,
+/// which the compiler already proves to be unique, so there is no flag and no check.
+///
+/// ```
+/// use bare_metal::{BrandToken, BrandedMutex};
+/// use critical_section::CriticalSection;
+///
+/// BrandToken::scope(|mut token| {
+///     let rx = BrandedMutex::new(0u32);
+///     let tx = BrandedMutex::new(0u32);
+///
+///     let cs = unsafe { CriticalSection::new() };
+///     *rx.borrow_mut(cs, &mut token) += 1;
+///     *tx.borrow_mut(cs, &mut token) += *rx.borrow(cs, &token);
+///     assert_eq!(*tx.borrow(cs, &token), 1);
+/// });
+/// ```
+///
+/// Holding on to a mutable borrow while borrowing again fails to compile:
+///
+/// ```compile_fail
+/// use bare_metal::{BrandToken, BrandedMutex};
+/// use critical_section::CriticalSection;
+///
+/// BrandToken::scope(|mut token| {
+///     let value = BrandedMutex::new(0u32);
+///     let cs = unsafe { CriticalSection::new() };
+///     let a = value.borrow_mut(cs, &mut token);
+///     let b = value.borrow_mut(cs, &mut token);
+///     *a += *b;
+/// });
+/// ```
+pub struct BrandedMutex<'brand, T> {
+    value: UnsafeCell<T>,
+    _brand: Brand<'brand>,
+}
+
+impl<'brand, T> BrandedMutex<'brand, T> {
+    /// Creates a new `BrandedMutex` of the brand `'brand`.
+    #[inline]
+    pub const fn new(value: T) -> Self {
+        BrandedMutex {
+            value: UnsafeCell::new(value),
+            _brand: PhantomData,
+        }
+    }
+
+    /// Borrows the data for as long as the critical section and the token borrow last.
+    #[inline]
+    pub fn borrow<'a>(&'a self, _cs: CriticalSection<'a>, _token: &'a BrandToken<'brand>) -> &'a T {
+        // SAFETY: mutable borrows need the token borrowed mutably, so none can exist now
+        unsafe { &*self.value.get() }
+    }
+
+    /// Mutably borrows the data for as long as the critical section and the token borrow last.
+    #[inline]
+    #[allow(clippy::mut_from_ref)]
+    pub fn borrow_mut<'a>(
+        &'a self,
+        _cs: CriticalSection<'a>,
+        _token: &'a mut BrandToken<'brand>,
+    ) -> &'a mut T {
+        // SAFETY: the token of the brand is unique and borrowed mutably, so no other borrow of
+        // a mutex of this brand can exist now
+        unsafe { &mut *self.value.get() }
+    }
+
+    /// Returns a mutable reference to the data.
+    #[inline]
+    pub fn get_mut(&mut self) -> &mut T {
+        self.value.get_mut()
+    }
+
+    /// Consumes the mutex, returning the data.
+    #[inline]
+    pub fn into_inner(self) -> T {
+        self.value.into_inner()
+    }
+}
+
+impl<T: Default> Default for BrandedMutex<'_, T> {
+    fn default() -> Self {
+        BrandedMutex::new(T::default())
+    }
+}
+
+impl<T> fmt::Debug for BrandedMutex<'_, T> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("BrandedMutex").finish_non_exhaustive()
+    }
+}
+
+// NOTE every borrow requires a critical section, which serializes all accesses, and mutable
+// borrows are unique because of the token. As with `Mutex`, the data must be `Send` because it
+// can be accessed from a different context.
+unsafe impl<T> Sync for BrandedMutex<'_, T> where T: Send {}
@@ -0,0 +1,104 @@
+use core::cell::RefCell;
+use core::fmt;
+
+use critical_section::Mutex;
+
+use crate::MutexRefCellExt;
+
+/// Shares one bus peripheral, e.g. an I2C or SPI controller, between several drivers.
+///
+/// Each driver gets its own [`BusHandle`] from [`acquire`](BusManager::acquire). Every access
+/// through a handle happens inside a critical section, so drivers running in different
+/// interrupt handlers can share the bus safely.
+///
+/// ```
+/// use bare_metal::BusManager;
+///
+/// struct I2c {
+///     transfers: u32,
+/// }
+///
+/// static I2C: BusManager<I2c> = BusManager::new(I2c { transfers: 0 });
+///
+/// let sensor = I2C.acquire();
+/// let eeprom = I2C.acquire();
+/// sensor.lock(|i2c| i2c.transfers += 1);
+/// eeprom.lock(|i2c| i2c.transfers += 1);
+/// assert_eq!(sensor.lock(|i2c| i2c.transfers), 2);
+/// ```
+pub struct BusManager<T> {
+    bus: Mutex<RefCell<T>>,
+}
+
+impl<T> BusManager<T> {
+    /// Creates a new `BusManager` that owns `bus`.
+    #[inline]
+    pub const fn new(bus: T) -> Self {
+        BusManager {
+            bus: Mutex::new(RefCell::new(bus)),
+        }
+    }
+
+    /// Returns a new handle to the bus.
+    #[inline]
+    pub fn acquire(&self) -> BusHandle<'_, T> {
+        BusHandle { bus: &self.bus }
+    }
+
+    /// Returns a mutable reference to the bus.
+    ///
+    /// This does not need a critical section since `&mut self` already guarantees exclusive
+    /// access.
+    #[inline]
+    pub fn get_mut(&mut self) -> &mut T {
+        self.bus.get_mut().get_mut()
+    }
+
+    /// Consumes the `BusManager`, returning the bus.
+    #[inline]
+    pub fn into_inner(self) -> T {
+        self.bus.into_inner().into_inner()
+    }
+}
+
+impl<T> fmt::Debug for BusManager<T> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("BusManager").finish_non_exhaustive()
+    }
+}
+
+/// A driver's handle to a bus shared through a [`BusManager`].
+///
+/// With the `embedded-hal` feature, a handle to an I2C bus implements `embedded_hal::i2c::I2c`,
+/// so that it can be passed to drivers in place of the bus. For SPI, wrap the handle in an
+/// [`SpiHandle`](crate::SpiHandle) together with the chip select pin of the device.
+pub struct BusHandle<'a, T> {
+    bus: &'a Mutex<RefCell<T>>,
+}
+
+impl<T> BusHandle<'_, T> {
+    /// Enters a critical section and calls `f` with the bus.
+    ///
+    /// # Panics
+    ///
+    /// Panics if called from within `f` of another `lock` call on a handle to the same bus.
+    #[inline]
+    #[track_caller]
+    pub fn lock<R>(&self, f: impl FnOnce(&mut T) -> R) -> R {
+        self.bus.lock(f)
+    }
+}
+
+impl<T> Clone for BusHandle<'_, T> {
+    fn clone(&self) -> Self {
+        *self
+    }
+}
+
+impl<T> Copy for BusHandle<'_, T> {}
+
+impl<T> fmt::Debug for BusHandle<'_, T> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("BusHandle").finish_non_exhaustive()
+    }
+}
@@ -0,0 +1,85 @@
+use core::fmt;
+use core::ops::{Deref, DerefMut};
+
+use crate::align::{Align64, Alignment};
+
+/// Pads and aligns a value to the length of a cache line.
+///
+/// On multi-core systems with caches, two locks in the same cache line false-share: every time
+/// one core takes its lock, the other core's copy of the line is invalidated. Wrapping hot locks
+/// in `CachePadded` puts each of them in a cache line of its own.
+///
+/// The alignment, and with it the assumed cache line length, is `A`, one of the markers in the
+/// [`align`](crate::align) module. The default is 64 bytes.
+///
+/// ```
+/// use bare_metal::align::Align32;
+/// use bare_metal::{CachePadded, SpinLock};
+///
+/// struct Shared {
+///     rx: CachePadded<SpinLock<u32>>,
+///     tx: CachePadded<SpinLock<u32>, Align32>,
+/// }
+///
+/// static SHARED: Shared = Shared {
+///     rx: CachePadded::new(SpinLock::new(0)),
+///     tx: CachePadded::new(SpinLock::new(0)),
+/// };
+///
+/// *SHARED.rx.lock() += 1;
+/// assert_eq!(core::mem::align_of_val(&SHARED.rx), 64);
+/// assert_eq!(core::mem::size_of_val(&SHARED.tx), 32);
+/// ```
+#[repr(C)]
+pub struct CachePadded<T, A: Alignment = Align64> {
+    _align: [A; 0],
+    value: T,
+}
+
+impl<T, A: Alignment> CachePadded<T, A> {
+    /// Pads and aligns `value`.
+    #[inline]
+    pub const fn new(value: T) -> Self {
+        CachePadded { _align: [], value }
+    }
+
+    /// Consumes the wrapper, returning the value.
+    #[inline]
+    pub fn into_inner(self) -> T {
+        self.value
+    }
+}
+
+impl<T, A: Alignment> Deref for CachePadded<T, A> {
+    type Target = T;
+
+    #[inline]
+    fn deref(&self) -> &T {
+        &self.value
+    }
+}
+
+impl<T, A: Alignment> DerefMut for CachePadded<T, A> {
+    #[inline]
+    fn deref_mut(&mut self) -> &mut T {
+        &mut self.value
+    }
+}
+
+impl<T: Default, A: Alignment> Default for CachePadded<T, A> {
+    fn default() -> Self {
+        CachePadded::new(T::default())
+    }
+}
+
+impl<T, A: Alignment> From<T> for CachePadded<T, A> {
+    fn from(value: T) -> Self {
+        CachePadded::new(value)
+    }
+}
+
+impl<T: fmt::Debug, A: Alignment> fmt::Debug for CachePadded<T, A> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_tuple("CachePadded").field(&self.value).finish()
+    }
+}
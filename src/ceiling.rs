@@ -0,0 +1,474 @@
+use core::cell::{Cell, UnsafeCell};
+use core::fmt;
+use core::marker::PhantomData;
+
+use critical_section::CriticalSection;
+
+/// A way to mask interrupts up to a priority threshold, e.g. `BASEPRI` on ARMv7-M.
+///
+/// Priorities are *logical*: a higher number means a more urgent interrupt, 0 is the priority of
+/// thread mode. Implementations convert them to the encoding used by the hardware.
+///
+/// # Safety
+///
+/// After [`raise`](PriorityThreshold::raise) returns, no interrupt with a logical priority of
+/// `ceiling` or lower may preempt the current context until the matching
+/// [`restore`](PriorityThreshold::restore) call. Interrupts with a higher priority may still
+/// run.
+pub unsafe trait PriorityThreshold {
+    /// Raises the current priority threshold to at least `ceiling` and returns the previous
+    /// threshold.
+    ///
+    /// If the threshold is already `ceiling` or higher it is left unchanged.
+    fn raise(ceiling: u8) -> u8;
+
+    /// Restores a threshold returned by [`raise`](PriorityThreshold::raise).
+    ///
+    /// # Safety
+    ///
+    /// Calls to `raise` and `restore` must be properly nested, and `previous` must be the value
+    /// returned by the matching `raise` call.
+    unsafe fn restore(previous: u8);
+}
+
+/// A mutex that implements the immediate priority ceiling protocol.
+///
+/// Instead of masking all interrupts, [`lock`](CeilingMutex::lock) only raises the priority
+/// threshold to `CEILING`, the highest priority of all contexts that access the data, using the
+/// backend `P`. Interrupts with a higher priority, which never touch the data, keep running.
+///
+/// This only protects the data from other contexts on the same core.
+///
+/// ```
+/// use bare_metal::{CeilingMutex, PriorityThreshold};
+/// use core::sync::atomic::{AtomicU8, Ordering};
+///
+/// static THRESHOLD: AtomicU8 = AtomicU8::new(0);
+///
+/// // a stand-in for a real backend, e.g. one writing to `BASEPRI`
+/// struct Basepri;
+///
+/// unsafe impl PriorityThreshold for Basepri {
+///     fn raise(ceiling: u8) -> u8 {
+///         THRESHOLD.fetch_max(ceiling, Ordering::SeqCst)
+///     }
+///
+///     unsafe fn restore(previous: u8) {
+///         THRESHOLD.store(previous, Ordering::SeqCst)
+///     }
+/// }
+///
+/// // SAFETY: only thread mode and the interrupt handlers of priority 2 or less use `COUNTER`
+/// static COUNTER: CeilingMutex<u32, Basepri, 2> = unsafe { CeilingMutex::new(0) };
+///
+/// COUNTER.lock(|counter| *counter += 1);
+/// assert_eq!(COUNTER.lock(|counter| *counter), 1);
+/// ```
+pub struct CeilingMutex<T, P, const CEILING: u8> {
+    locked: Cell<bool>,
+    value: UnsafeCell<T>,
+    _backend: PhantomData<fn() -> P>,
+}
+
+impl<T, P, const CEILING: u8> CeilingMutex<T, P, CEILING> {
+    /// Creates a new `CeilingMutex`.
+    ///
+    /// # Safety
+    ///
+    /// The mutex must only be accessed from a single core, and only from contexts whose logical
+    /// priority is `CEILING` or lower.
+    #[inline]
+    pub const unsafe fn new(value: T) -> Self {
+        CeilingMutex {
+            locked: Cell::new(false),
+            value: UnsafeCell::new(value),
+            _backend: PhantomData,
+        }
+    }
+
+    /// Returns a mutable reference to the data.
+    ///
+    /// This does not need to take the lock since `&mut self` already guarantees exclusive
+    /// access.
+    #[inline]
+    pub fn get_mut(&mut self) -> &mut T {
+        self.value.get_mut()
+    }
+
+    /// Consumes the `CeilingMutex`, returning the data.
+    #[inline]
+    pub fn into_inner(self) -> T {
+        self.value.into_inner()
+    }
+}
+
+impl<T, P: PriorityThreshold, const CEILING: u8> CeilingMutex<T, P, CEILING> {
+    /// Raises the priority threshold to `CEILING` and calls `f` with the data.
+    ///
+    /// # Panics
+    ///
+    /// Panics if the mutex is already locked, i.e. if `f` tries to lock it again.
+    #[track_caller]
+    pub fn lock<R>(&self, f: impl FnOnce(&mut T) -> R) -> R {
+        let previous = P::raise(CEILING);
+        if self.locked.replace(true) {
+            // SAFETY: matches the `raise` above
+            unsafe { P::restore(previous) };
+            panic!("CeilingMutex already locked");
+        }
+
+        struct Unlock<'a, P: PriorityThreshold> {
+            locked: &'a Cell<bool>,
+            previous: u8,
+            _backend: PhantomData<fn() -> P>,
+        }
+
+        impl<P: PriorityThreshold> Drop for Unlock<'_, P> {
+            fn drop(&mut self) {
+                self.locked.set(false);
+                // SAFETY: matches the `raise` in `lock`
+                unsafe { P::restore(self.previous) };
+            }
+        }
+
+        let _unlock = Unlock::<P> {
+            locked: &self.locked,
+            previous,
+            _backend: PhantomData,
+        };
+        // SAFETY: the threshold keeps all other contexts that access the data from running,
+        // and the flag rules out reentrant locking from within `f`
+        f(unsafe { &mut *self.value.get() })
+    }
+}
+
+impl<T, P, const CEILING: u8> fmt::Debug for CeilingMutex<T, P, CEILING> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("CeilingMutex")
+            .field("ceiling", &CEILING)
+            .finish_non_exhaustive()
+    }
+}
+
+// NOTE the flag and the data are only accessed with the threshold raised, which the contract of
+// `new` guarantees to exclude all other contexts using the mutex
+unsafe impl<T, P, const CEILING: u8> Sync for CeilingMutex<T, P, CEILING> where T: Send {}
+
+/// Proof that interrupts up to the logical priority `LEVEL` are masked.
+///
+/// This is the priority-threshold counterpart of a critical section token. It is created by
+/// [`with_threshold`] and grants access to the data in [`ThresholdMutex`]es whose ceiling is
+/// `LEVEL` or lower. A critical section masks all interrupts, so it can be turned into a token
+/// for the highest level with [`from_cs`](ThresholdToken::from_cs).
+#[derive(Clone, Copy)]
+pub struct ThresholdToken<'t, const LEVEL: u8> {
+    _lifetime: PhantomData<&'t ()>,
+    // the threshold only applies to the current core
+    _not_send: PhantomData<*mut ()>,
+}
+
+impl<'t> ThresholdToken<'t, { u8::MAX }> {
+    /// Returns a token for the highest level, which is implied by a critical section.
+    #[inline]
+    pub fn from_cs(_cs: CriticalSection<'t>) -> Self {
+        ThresholdToken {
+            _lifetime: PhantomData,
+            _not_send: PhantomData,
+        }
+    }
+}
+
+impl<'t, const LEVEL: u8> ThresholdToken<'t, LEVEL> {
+    /// Returns a token for a lower level, which is implied by this one.
+    ///
+    /// Trying to raise the level fails to compile.
+    #[inline]
+    pub fn lower<const TO: u8>(self) -> ThresholdToken<'t, TO> {
+        #[allow(clippy::let_unit_value)]
+        let () = AtLeast::<LEVEL, TO>::OK;
+        ThresholdToken {
+            _lifetime: PhantomData,
+            _not_send: PhantomData,
+        }
+    }
+}
+
+impl<const LEVEL: u8> fmt::Debug for ThresholdToken<'_, LEVEL> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("ThresholdToken")
+            .field("level", &LEVEL)
+            .finish()
+    }
+}
+
+struct AtLeast<const LEVEL: u8, const CEILING: u8>;
+
+impl<const LEVEL: u8, const CEILING: u8> AtLeast<LEVEL, CEILING> {
+    const OK: () = assert!(LEVEL >= CEILING, "the threshold is lower than required");
+}
+
+/// Raises the priority threshold to `LEVEL` using the backend `P` and calls `f` with a token.
+///
+/// ```
+/// use bare_metal::{with_threshold, PriorityThreshold, ThresholdMutex};
+/// use core::cell::Cell;
+/// # struct Basepri;
+/// # unsafe impl PriorityThreshold for Basepri {
+/// #     fn raise(ceiling: u8) -> u8 { 0 }
+/// #     unsafe fn restore(previous: u8) {}
+/// # }
+///
+/// // SAFETY: only thread mode and the interrupt handlers of priority 2 or less use `COUNTER`
+/// static COUNTER: ThresholdMutex<Cell<u32>, 2> = unsafe { ThresholdMutex::new(Cell::new(0)) };
+///
+/// with_threshold::<Basepri, 3, _>(|token| {
+///     let counter = COUNTER.borrow(token);
+///     counter.set(counter.get() + 1);
+/// });
+/// ```
+///
+/// A token for a level below the mutex's ceiling is rejected at compile time:
+///
+/// ```compile_fail
+/// use bare_metal::{with_threshold, PriorityThreshold, ThresholdMutex};
+/// use core::cell::Cell;
+/// # struct Basepri;
+/// # unsafe impl PriorityThreshold for Basepri {
+/// #     fn raise(ceiling: u8) -> u8 { 0 }
+/// #     unsafe fn restore(previous: u8) {}
+/// # }
+///
+/// static COUNTER: ThresholdMutex<Cell<u32>, 2> = unsafe { ThresholdMutex::new(Cell::new(0)) };
+///
+/// with_threshold::<Basepri, 1, _>(|token| COUNTER.borrow(token).set(1));
+/// ```
+pub fn with_threshold<P, const LEVEL: u8, R>(f: impl FnOnce(ThresholdToken<'_, LEVEL>) -> R) -> R
+where
+    P: PriorityThreshold,
+{
+    struct Restore<P: PriorityThreshold> {
+        previous: u8,
+        _backend: PhantomData<fn() -> P>,
+    }
+
+    impl<P: PriorityThreshold> Drop for Restore<P> {
+        fn drop(&mut self) {
+            // SAFETY: matches the `raise` in `with_threshold`
+            unsafe { P::restore(self.previous) };
+        }
+    }
+
+    let _restore = Restore::<P> {
+        previous: P::raise(LEVEL),
+        _backend: PhantomData,
+    };
+    f(ThresholdToken {
+        _lifetime: PhantomData,
+        _not_send: PhantomData,
+    })
+}
+
+/// A mutex that is unlocked by a [`ThresholdToken`] of level `CEILING` or higher.
+///
+/// This is like `critical_section::Mutex`, with a priority threshold in place of the critical
+/// section. It only adds `Sync`; use interior mutability, e.g. a `Cell` or `RefCell`, to modify
+/// the data. The minimum level is checked at compile time.
+pub struct ThresholdMutex<T, const CEILING: u8> {
+    value: T,
+}
+
+impl<T, const CEILING: u8> ThresholdMutex<T, CEILING> {
+    /// Creates a new `ThresholdMutex`.
+    ///
+    /// # Safety
+    ///
+    /// The mutex must only be accessed from a single core, and only from contexts whose logical
+    /// priority is `CEILING` or lower.
+    #[inline]
+    pub const unsafe fn new(value: T) -> Self {
+        ThresholdMutex { value }
+    }
+
+    /// Borrows the data for the lifetime of the token.
+    #[inline]
+    pub fn borrow<'t, const LEVEL: u8>(&'t self, _token: ThresholdToken<'t, LEVEL>) -> &'t T {
+        #[allow(clippy::let_unit_value)]
+        let () = AtLeast::<LEVEL, CEILING>::OK;
+        &self.value
+    }
+
+    /// Returns a mutable reference to the data.
+    #[inline]
+    pub fn get_mut(&mut self) -> &mut T {
+        &mut self.value
+    }
+
+    /// Consumes the mutex, returning the data.
+    #[inline]
+    pub fn into_inner(self) -> T {
+        self.value
+    }
+}
+
+impl<T, const CEILING: u8> fmt::Debug for ThresholdMutex<T, CEILING> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("ThresholdMutex")
+            .field("ceiling", &CEILING)
+            .finish_non_exhaustive()
+    }
+}
+
+// NOTE the data is only reachable with a token for the ceiling, which the contract of `new`
+// guarantees to exclude all other contexts using the mutex
+unsafe impl<T, const CEILING: u8> Sync for ThresholdMutex<T, CEILING> where T: Send {}
+
+/// Declares resources that are shared between contexts of different priorities, and the proxy
+/// types through which each context locks them.
+///
+/// This brings the resource model of RTIC to firmware that doesn't use RTIC. Each resource is
+/// declared with the logical priorities of all contexts that access it, and is stored in a
+/// [`CeilingMutex`] whose ceiling is the highest of them, using the [`PriorityThreshold`]
+/// backend given first. Locking a resource therefore only masks the interrupts that may
+/// actually touch it.
+///
+/// For each resource, the macro generates a proxy type `Name<const PRIORITY: u8>`. A context
+/// running at logical priority `PRIORITY` creates its proxy with the unsafe `new`, which fails to
+/// compile if `PRIORITY` isn't one of the declared priorities, and calls `lock` on it. The ceiling
+/// is available as `Name::<PRIORITY>::CEILING`. Proxies can't be sent to other contexts.
+///
+/// ```
+/// use bare_metal::{shared_resources, PriorityThreshold};
+/// use core::sync::atomic::{AtomicU8, Ordering};
+///
+/// static THRESHOLD: AtomicU8 = AtomicU8::new(0);
+///
+/// // a stand-in for a real backend, e.g. one writing to `BASEPRI`
+/// struct Basepri;
+///
+/// unsafe impl PriorityThreshold for Basepri {
+///     fn raise(ceiling: u8) -> u8 {
+///         THRESHOLD.fetch_max(ceiling, Ordering::SeqCst)
+///     }
+///
+///     unsafe fn restore(previous: u8) {
+///         THRESHOLD.store(previous, Ordering::SeqCst)
+///     }
+/// }
+///
+/// shared_resources! {
+///     backend: Basepri;
+///
+///     /// The number of timer ticks, counted by the priority 2 timer interrupt and read by
+///     /// thread mode.
+///     pub resource Ticks: u32 = 0 => [0, 2];
+/// }
+///
+/// fn timer_interrupt() {
+///     // SAFETY: the timer interrupt has logical priority 2
+///     let mut ticks = unsafe { Ticks::<2>::new() };
+///     ticks.lock(|ticks| *ticks += 1);
+/// }
+///
+/// # timer_interrupt();
+/// // SAFETY: this is thread mode
+/// let mut ticks = unsafe { Ticks::<0>::new() };
+/// assert_eq!(Ticks::<0>::CEILING, 2);
+/// ticks.lock(|ticks| {
+///     // the timer interrupt is masked, but more urgent ones are not
+///     assert_eq!(THRESHOLD.load(Ordering::SeqCst), 2);
+///     assert_eq!(*ticks, 1);
+/// });
+/// ```
+///
+/// Creating a proxy for a priority the resource isn't declared for is rejected at compile time:
+///
+/// ```compile_fail
+/// use bare_metal::{shared_resources, PriorityThreshold};
+/// # struct Basepri;
+/// # unsafe impl PriorityThreshold for Basepri {
+/// #     fn raise(ceiling: u8) -> u8 { 0 }
+/// #     unsafe fn restore(previous: u8) {}
+/// # }
+///
+/// shared_resources! {
+///     backend: Basepri;
+///
+///     resource Ticks: u32 = 0 => [0, 2];
+/// }
+///
+/// let mut ticks = unsafe { Ticks::<1>::new() };
+/// ticks.lock(|ticks| *ticks += 1);
+/// ```
+///
+/// # Safety
+///
+/// The resources may only be used from a single core. A proxy may only be created, and used, by a
+/// context running at the logical priority it is created for; `lock` relies on the declared
+/// priorities to compute a ceiling that excludes every other context using the resource.
+#[macro_export]
+macro_rules! shared_resources {
+    (
+        backend: $backend:ty;
+        $(
+            $(#[$attr:meta])*
+            $vis:vis resource $name:ident: $ty:ty = $init:expr => [$($priority:expr),+ $(,)?];
+        )*
+    ) => {$(
+        $(#[$attr])*
+        $vis struct $name<const PRIORITY: u8> {
+            // the proxy is tied to the priority of the context that created it
+            _not_send: ::core::marker::PhantomData<*mut ()>,
+        }
+
+        impl<const PRIORITY: u8> $name<PRIORITY> {
+            /// The ceiling of the resource: the highest priority it is accessed from.
+            pub const CEILING: u8 = $crate::__private::ceiling(&[$($priority),+]);
+
+            const DECLARED: () = assert!(
+                $crate::__private::contains(&[$($priority),+], PRIORITY),
+                "the resource is not accessed from this priority"
+            );
+
+            /// Creates a proxy for the context running at logical priority `PRIORITY`.
+            ///
+            /// # Safety
+            ///
+            /// The proxy must only be used by a context running at logical priority `PRIORITY`,
+            /// on the core the resource belongs to.
+            #[inline]
+            pub unsafe fn new() -> Self {
+                #[allow(clippy::let_unit_value)]
+                let () = Self::DECLARED;
+                $name {
+                    _not_send: ::core::marker::PhantomData,
+                }
+            }
+
+            /// Raises the priority threshold to the ceiling of the resource and calls `f` with
+            /// it.
+            ///
+            /// # Panics
+            ///
+            /// Panics if the resource is already locked, i.e. if `f` locks it again through
+            /// another proxy.
+            #[inline]
+            #[track_caller]
+            pub fn lock<R>(&mut self, f: impl FnOnce(&mut $ty) -> R) -> R {
+                const CEILING: u8 = $name::<0>::CEILING;
+                // SAFETY: the contract of the proxies limits the resource to one core and the
+                // declared priorities, the highest of which is the ceiling
+                static RESOURCE: $crate::CeilingMutex<$ty, $backend, CEILING> =
+                    unsafe { $crate::CeilingMutex::new($init) };
+                RESOURCE.lock(f)
+            }
+        }
+
+        impl<const PRIORITY: u8> ::core::fmt::Debug for $name<PRIORITY> {
+            fn fmt(&self, f: &mut ::core::fmt::Formatter<'_>) -> ::core::fmt::Result {
+                f.debug_struct(::core::stringify!($name))
+                    .field("priority", &PRIORITY)
+                    .finish_non_exhaustive()
+            }
+        }
+    )*};
+}
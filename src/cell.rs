@@ -0,0 +1,251 @@
+use core::cell::{Cell, UnsafeCell};
+use core::fmt;
+use core::marker::PhantomData;
+use core::ops::{Deref, DerefMut};
+
+use critical_section::CriticalSection;
+
+/// A [`Cell`] that can only be accessed inside a critical section.
+///
+/// This is the standalone equivalent of `Mutex<Cell<T>>`, most useful for scalars that are
+/// shared with interrupt handlers.
+///
+/// ```
+/// use bare_metal::CsCell;
+/// use critical_section::CriticalSection;
+///
+/// static TICKS: CsCell<u32> = CsCell::new(0);
+///
+/// let cs = unsafe { CriticalSection::new() };
+/// TICKS.update(cs, |t| t + 1);
+/// assert_eq!(TICKS.get(cs), 1);
+/// assert_eq!(TICKS.replace(cs, 5), 1);
+/// TICKS.set(cs, 0);
+/// ```
+pub struct CsCell<T> {
+    value: Cell<T>,
+}
+
+impl<T> CsCell<T> {
+    /// Creates a new `CsCell` containing `value`.
+    #[inline]
+    pub const fn new(value: T) -> Self {
+        CsCell {
+            value: Cell::new(value),
+        }
+    }
+
+    /// Returns a copy of the contained value.
+    #[inline]
+    pub fn get(&self, _cs: CriticalSection<'_>) -> T
+    where
+        T: Copy,
+    {
+        self.value.get()
+    }
+
+    /// Sets the contained value.
+    #[inline]
+    pub fn set(&self, _cs: CriticalSection<'_>, val: T) {
+        self.value.set(val)
+    }
+
+    /// Replaces the contained value with `val`, and returns the old contained value.
+    #[inline]
+    pub fn replace(&self, _cs: CriticalSection<'_>, val: T) -> T {
+        self.value.replace(val)
+    }
+
+    /// Updates the contained value using `f`.
+    #[inline]
+    pub fn update<F>(&self, _cs: CriticalSection<'_>, f: F)
+    where
+        T: Copy,
+        F: FnOnce(T) -> T,
+    {
+        self.value.set(f(self.value.get()))
+    }
+
+    /// Takes the value, leaving `Default::default()` in its place.
+    #[inline]
+    pub fn take(&self, _cs: CriticalSection<'_>) -> T
+    where
+        T: Default,
+    {
+        self.value.take()
+    }
+
+    /// Returns a mutable reference to the contained value.
+    ///
+    /// This does not require a critical section since `&mut self` already guarantees exclusive
+    /// access.
+    #[inline]
+    pub fn get_mut(&mut self) -> &mut T {
+        self.value.get_mut()
+    }
+
+    /// Consumes the `CsCell`, returning the contained value.
+    #[inline]
+    pub fn into_inner(self) -> T {
+        self.value.into_inner()
+    }
+}
+
+impl<T: Default> Default for CsCell<T> {
+    fn default() -> Self {
+        CsCell::new(T::default())
+    }
+}
+
+impl<T> fmt::Debug for CsCell<T> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("CsCell").finish_non_exhaustive()
+    }
+}
+
+// NOTE the value is only accessed while a critical section is held
+unsafe impl<T> Sync for CsCell<T> where T: Send {}
+
+/// A mutable memory location that can only be borrowed inside a critical section.
+///
+/// This fuses `Mutex<RefCell<T>>` into a single type. Since the critical section already
+/// prevents concurrent access, the only thing left to check at runtime is that the data isn't
+/// borrowed twice, e.g. by nested critical sections. `CsRefCell` therefore only supports
+/// exclusive borrows and tracks them with a single `bool`.
+///
+/// ```
+/// use bare_metal::CsRefCell;
+/// use critical_section::CriticalSection;
+///
+/// static BUFFER: CsRefCell<[u8; 4]> = CsRefCell::new([0; 4]);
+///
+/// let cs = unsafe { CriticalSection::new() };
+/// let mut buffer = BUFFER.borrow_mut(cs);
+/// buffer[0] = 1;
+/// assert!(BUFFER.try_borrow_mut(cs).is_none());
+/// drop(buffer);
+/// assert_eq!(BUFFER.borrow_mut(cs)[0], 1);
+/// ```
+pub struct CsRefCell<T> {
+    borrowed: Cell<bool>,
+    value: UnsafeCell<T>,
+}
+
+impl<T> CsRefCell<T> {
+    /// Creates a new `CsRefCell` containing `value`.
+    #[inline]
+    pub const fn new(value: T) -> Self {
+        CsRefCell {
+            borrowed: Cell::new(false),
+            value: UnsafeCell::new(value),
+        }
+    }
+
+    /// Mutably borrows the data for the duration of the critical section.
+    ///
+    /// # Panics
+    ///
+    /// Panics if the data is already borrowed. For a non-panicking variant, use
+    /// [`try_borrow_mut`](CsRefCell::try_borrow_mut).
+    #[inline]
+    #[track_caller]
+    pub fn borrow_mut<'cs>(&'cs self, cs: CriticalSection<'cs>) -> CsRefMut<'cs, T> {
+        match self.try_borrow_mut(cs) {
+            Some(guard) => guard,
+            None => already_borrowed(),
+        }
+    }
+
+    /// Mutably borrows the data for the duration of the critical section, returning `None` if
+    /// it is already borrowed.
+    #[inline]
+    pub fn try_borrow_mut<'cs>(&'cs self, _cs: CriticalSection<'cs>) -> Option<CsRefMut<'cs, T>> {
+        if self.borrowed.replace(true) {
+            #[cfg(feature = "instrument")]
+            crate::instrument::borrow_failed(_cs);
+            None
+        } else {
+            Some(CsRefMut {
+                cell: self,
+                _not_send: PhantomData,
+            })
+        }
+    }
+
+    /// Returns a mutable reference to the data.
+    ///
+    /// This does not require a critical section since `&mut self` already guarantees exclusive
+    /// access.
+    #[inline]
+    pub fn get_mut(&mut self) -> &mut T {
+        self.value.get_mut()
+    }
+
+    /// Consumes the `CsRefCell`, returning the data.
+    #[inline]
+    pub fn into_inner(self) -> T {
+        self.value.into_inner()
+    }
+}
+
+impl<T: Default> Default for CsRefCell<T> {
+    fn default() -> Self {
+        CsRefCell::new(T::default())
+    }
+}
+
+impl<T> fmt::Debug for CsRefCell<T> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("CsRefCell").finish_non_exhaustive()
+    }
+}
+
+// NOTE the flag and the data are only accessed while a critical section is held, which
+// serializes all accesses. As with `Mutex`, the data must be `Send` because it can be handed
+// over to a different thread or interrupt handler.
+unsafe impl<T> Sync for CsRefCell<T> where T: Send {}
+
+/// A mutable borrow of the data in a [`CsRefCell`].
+///
+/// The borrow is released when this guard is dropped.
+pub struct CsRefMut<'cs, T> {
+    cell: &'cs CsRefCell<T>,
+    // the guard must be dropped in the context that holds the critical section
+    _not_send: PhantomData<*mut ()>,
+}
+
+impl<T> Deref for CsRefMut<'_, T> {
+    type Target = T;
+
+    #[inline]
+    fn deref(&self) -> &T {
+        unsafe { &*self.cell.value.get() }
+    }
+}
+
+impl<T> DerefMut for CsRefMut<'_, T> {
+    #[inline]
+    fn deref_mut(&mut self) -> &mut T {
+        unsafe { &mut *self.cell.value.get() }
+    }
+}
+
+impl<T> Drop for CsRefMut<'_, T> {
+    #[inline]
+    fn drop(&mut self) {
+        self.cell.borrowed.set(false);
+    }
+}
+
+impl<T: fmt::Debug> fmt::Debug for CsRefMut<'_, T> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        (**self).fmt(f)
+    }
+}
+
+#[cold]
+#[inline(never)]
+#[track_caller]
+fn already_borrowed() -> ! {
+    panic!("already borrowed")
+}
@@ -0,0 +1,349 @@
+//! Manual control over critical sections.
+//!
+//! `critical_section::with` is the safest way to enter a critical section. When a closure doesn't
+//! fit, the functions in this module enter and leave critical sections explicitly, like
+//! `critical_section::acquire` and `critical_section::release` do.
+//!
+//! In builds with `debug-assertions` enabled, [`acquire_raw`] and [`release_raw`] keep track of
+//! how deeply critical sections are nested. Releasing a critical section that was never
+//! acquired panics instead of silently leaving interrupts in the wrong state, and
+//! [`nesting_depth`] reports the current depth.
+//!
+//! For targets without a `critical-section` implementation,
+//! [`custom_critical_section!`](crate::custom_critical_section) installs one.
+//!
+//! ```
+//! use bare_metal::cs;
+//!
+//! unsafe {
+//!     let outer = cs::acquire_raw();
+//!     let inner = cs::acquire_raw();
+//!     // `None` in release builds
+//!     assert!(matches!(cs::nesting_depth(), Some(2) | None));
+//!     cs::release_raw(inner);
+//!     cs::release_raw(outer);
+//! }
+//! assert!(matches!(cs::nesting_depth(), Some(0) | None));
+//! ```
+
+#[cfg(debug_assertions)]
+use core::cell::Cell;
+use core::fmt;
+use core::marker::PhantomData;
+
+#[cfg(debug_assertions)]
+use critical_section::Mutex;
+use critical_section::{CriticalSection, RestoreState};
+
+#[cfg(debug_assertions)]
+static DEPTH: Mutex<Cell<usize>> = Mutex::new(Cell::new(0));
+
+/// Acquires a critical section.
+///
+/// This is `critical_section::acquire` plus, in debug builds, nesting tracking.
+///
+/// # Safety
+///
+/// This has the same contract as `critical_section::acquire`: the returned state must be passed
+/// to [`release_raw`], and critical sections must be released in the reverse order in which
+/// they were acquired.
+#[inline]
+#[track_caller]
+pub unsafe fn acquire_raw() -> RestoreState {
+    #[cfg(feature = "deadlock-detection")]
+    crate::deadlock::wait_cs();
+    let restore = critical_section::acquire();
+    #[cfg(feature = "deadlock-detection")]
+    crate::deadlock::entered_cs();
+    #[cfg(debug_assertions)]
+    {
+        let depth = DEPTH.borrow(CriticalSection::new());
+        depth.set(depth.get() + 1);
+    }
+    restore
+}
+
+/// Releases a critical section acquired with [`acquire_raw`].
+///
+/// # Safety
+///
+/// This has the same contract as `critical_section::release`: `restore` must come from the
+/// matching call to [`acquire_raw`].
+///
+/// # Panics
+///
+/// In debug builds, panics if no critical section acquired with [`acquire_raw`] is active.
+#[inline]
+#[track_caller]
+pub unsafe fn release_raw(restore: RestoreState) {
+    #[cfg(debug_assertions)]
+    {
+        // NOTE this nests inside the section being released, so the check can't race
+        let balanced = critical_section::with(|cs| {
+            let depth = DEPTH.borrow(cs);
+            match depth.get().checked_sub(1) {
+                Some(d) => {
+                    depth.set(d);
+                    true
+                }
+                None => false,
+            }
+        });
+        if !balanced {
+            unbalanced();
+        }
+    }
+    #[cfg(feature = "deadlock-detection")]
+    crate::deadlock::leaving_cs();
+    critical_section::release(restore);
+}
+
+#[cfg(debug_assertions)]
+#[cold]
+#[track_caller]
+fn unbalanced() -> ! {
+    panic!("critical section released without being acquired")
+}
+
+/// Returns how many critical sections acquired with [`acquire_raw`] are currently active.
+///
+/// Nesting is only tracked in builds with `debug-assertions` enabled; otherwise this returns
+/// `None`.
+#[inline]
+pub fn nesting_depth() -> Option<usize> {
+    #[cfg(debug_assertions)]
+    return Some(critical_section::with(|cs| DEPTH.borrow(cs).get()));
+    #[cfg(not(debug_assertions))]
+    None
+}
+
+/// A critical section that lasts until the guard is dropped.
+///
+/// This is a scoped alternative to `critical_section::with`, created by [`CsGuard::new`]. The
+/// critical section isn't tied to a closure, so the code inside it can use `return` and `?`
+/// freely. Dropping the guard restores the state from before
+/// it was created, which may still be inside an outer critical section.
+///
+/// ```
+/// use bare_metal::cs::CsGuard;
+/// use core::cell::Cell;
+/// use critical_section::Mutex;
+///
+/// static LEVEL: Mutex<Cell<u8>> = Mutex::new(Cell::new(0));
+///
+/// fn raise(by: u8) -> Option<u8> {
+///     // SAFETY: the guard is dropped at the end of this function
+///     let guard = unsafe { CsGuard::new() };
+///     let level = LEVEL.borrow(guard.token());
+///     level.set(level.get().checked_add(by)?);
+///     Some(level.get())
+/// }
+///
+/// assert_eq!(raise(200), Some(200));
+/// assert_eq!(raise(100), None);
+/// ```
+pub struct CsGuard {
+    restore: RestoreState,
+    #[cfg(feature = "instrument")]
+    start: Option<u32>,
+    _not_send: PhantomData<*mut ()>,
+}
+
+impl CsGuard {
+    /// Enters a critical section.
+    ///
+    /// # Safety
+    ///
+    /// Critical sections must be properly nested. The guard must be dropped before any critical
+    /// section that was entered before it is left, including the one of an enclosing
+    /// `critical_section::with` call and the one owned by another guard.
+    #[inline]
+    pub unsafe fn new() -> Self {
+        let restore = acquire_raw();
+        CsGuard {
+            restore,
+            #[cfg(feature = "instrument")]
+            start: crate::instrument::start(CriticalSection::new()),
+            _not_send: PhantomData,
+        }
+    }
+
+    /// Returns a token for the critical section, valid for as long as the guard is borrowed.
+    #[inline]
+    pub fn token(&self) -> CriticalSection<'_> {
+        // SAFETY: the critical section lasts until the guard is dropped
+        unsafe { CriticalSection::new() }
+    }
+}
+
+impl Drop for CsGuard {
+    #[inline]
+    fn drop(&mut self) {
+        #[cfg(feature = "instrument")]
+        let overrun = crate::instrument::record(self.token(), self.start);
+        unsafe { release_raw(self.restore) }
+        #[cfg(feature = "instrument")]
+        if let Some(overrun) = overrun {
+            overrun.report();
+        }
+    }
+}
+
+impl fmt::Debug for CsGuard {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("CsGuard").finish_non_exhaustive()
+    }
+}
+
+/// A critical section token that can only be created by [`with_branded`].
+///
+/// A `CriticalSection<'static>` can be created at any time with `unsafe { CriticalSection::new()
+/// }`, and kept around after the critical section it was meant for has ended. Code that takes a
+/// `BrandedCs` instead rules that out: the only way to get one is from [`with_branded`], and its
+/// lifetime `'brand` is invariant and unique to that call, so the token can't be stored or
+/// returned past the end of the closure.
+///
+/// ```
+/// use bare_metal::cs::{self, BrandedCs};
+/// use core::cell::Cell;
+/// use critical_section::Mutex;
+///
+/// static COUNT: Mutex<Cell<u32>> = Mutex::new(Cell::new(0));
+///
+/// fn increment(cs: BrandedCs<'_>) {
+///     let count = COUNT.borrow(cs.token());
+///     count.set(count.get() + 1);
+/// }
+///
+/// cs::with_branded(|cs| increment(cs));
+/// ```
+///
+/// Smuggling the token out of the closure doesn't compile:
+///
+/// ```compile_fail
+/// use bare_metal::cs::{self, BrandedCs};
+///
+/// let token: BrandedCs<'static> = cs::with_branded(|cs| cs);
+/// ```
+#[derive(Clone, Copy)]
+pub struct BrandedCs<'brand> {
+    cs: CriticalSection<'brand>,
+    _brand: PhantomData<fn(&'brand ()) -> &'brand ()>,
+}
+
+impl<'brand> BrandedCs<'brand> {
+    /// Returns a plain token for the critical section.
+    #[inline]
+    pub fn token(self) -> CriticalSection<'brand> {
+        self.cs
+    }
+}
+
+impl fmt::Debug for BrandedCs<'_> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("BrandedCs").finish_non_exhaustive()
+    }
+}
+
+/// Executes `f` in a critical section, passing it a [`BrandedCs`] token.
+///
+/// This is `critical_section::with` with a branded token.
+#[inline]
+pub fn with_branded<R>(f: impl for<'brand> FnOnce(BrandedCs<'brand>) -> R) -> R {
+    crate::with_cs(|cs| {
+        f(BrandedCs {
+            cs,
+            _brand: PhantomData,
+        })
+    })
+}
+
+/// Installs a `critical-section` implementation built from an `acquire` and a `release`
+/// expression.
+///
+/// This generates the type, the `critical_section::Impl` implementation and the
+/// `critical_section::set_impl!` call that a custom backend needs, e.g. one that masks the
+/// interrupts of a particular interrupt controller or takes a hardware spinlock. `acquire` is
+/// evaluated to enter a critical section and must produce a `critical_section::RawRestoreState`,
+/// the type selected with the `restore-state-*` features of `critical-section`. `release` is
+/// evaluated with that value bound to the given name to leave it again. Both are evaluated in an
+/// `unsafe` context.
+///
+/// Like any `critical-section` implementation, this must only be used once in the final binary,
+/// typically by the crate that supports the target, and not together with another backend such
+/// as the one of `cortex-m`'s `critical-section-single-core` feature.
+///
+/// ``` no_run
+/// # use core::sync::atomic::{AtomicBool, Ordering};
+/// // the interrupt controller of the target
+/// # static ENABLED: AtomicBool = AtomicBool::new(true);
+/// unsafe fn interrupts_enabled() -> bool {
+///     # ENABLED.load(Ordering::Relaxed)
+///     // ...
+/// }
+/// unsafe fn disable_interrupts() {
+///     # ENABLED.store(false, Ordering::Relaxed)
+///     // ...
+/// }
+/// unsafe fn enable_interrupts() {
+///     # ENABLED.store(true, Ordering::Relaxed)
+///     // ...
+/// }
+///
+/// // requires `critical-section`'s `restore-state-bool` feature
+/// bare_metal::custom_critical_section! {
+///     // SAFETY: masking interrupts excludes all other contexts on this single-core target, and
+///     // the previous state is restored so that critical sections can be nested
+///     unsafe {
+///         acquire: {
+///             let enabled = interrupts_enabled();
+///             disable_interrupts();
+///             enabled
+///         },
+///         release: |was_enabled| {
+///             if was_enabled {
+///                 enable_interrupts();
+///             }
+///         },
+///     }
+/// }
+/// ```
+///
+/// # Safety
+///
+/// While any critical section entered with `acquire` hasn't been left with `release`, no other
+/// context that uses the critical section may run, whether it is an interrupt handler, another
+/// thread or another core. `acquire` must support nesting: its value must let `release` restore
+/// the state from before the matching `acquire`, as critical sections are left in the reverse
+/// order in which they were entered.
+#[macro_export]
+macro_rules! custom_critical_section {
+    (unsafe {
+        acquire: $acquire:expr,
+        release: |$state:ident| $release:expr $(,)?
+    }) => {
+        const _: () = {
+            struct CustomCriticalSection;
+
+            $crate::__private::critical_section::set_impl!(CustomCriticalSection);
+
+            unsafe impl $crate::__private::critical_section::Impl for CustomCriticalSection {
+                // the expressions come from the caller's crate, where unsafe operations in an
+                // `unsafe fn` may need an `unsafe` block
+                unsafe fn acquire() -> $crate::__private::critical_section::RawRestoreState {
+                    #[allow(unused_unsafe)]
+                    unsafe {
+                        $acquire
+                    }
+                }
+
+                unsafe fn release($state: $crate::__private::critical_section::RawRestoreState) {
+                    #[allow(unused_unsafe)]
+                    unsafe {
+                        $release
+                    }
+                }
+            }
+        };
+    };
+}
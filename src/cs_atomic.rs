@@ -0,0 +1,393 @@
+use core::fmt;
+use core::sync::atomic::Ordering;
+
+use crate::CsCell;
+
+/// A boolean that can be shared between contexts, emulating [`AtomicBool`] with critical
+/// sections.
+///
+/// Every operation runs in a short critical section, so this works on targets without
+/// compare-and-swap instructions, like `thumbv6m`, AVR and MSP430, and is a drop-in replacement
+/// for [`AtomicBool`]. The `Ordering` arguments are only there for compatibility: a critical
+/// section orders memory accesses like `SeqCst`.
+///
+/// ```
+/// use bare_metal::CsAtomicBool;
+/// use core::sync::atomic::Ordering;
+///
+/// static READY: CsAtomicBool = CsAtomicBool::new(false);
+///
+/// assert!(!READY.swap(true, Ordering::AcqRel));
+/// assert!(READY.load(Ordering::Acquire));
+/// ```
+///
+/// [`AtomicBool`]: core::sync::atomic::AtomicBool
+pub struct CsAtomicBool {
+    value: CsCell<bool>,
+}
+
+impl CsAtomicBool {
+    /// Creates a new `CsAtomicBool`.
+    #[inline]
+    pub const fn new(value: bool) -> Self {
+        CsAtomicBool {
+            value: CsCell::new(value),
+        }
+    }
+
+    /// Returns a mutable reference to the value.
+    #[inline]
+    pub fn get_mut(&mut self) -> &mut bool {
+        self.value.get_mut()
+    }
+
+    /// Consumes the atomic, returning the value.
+    #[inline]
+    pub fn into_inner(self) -> bool {
+        self.value.into_inner()
+    }
+
+    /// Loads the value.
+    #[inline]
+    pub fn load(&self, _order: Ordering) -> bool {
+        crate::with_cs(|cs| self.value.get(cs))
+    }
+
+    /// Stores `value`.
+    #[inline]
+    pub fn store(&self, value: bool, _order: Ordering) {
+        crate::with_cs(|cs| self.value.set(cs, value))
+    }
+
+    /// Stores `value`, returning the previous value.
+    #[inline]
+    pub fn swap(&self, value: bool, _order: Ordering) -> bool {
+        crate::with_cs(|cs| self.value.replace(cs, value))
+    }
+
+    /// Stores `new` if the value is `current`, returning the previous value in `Ok` if it was
+    /// stored and in `Err` otherwise.
+    #[inline]
+    pub fn compare_exchange(
+        &self,
+        current: bool,
+        new: bool,
+        success: Ordering,
+        failure: Ordering,
+    ) -> Result<bool, bool> {
+        self.fetch_update(success, failure, |v| (v == current).then_some(new))
+    }
+
+    /// Like [`compare_exchange`](CsAtomicBool::compare_exchange). This never fails spuriously.
+    #[inline]
+    pub fn compare_exchange_weak(
+        &self,
+        current: bool,
+        new: bool,
+        success: Ordering,
+        failure: Ordering,
+    ) -> Result<bool, bool> {
+        self.compare_exchange(current, new, success, failure)
+    }
+
+    /// Logical "and" with `value`, returning the previous value.
+    #[inline]
+    pub fn fetch_and(&self, value: bool, _order: Ordering) -> bool {
+        self.modify(|v| v & value)
+    }
+
+    /// Logical "nand" with `value`, returning the previous value.
+    #[inline]
+    pub fn fetch_nand(&self, value: bool, _order: Ordering) -> bool {
+        self.modify(|v| !(v & value))
+    }
+
+    /// Logical "or" with `value`, returning the previous value.
+    #[inline]
+    pub fn fetch_or(&self, value: bool, _order: Ordering) -> bool {
+        self.modify(|v| v | value)
+    }
+
+    /// Logical "xor" with `value`, returning the previous value.
+    #[inline]
+    pub fn fetch_xor(&self, value: bool, _order: Ordering) -> bool {
+        self.modify(|v| v ^ value)
+    }
+
+    /// Stores the value returned by `f` if it returns `Some`, returning the previous value in
+    /// `Ok` if a value was stored and in `Err` otherwise.
+    ///
+    /// `f` is called exactly once, inside the critical section.
+    #[inline]
+    pub fn fetch_update(
+        &self,
+        _set_order: Ordering,
+        _fetch_order: Ordering,
+        f: impl FnOnce(bool) -> Option<bool>,
+    ) -> Result<bool, bool> {
+        crate::with_cs(|cs| {
+            let previous = self.value.get(cs);
+            match f(previous) {
+                Some(new) => {
+                    self.value.set(cs, new);
+                    Ok(previous)
+                }
+                None => Err(previous),
+            }
+        })
+    }
+
+    #[inline]
+    fn modify(&self, f: impl FnOnce(bool) -> bool) -> bool {
+        crate::with_cs(|cs| {
+            let previous = self.value.get(cs);
+            self.value.set(cs, f(previous));
+            previous
+        })
+    }
+}
+
+impl Default for CsAtomicBool {
+    fn default() -> Self {
+        CsAtomicBool::new(false)
+    }
+}
+
+impl From<bool> for CsAtomicBool {
+    fn from(value: bool) -> Self {
+        CsAtomicBool::new(value)
+    }
+}
+
+impl fmt::Debug for CsAtomicBool {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        fmt::Debug::fmt(&self.load(Ordering::Relaxed), f)
+    }
+}
+
+macro_rules! cs_atomic_int {
+    ($(#[$meta:meta])* $name:ident, $t:ty) => {
+        $(#[$meta])*
+        ///
+        /// Every operation runs in a short critical section, so this works on targets without
+        /// compare-and-swap instructions, like `thumbv6m`, AVR and MSP430, and is a drop-in
+        /// replacement for the `core` atomic. The `Ordering` arguments are only there for
+        /// compatibility: a critical section orders memory accesses like `SeqCst`.
+        ///
+        /// Arithmetic wraps around on overflow.
+        pub struct $name {
+            value: CsCell<$t>,
+        }
+
+        impl $name {
+            #[doc = concat!("Creates a new `", stringify!($name), "`.")]
+            #[inline]
+            pub const fn new(value: $t) -> Self {
+                $name {
+                    value: CsCell::new(value),
+                }
+            }
+
+            /// Returns a mutable reference to the value.
+            #[inline]
+            pub fn get_mut(&mut self) -> &mut $t {
+                self.value.get_mut()
+            }
+
+            /// Consumes the atomic, returning the value.
+            #[inline]
+            pub fn into_inner(self) -> $t {
+                self.value.into_inner()
+            }
+
+            /// Loads the value.
+            #[inline]
+            pub fn load(&self, _order: Ordering) -> $t {
+                crate::with_cs(|cs| self.value.get(cs))
+            }
+
+            /// Stores `value`.
+            #[inline]
+            pub fn store(&self, value: $t, _order: Ordering) {
+                crate::with_cs(|cs| self.value.set(cs, value))
+            }
+
+            /// Stores `value`, returning the previous value.
+            #[inline]
+            pub fn swap(&self, value: $t, _order: Ordering) -> $t {
+                crate::with_cs(|cs| self.value.replace(cs, value))
+            }
+
+            /// Stores `new` if the value is `current`, returning the previous value in `Ok` if
+            /// it was stored and in `Err` otherwise.
+            #[inline]
+            pub fn compare_exchange(
+                &self,
+                current: $t,
+                new: $t,
+                success: Ordering,
+                failure: Ordering,
+            ) -> Result<$t, $t> {
+                self.fetch_update(success, failure, |v| (v == current).then_some(new))
+            }
+
+            #[doc = concat!("Like [`compare_exchange`](", stringify!($name), "::compare_exchange). This never fails spuriously.")]
+            #[inline]
+            pub fn compare_exchange_weak(
+                &self,
+                current: $t,
+                new: $t,
+                success: Ordering,
+                failure: Ordering,
+            ) -> Result<$t, $t> {
+                self.compare_exchange(current, new, success, failure)
+            }
+
+            /// Adds `value`, returning the previous value.
+            #[inline]
+            pub fn fetch_add(&self, value: $t, _order: Ordering) -> $t {
+                self.modify(|v| v.wrapping_add(value))
+            }
+
+            /// Subtracts `value`, returning the previous value.
+            #[inline]
+            pub fn fetch_sub(&self, value: $t, _order: Ordering) -> $t {
+                self.modify(|v| v.wrapping_sub(value))
+            }
+
+            /// Bitwise "and" with `value`, returning the previous value.
+            #[inline]
+            pub fn fetch_and(&self, value: $t, _order: Ordering) -> $t {
+                self.modify(|v| v & value)
+            }
+
+            /// Bitwise "nand" with `value`, returning the previous value.
+            #[inline]
+            pub fn fetch_nand(&self, value: $t, _order: Ordering) -> $t {
+                self.modify(|v| !(v & value))
+            }
+
+            /// Bitwise "or" with `value`, returning the previous value.
+            #[inline]
+            pub fn fetch_or(&self, value: $t, _order: Ordering) -> $t {
+                self.modify(|v| v | value)
+            }
+
+            /// Bitwise "xor" with `value`, returning the previous value.
+            #[inline]
+            pub fn fetch_xor(&self, value: $t, _order: Ordering) -> $t {
+                self.modify(|v| v ^ value)
+            }
+
+            /// Stores the maximum of the value and `value`, returning the previous value.
+            #[inline]
+            pub fn fetch_max(&self, value: $t, _order: Ordering) -> $t {
+                self.modify(|v| v.max(value))
+            }
+
+            /// Stores the minimum of the value and `value`, returning the previous value.
+            #[inline]
+            pub fn fetch_min(&self, value: $t, _order: Ordering) -> $t {
+                self.modify(|v| v.min(value))
+            }
+
+            /// Stores the value returned by `f` if it returns `Some`, returning the previous
+            /// value in `Ok` if a value was stored and in `Err` otherwise.
+            ///
+            /// `f` is called exactly once, inside the critical section.
+            #[inline]
+            pub fn fetch_update(
+                &self,
+                _set_order: Ordering,
+                _fetch_order: Ordering,
+                f: impl FnOnce($t) -> Option<$t>,
+            ) -> Result<$t, $t> {
+                crate::with_cs(|cs| {
+                    let previous = self.value.get(cs);
+                    match f(previous) {
+                        Some(new) => {
+                            self.value.set(cs, new);
+                            Ok(previous)
+                        }
+                        None => Err(previous),
+                    }
+                })
+            }
+
+            #[inline]
+            fn modify(&self, f: impl FnOnce($t) -> $t) -> $t {
+                crate::with_cs(|cs| {
+                    let previous = self.value.get(cs);
+                    self.value.set(cs, f(previous));
+                    previous
+                })
+            }
+        }
+
+        impl Default for $name {
+            fn default() -> Self {
+                $name::new(0)
+            }
+        }
+
+        impl From<$t> for $name {
+            fn from(value: $t) -> Self {
+                $name::new(value)
+            }
+        }
+
+        impl fmt::Debug for $name {
+            fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+                fmt::Debug::fmt(&self.load(Ordering::Relaxed), f)
+            }
+        }
+    };
+}
+
+cs_atomic_int! {
+    /// An integer that can be shared between contexts, emulating
+    /// [`AtomicU8`](core::sync::atomic::AtomicU8) with critical sections.
+    ///
+    /// ```
+    /// use bare_metal::CsAtomicU8;
+    /// use core::sync::atomic::Ordering;
+    ///
+    /// static PENDING: CsAtomicU8 = CsAtomicU8::new(0);
+    ///
+    /// PENDING.fetch_or(0b10, Ordering::Relaxed);
+    /// assert_eq!(PENDING.swap(0, Ordering::Relaxed), 0b10);
+    /// ```
+    CsAtomicU8, u8
+}
+
+cs_atomic_int! {
+    /// An integer that can be shared between contexts, emulating
+    /// [`AtomicU16`](core::sync::atomic::AtomicU16) with critical sections.
+    CsAtomicU16, u16
+}
+
+cs_atomic_int! {
+    /// An integer that can be shared between contexts, emulating
+    /// [`AtomicU32`](core::sync::atomic::AtomicU32) with critical sections.
+    ///
+    /// ```
+    /// use bare_metal::CsAtomicU32;
+    /// use core::sync::atomic::Ordering;
+    ///
+    /// static TICKS: CsAtomicU32 = CsAtomicU32::new(0);
+    ///
+    /// TICKS.fetch_add(1, Ordering::Relaxed);
+    /// assert_eq!(
+    ///     TICKS.compare_exchange(1, 10, Ordering::AcqRel, Ordering::Acquire),
+    ///     Ok(1)
+    /// );
+    /// assert_eq!(TICKS.load(Ordering::Relaxed), 10);
+    /// ```
+    CsAtomicU32, u32
+}
+
+cs_atomic_int! {
+    /// An integer that can be shared between contexts, emulating
+    /// [`AtomicUsize`](core::sync::atomic::AtomicUsize) with critical sections.
+    CsAtomicUsize, usize
+}
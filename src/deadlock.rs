@@ -0,0 +1,255 @@
+//! Detection of deadlocks between critical sections and spinlocks.
+//!
+//! Critical sections and [`SpinLock`](crate::SpinLock)s can deadlock each other on multi-core
+//! systems: core 0 enters a critical section and waits for a spinlock held by core 1, while
+//! core 1 waits to enter the critical section. With the `deadlock-detection` feature enabled,
+//! this crate records which locks each core holds and which one it is waiting for. A core that
+//! is about to wait on a lock first follows the chain of owners and waiters, and panics with
+//! the identities of the locks involved when the chain leads back to itself.
+//!
+//! This also catches the single-core variant of the problem, where an interrupt handler spins on
+//! a lock held by the code it preempted.
+//!
+//! Only critical sections entered by this crate, including through [`cs`](crate::cs), are
+//! tracked. The tracking is best effort: on multi-core systems it reads the other cores' state
+//! without synchronizing with them, so it is a development aid, not a guarantee.
+//!
+//! On multi-core systems, register a function that returns the current core's number with
+//! [`set_core_id`]. Until one is registered, all code is assumed to run on core 0.
+//!
+//! ```should_panic
+//! use bare_metal::SpinLock;
+//!
+//! static SHARED: SpinLock<u32> = SpinLock::new(0);
+//!
+//! let _guard = SHARED.lock();
+//! // In an interrupt handler that preempted the code above.
+//! // Panics: "deadlock: core 0 waits for the spinlock at 0x..., which it holds itself"
+//! let _guard = SHARED.lock();
+//! ```
+//!
+//! This module is only available when the `deadlock-detection` feature is enabled.
+
+use core::fmt;
+use core::sync::atomic::{AtomicUsize, Ordering};
+
+/// The number of cores that are tracked.
+///
+/// Cores with a higher number are ignored.
+pub const MAX_CORES: usize = 8;
+
+// the number of locks tracked per core; any further ones are ignored
+const MAX_HELD: usize = 8;
+
+// lock ids: 0 is no lock, 1 is the critical section, anything else is the address of a spinlock
+const NONE: usize = 0;
+const CRITICAL_SECTION: usize = 1;
+
+struct Core {
+    waiting: AtomicUsize,
+    held: [AtomicUsize; MAX_HELD],
+    cs_depth: AtomicUsize,
+}
+
+#[allow(clippy::declare_interior_mutable_const)]
+const EMPTY: AtomicUsize = AtomicUsize::new(NONE);
+#[allow(clippy::declare_interior_mutable_const)]
+const CORE: Core = Core {
+    waiting: EMPTY,
+    held: [EMPTY; MAX_HELD],
+    cs_depth: EMPTY,
+};
+
+// NOTE each core only writes to its own entry, except for releasing a lock whose guard was moved
+// to another core, so plain loads and stores suffice
+static CORES: [Core; MAX_CORES] = [CORE; MAX_CORES];
+
+// a `fn() -> usize`, or 0 if none was registered
+static CORE_ID: AtomicUsize = AtomicUsize::new(0);
+
+/// Registers the function that returns the number of the core it is called on.
+///
+/// Cores must be numbered from 0. This is meant to be called once, by the final application,
+/// before the other cores are started.
+pub fn set_core_id(core_id: fn() -> usize) {
+    CORE_ID.store(core_id as usize, Ordering::Relaxed);
+}
+
+fn current() -> Option<usize> {
+    let id = match CORE_ID.load(Ordering::Relaxed) {
+        0 => 0,
+        // SAFETY: the only non-zero values ever stored are `fn() -> usize` pointers
+        f => unsafe { core::mem::transmute::<usize, fn() -> usize>(f)() },
+    };
+    Some(id).filter(|&id| id < MAX_CORES)
+}
+
+#[derive(Clone, Copy)]
+struct Lock(usize);
+
+impl fmt::Display for Lock {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self.0 {
+            CRITICAL_SECTION => f.write_str("the critical section"),
+            addr => write!(f, "the spinlock at {:#x}", addr),
+        }
+    }
+}
+
+// an empty slot is not a lock, so `NONE` has no owner
+fn owner(lock: usize) -> Option<usize> {
+    if lock == NONE {
+        return None;
+    }
+    CORES.iter().position(|core| {
+        core.held
+            .iter()
+            .any(|held| held.load(Ordering::Relaxed) == lock)
+    })
+}
+
+#[track_caller]
+fn wait(lock: usize) {
+    let me = match current() {
+        Some(me) => me,
+        None => return,
+    };
+    CORES[me].waiting.store(lock, Ordering::Relaxed);
+
+    let first = match owner(lock) {
+        Some(first) => first,
+        None => return,
+    };
+    if first == me {
+        CORES[me].waiting.store(NONE, Ordering::Relaxed);
+        panic!(
+            "deadlock: core {} waits for {}, which it holds itself",
+            me,
+            Lock(lock)
+        );
+    }
+    let second = CORES[first].waiting.load(Ordering::Relaxed);
+    let mut next = second;
+    for _ in 0..MAX_CORES {
+        // the chain ends at a core that isn't waiting, which will release its locks
+        if next == NONE {
+            return;
+        }
+        match owner(next) {
+            Some(owner) if owner == me => {
+                CORES[me].waiting.store(NONE, Ordering::Relaxed);
+                panic!(
+                    "deadlock: core {} waits for {}, held by core {}, which waits for {}",
+                    me,
+                    Lock(lock),
+                    first,
+                    Lock(second)
+                );
+            }
+            Some(owner) => next = CORES[owner].waiting.load(Ordering::Relaxed),
+            None => return,
+        }
+    }
+}
+
+fn acquired(lock: usize) {
+    let me = match current() {
+        Some(me) => me,
+        None => return,
+    };
+    let core = &CORES[me];
+    core.waiting.store(NONE, Ordering::Relaxed);
+    if let Some(slot) = core
+        .held
+        .iter()
+        .find(|held| held.load(Ordering::Relaxed) == NONE)
+    {
+        slot.store(lock, Ordering::Relaxed);
+    }
+}
+
+fn released(lock: usize) {
+    // the guard may have been moved to another core, so look at all of them
+    let slot = CORES
+        .iter()
+        .flat_map(|core| core.held.iter())
+        .find(|held| held.load(Ordering::Relaxed) == lock);
+    if let Some(slot) = slot {
+        slot.store(NONE, Ordering::Relaxed);
+    }
+}
+
+#[cfg(target_has_atomic = "8")]
+#[inline]
+#[track_caller]
+pub(crate) fn wait_spin(lock: *const ()) {
+    wait(lock as usize)
+}
+
+#[cfg(target_has_atomic = "8")]
+#[inline]
+pub(crate) fn acquired_spin(lock: *const ()) {
+    acquired(lock as usize)
+}
+
+#[cfg(target_has_atomic = "8")]
+#[inline]
+pub(crate) fn released_spin(lock: *const ()) {
+    released(lock as usize)
+}
+
+/// Called before entering a critical section.
+#[inline]
+#[track_caller]
+pub(crate) fn wait_cs() {
+    if let Some(me) = current() {
+        // a nested critical section never waits
+        if CORES[me].cs_depth.load(Ordering::Relaxed) == 0 {
+            wait(CRITICAL_SECTION);
+        }
+    }
+}
+
+/// Called after entering a critical section.
+#[inline]
+pub(crate) fn entered_cs() {
+    if let Some(me) = current() {
+        let depth = &CORES[me].cs_depth;
+        let d = depth.load(Ordering::Relaxed);
+        depth.store(d + 1, Ordering::Relaxed);
+        if d == 0 {
+            acquired(CRITICAL_SECTION);
+        }
+    }
+}
+
+/// Called before leaving a critical section.
+#[inline]
+pub(crate) fn leaving_cs() {
+    if let Some(me) = current() {
+        let depth = &CORES[me].cs_depth;
+        let d = depth.load(Ordering::Relaxed).saturating_sub(1);
+        depth.store(d, Ordering::Relaxed);
+        if d == 0 {
+            released(CRITICAL_SECTION);
+        }
+    }
+}
+
+/// Calls [`leaving_cs`] when dropped, so that it also happens when unwinding.
+pub(crate) struct HeldCs;
+
+impl HeldCs {
+    #[inline]
+    pub(crate) fn new() -> Self {
+        entered_cs();
+        HeldCs
+    }
+}
+
+impl Drop for HeldCs {
+    #[inline]
+    fn drop(&mut self) {
+        leaving_cs();
+    }
+}
@@ -0,0 +1,243 @@
+//! Buffers that are safe to hand to a DMA engine.
+//!
+//! A DMA transfer keeps accessing its buffer after the call that started it has returned. A
+//! HAL driver that takes a raw pointer can't tell whether the buffer will still be there at
+//! that point. [`ReadBuffer`] and [`WriteBuffer`] instead describe buffers that stay at the same
+//! address for as long as they are not dropped, even when they are moved: a driver takes the
+//! buffer by value for the duration of the transfer and gives it back once it has completed.
+//!
+//! ```
+//! use bare_metal::dma::{DmaBuffer, ReadBuffer, WriteBuffer};
+//!
+//! // skeleton of a DMA driver
+//! struct Transfer<B> {
+//!     buffer: B,
+//! }
+//!
+//! fn start_rx<B: WriteBuffer<Word = u8>>(mut buffer: B) -> Transfer<B> {
+//!     let (ptr, len) = unsafe { buffer.write_buffer() };
+//!     // program the DMA channel with `ptr` and `len`
+//! #   let _ = (ptr, len);
+//!     Transfer { buffer }
+//! }
+//!
+//! impl<B> Transfer<B> {
+//!     fn wait(self) -> B {
+//!         // wait for the transfer to complete
+//!         self.buffer
+//!     }
+//! }
+//!
+//! let buffer: &'static mut DmaBuffer<64, 32> = bare_metal::singleton!(: DmaBuffer<64, 32> =
+//!     DmaBuffer::new()).unwrap();
+//! let buffer = start_rx(buffer).wait();
+//! assert_eq!(buffer.as_ptr() as usize % 32, 0);
+//! ```
+
+use core::fmt;
+use core::ops::{Deref, DerefMut};
+
+use crate::align::{ConstAlign, SupportedAlignment};
+use crate::PoolBox;
+
+/// A word that DMA engines transfer.
+///
+/// # Safety
+///
+/// Every bit pattern must be a valid value of the type, since the DMA engine may write any.
+pub unsafe trait Word: Copy {}
+
+unsafe impl Word for u8 {}
+unsafe impl Word for u16 {}
+unsafe impl Word for u32 {}
+unsafe impl Word for u64 {}
+unsafe impl Word for i8 {}
+unsafe impl Word for i16 {}
+unsafe impl Word for i32 {}
+unsafe impl Word for i64 {}
+
+/// A buffer that a DMA engine can read from.
+///
+/// # Safety
+///
+/// The returned pointer and length must describe memory that is valid for reads, and they must
+/// stay the same and valid for as long as `self` isn't dropped, even if `self` is moved. The
+/// memory must not be written through `self` while the buffer is in use.
+pub unsafe trait ReadBuffer {
+    /// The type of the words in the buffer.
+    type Word: Word;
+
+    /// Returns a pointer to the first word of the buffer and the number of words.
+    ///
+    /// # Safety
+    ///
+    /// The memory may only be accessed while `self` is alive, and only read.
+    unsafe fn read_buffer(&self) -> (*const Self::Word, usize);
+}
+
+/// A buffer that a DMA engine can write to.
+///
+/// # Safety
+///
+/// The returned pointer and length must describe memory that is valid for reads and writes, and
+/// they must stay the same and valid for as long as `self` isn't dropped, even if `self` is
+/// moved. The memory must not be accessed through `self` while the buffer is in use.
+pub unsafe trait WriteBuffer {
+    /// The type of the words in the buffer.
+    type Word: Word;
+
+    /// Returns a pointer to the first word of the buffer and the number of words.
+    ///
+    /// # Safety
+    ///
+    /// The memory may only be accessed while `self` is alive.
+    unsafe fn write_buffer(&mut self) -> (*mut Self::Word, usize);
+}
+
+/// A zero-initialized buffer of `N` bytes, aligned to `ALIGN` bytes.
+///
+/// Many DMA engines require their buffers to be aligned to a word, a burst or a cache line.
+/// `ALIGN` must be a power of two up to 128. References to a `DmaBuffer` with a `'static`
+/// lifetime are [`ReadBuffer`]s and [`WriteBuffer`]s, and so are [`PoolBox`]es holding one.
+///
+/// ```compile_fail
+/// use bare_metal::dma::DmaBuffer;
+///
+/// let buffer = DmaBuffer::<64, 3>::new();
+/// ```
+#[repr(C)]
+pub struct DmaBuffer<const N: usize, const ALIGN: usize>
+where
+    ConstAlign<ALIGN>: SupportedAlignment,
+{
+    _align: [<ConstAlign<ALIGN> as SupportedAlignment>::Marker; 0],
+    data: [u8; N],
+}
+
+impl<const N: usize, const ALIGN: usize> DmaBuffer<N, ALIGN>
+where
+    ConstAlign<ALIGN>: SupportedAlignment,
+{
+    /// Creates a zero-initialized buffer.
+    #[inline]
+    pub const fn new() -> Self {
+        DmaBuffer {
+            _align: [],
+            data: [0; N],
+        }
+    }
+}
+
+impl<const N: usize, const ALIGN: usize> Deref for DmaBuffer<N, ALIGN>
+where
+    ConstAlign<ALIGN>: SupportedAlignment,
+{
+    type Target = [u8; N];
+
+    #[inline]
+    fn deref(&self) -> &[u8; N] {
+        &self.data
+    }
+}
+
+impl<const N: usize, const ALIGN: usize> DerefMut for DmaBuffer<N, ALIGN>
+where
+    ConstAlign<ALIGN>: SupportedAlignment,
+{
+    #[inline]
+    fn deref_mut(&mut self) -> &mut [u8; N] {
+        &mut self.data
+    }
+}
+
+impl<const N: usize, const ALIGN: usize> Default for DmaBuffer<N, ALIGN>
+where
+    ConstAlign<ALIGN>: SupportedAlignment,
+{
+    fn default() -> Self {
+        DmaBuffer::new()
+    }
+}
+
+impl<const N: usize, const ALIGN: usize> fmt::Debug for DmaBuffer<N, ALIGN>
+where
+    ConstAlign<ALIGN>: SupportedAlignment,
+{
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("DmaBuffer")
+            .field("len", &N)
+            .field("align", &ALIGN)
+            .finish_non_exhaustive()
+    }
+}
+
+macro_rules! static_buffers {
+    ($([$($generics:tt)*] $ty:ty => $word:ty $(where [$($bounds:tt)*])?;)*) => {$(
+        // NOTE the referenced memory lives forever and doesn't move with the reference
+        unsafe impl<$($generics)*> ReadBuffer for &'static $ty $(where $($bounds)*)? {
+            type Word = $word;
+
+            #[inline]
+            unsafe fn read_buffer(&self) -> (*const $word, usize) {
+                (self.as_ptr(), self.len())
+            }
+        }
+
+        // NOTE the referenced memory lives forever and doesn't move with the reference
+        unsafe impl<$($generics)*> ReadBuffer for &'static mut $ty $(where $($bounds)*)? {
+            type Word = $word;
+
+            #[inline]
+            unsafe fn read_buffer(&self) -> (*const $word, usize) {
+                (self.as_ptr(), self.len())
+            }
+        }
+
+        // NOTE the referenced memory lives forever and doesn't move with the reference
+        unsafe impl<$($generics)*> WriteBuffer for &'static mut $ty $(where $($bounds)*)? {
+            type Word = $word;
+
+            #[inline]
+            unsafe fn write_buffer(&mut self) -> (*mut $word, usize) {
+                (self.as_mut_ptr(), self.len())
+            }
+        }
+    )*};
+}
+
+macro_rules! pool_buffers {
+    ($([$($generics:tt)*] $ty:ty => $word:ty $(where [$($bounds:tt)*])?;)*) => {$(
+        // NOTE the pool block lives forever and doesn't move with the `PoolBox`
+        unsafe impl<$($generics)*> ReadBuffer for PoolBox<'static, $ty> $(where $($bounds)*)? {
+            type Word = $word;
+
+            #[inline]
+            unsafe fn read_buffer(&self) -> (*const $word, usize) {
+                (self.as_ptr(), self.len())
+            }
+        }
+
+        // NOTE the pool block lives forever and doesn't move with the `PoolBox`
+        unsafe impl<$($generics)*> WriteBuffer for PoolBox<'static, $ty> $(where $($bounds)*)? {
+            type Word = $word;
+
+            #[inline]
+            unsafe fn write_buffer(&mut self) -> (*mut $word, usize) {
+                (self.as_mut_ptr(), self.len())
+            }
+        }
+    )*};
+}
+
+static_buffers! {
+    [W: Word] [W] => W;
+    [W: Word, const N: usize] [W; N] => W;
+    [const N: usize, const ALIGN: usize] DmaBuffer<N, ALIGN> => u8
+        where [ConstAlign<ALIGN>: SupportedAlignment];
+}
+
+pool_buffers! {
+    [W: Word, const N: usize] [W; N] => W;
+    [const N: usize, const ALIGN: usize] DmaBuffer<N, ALIGN> => u8
+        where [ConstAlign<ALIGN>: SupportedAlignment];
+}
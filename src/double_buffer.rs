@@ -0,0 +1,268 @@
+//! A ping-pong buffer between a producing interrupt handler and a consuming main loop.
+//!
+//! The [`Writer`] fills the back buffer, e.g. with the samples of an ADC or an audio frame, and
+//! publishes it by swapping it with the front buffer. The [`Reader`] reads the front buffer in
+//! the meantime. A swap never happens while the reader holds the front buffer: a frame that is
+//! published then is swapped in as soon as the reader lets go of the front buffer, and if the
+//! writer overwrites it before that, the frame is dropped and the *missed* indicator is set.
+//!
+//! ```
+//! use bare_metal::double_buffer::DoubleBuffer;
+//!
+//! let mut buffer = DoubleBuffer::new([0u16; 4], [0u16; 4]);
+//! let (mut writer, mut reader) = buffer.split();
+//!
+//! // In the ADC interrupt handler
+//! writer.write(|samples| *samples = [1, 2, 3, 4]);
+//!
+//! // In the main loop
+//! let samples = reader.read().unwrap();
+//! assert_eq!(*samples, [1, 2, 3, 4]);
+//! // the next frame is held back while the front buffer is read
+//! writer.write(|samples| *samples = [5, 6, 7, 8]);
+//! drop(samples);
+//!
+//! assert_eq!(*reader.read().unwrap(), [5, 6, 7, 8]);
+//! assert!(reader.read().is_none());
+//! assert!(!reader.take_missed());
+//! ```
+
+use core::cell::UnsafeCell;
+use core::fmt;
+use core::marker::PhantomData;
+use core::ops::Deref;
+
+use crate::atomic::{AtomicU8, Ordering};
+
+// index of the front buffer
+const FRONT: u8 = 1 << 0;
+// set while the reader holds the front buffer
+const READING: u8 = 1 << 1;
+// set while the writer fills the back buffer
+const WRITING: u8 = 1 << 2;
+// set when the front buffer holds a frame the reader hasn't seen yet
+const FRESH: u8 = 1 << 3;
+// set when the back buffer holds a frame that couldn't be swapped in while the reader held
+// the front buffer
+const PENDING: u8 = 1 << 4;
+// set when a frame was overwritten before the reader saw it
+const MISSED: u8 = 1 << 5;
+
+/// Two buffers of type `T` that are swapped between a writer and a reader.
+///
+/// The state of the buffers is updated with atomic read-modify-write operations, which fall
+/// back to critical sections on targets without compare-and-swap.
+pub struct DoubleBuffer<T> {
+    state: AtomicU8,
+    buffers: [UnsafeCell<T>; 2],
+}
+
+impl<T> DoubleBuffer<T> {
+    const_fn! {
+        /// Creates a double buffer from its initial front and back buffers.
+        ///
+        /// The initial front buffer is not considered a new frame, so it isn't handed to the
+        /// reader.
+        #[inline]
+        pub const fn new(front: T, back: T) -> Self {
+            DoubleBuffer {
+                state: AtomicU8::new(0),
+                buffers: [UnsafeCell::new(front), UnsafeCell::new(back)],
+            }
+        }
+    }
+
+    /// Splits the double buffer into its writer and reader halves.
+    #[inline]
+    pub fn split(&mut self) -> (Writer<'_, T>, Reader<'_, T>) {
+        (
+            Writer {
+                buffer: self,
+                _not_sync: PhantomData,
+            },
+            Reader {
+                buffer: self,
+                _not_sync: PhantomData,
+            },
+        )
+    }
+
+    /// Consumes the double buffer, returning the front and back buffers.
+    #[inline]
+    pub fn into_inner(self) -> (T, T) {
+        let front = self.state.load(Ordering::Relaxed) & FRONT;
+        let [a, b] = self.buffers;
+        let (a, b) = (a.into_inner(), b.into_inner());
+        if front == 0 {
+            (a, b)
+        } else {
+            (b, a)
+        }
+    }
+
+    #[inline]
+    fn buffer(&self, state: u8) -> *mut T {
+        self.buffers[usize::from(state & FRONT)].get()
+    }
+
+    // SAFETY: must only be called by the single writer
+    unsafe fn inner_write(&self, f: impl FnOnce(&mut T)) {
+        // the reader doesn't swap the buffers while `WRITING` is set
+        let state = self.state.fetch_or(WRITING, Ordering::Acquire);
+        f(&mut *self.buffer(state ^ FRONT));
+        let _ = self
+            .state
+            .fetch_update(Ordering::AcqRel, Ordering::Relaxed, |state| {
+                let state = state & !WRITING;
+                Some(if state & READING != 0 {
+                    // hold the frame back until the reader lets go of the front buffer
+                    let missed = if state & PENDING != 0 { MISSED } else { 0 };
+                    state | PENDING | missed
+                } else {
+                    let missed = if state & (FRESH | PENDING) != 0 {
+                        MISSED
+                    } else {
+                        0
+                    };
+                    ((state ^ FRONT) | FRESH | missed) & !PENDING
+                })
+            });
+    }
+
+    // SAFETY: must only be called by the single reader, while it doesn't hold the front buffer
+    unsafe fn inner_read(&self) -> Option<*const T> {
+        let state = self
+            .state
+            .fetch_update(Ordering::AcqRel, Ordering::Acquire, |state| {
+                let state = swap_pending(state);
+                (state & FRESH != 0).then_some((state | READING) & !FRESH)
+            })
+            .ok()?;
+        Some(self.buffer(swap_pending(state)))
+    }
+
+    // SAFETY: must only be called by the single reader, while it holds the front buffer
+    unsafe fn inner_release(&self) {
+        let _ = self
+            .state
+            .fetch_update(Ordering::AcqRel, Ordering::Relaxed, |state| {
+                Some(swap_pending(state & !READING))
+            });
+    }
+}
+
+// swaps in a pending frame, unless the writer is filling the back buffer again
+#[inline]
+fn swap_pending(state: u8) -> u8 {
+    if state & (PENDING | WRITING | READING) == PENDING {
+        ((state ^ FRONT) | FRESH) & !PENDING
+    } else {
+        state
+    }
+}
+
+impl<T: Default> Default for DoubleBuffer<T> {
+    fn default() -> Self {
+        DoubleBuffer::new(T::default(), T::default())
+    }
+}
+
+impl<T> fmt::Debug for DoubleBuffer<T> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("DoubleBuffer").finish_non_exhaustive()
+    }
+}
+
+/// The writer half of a [`DoubleBuffer`].
+pub struct Writer<'a, T> {
+    buffer: &'a DoubleBuffer<T>,
+    _not_sync: PhantomData<*mut ()>,
+}
+
+impl<T> Writer<'_, T> {
+    /// Fills the back buffer with `f` and publishes it.
+    ///
+    /// The back buffer still holds an older frame, which `f` can reuse or overwrite.
+    #[inline]
+    pub fn write(&mut self, f: impl FnOnce(&mut T)) {
+        // SAFETY: there is only one writer, and it is borrowed mutably
+        unsafe { self.buffer.inner_write(f) }
+    }
+}
+
+impl<T> fmt::Debug for Writer<'_, T> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("Writer").finish_non_exhaustive()
+    }
+}
+
+// NOTE buffers filled by the writer are read by the reader, possibly in another context
+unsafe impl<T> Send for Writer<'_, T> where T: Send {}
+
+/// The reader half of a [`DoubleBuffer`].
+pub struct Reader<'a, T> {
+    buffer: &'a DoubleBuffer<T>,
+    _not_sync: PhantomData<*mut ()>,
+}
+
+impl<'a, T> Reader<'a, T> {
+    /// Returns the front buffer if it holds a frame that hasn't been read yet.
+    ///
+    /// The buffers aren't swapped while the returned guard is alive.
+    #[inline]
+    pub fn read(&mut self) -> Option<Front<'_, 'a, T>> {
+        // SAFETY: there is only one reader, and it is borrowed mutably by the guard
+        let value = unsafe { self.buffer.inner_read() }?;
+        Some(Front {
+            reader: self,
+            value,
+        })
+    }
+
+    /// Returns `true` if a frame was overwritten before it could be read since the last call,
+    /// and clears the indicator.
+    #[inline]
+    pub fn take_missed(&mut self) -> bool {
+        self.buffer.state.fetch_and(!MISSED, Ordering::Relaxed) & MISSED != 0
+    }
+}
+
+impl<T> fmt::Debug for Reader<'_, T> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("Reader").finish_non_exhaustive()
+    }
+}
+
+// NOTE see `Writer`
+unsafe impl<T> Send for Reader<'_, T> where T: Send {}
+
+/// The front buffer of a [`DoubleBuffer`], held by the [`Reader`].
+///
+/// Dropping the guard lets the writer swap the buffers again.
+pub struct Front<'r, 'a, T> {
+    reader: &'r mut Reader<'a, T>,
+    value: *const T,
+}
+
+impl<T> Deref for Front<'_, '_, T> {
+    type Target = T;
+
+    #[inline]
+    fn deref(&self) -> &T {
+        // SAFETY: the writer doesn't access the front buffer while `READING` is set
+        unsafe { &*self.value }
+    }
+}
+
+impl<T> Drop for Front<'_, '_, T> {
+    fn drop(&mut self) {
+        // SAFETY: there is only one reader, and this is its guard
+        unsafe { self.reader.buffer.inner_release() }
+    }
+}
+
+impl<T: fmt::Debug> fmt::Debug for Front<'_, '_, T> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        fmt::Debug::fmt(&**self, f)
+    }
+}
@@ -0,0 +1,104 @@
+use critical_section::CriticalSection;
+
+use crate::CsCell;
+
+/// A group of 32 event flags.
+///
+/// Interrupt handlers set bits to report events, and the main loop tests and clears
+/// combinations of them, similar to the event groups of an RTOS. All operations take a
+/// [`CriticalSection`] token to make each of them atomic.
+///
+/// [`take_any`](EventFlags::take_any) and [`take_all`](EventFlags::take_all) implement the
+/// "wait for any" and "wait for all" semantics for a main loop that polls the flags.
+///
+/// ```
+/// use bare_metal::EventFlags;
+/// use critical_section::CriticalSection;
+///
+/// const RX_DONE: u32 = 1 << 0;
+/// const TX_DONE: u32 = 1 << 1;
+///
+/// static EVENTS: EventFlags = EventFlags::new();
+///
+/// let cs = unsafe { CriticalSection::new() };
+/// // In the interrupt handler
+/// EVENTS.set(cs, RX_DONE);
+///
+/// // In the main loop
+/// assert!(!EVENTS.take_all(cs, RX_DONE | TX_DONE));
+/// assert_eq!(EVENTS.take_any(cs, RX_DONE | TX_DONE), RX_DONE);
+/// assert_eq!(EVENTS.get(cs), 0);
+/// ```
+#[derive(Debug, Default)]
+pub struct EventFlags {
+    bits: CsCell<u32>,
+}
+
+impl EventFlags {
+    /// Creates a new group with all flags cleared.
+    #[inline]
+    pub const fn new() -> Self {
+        EventFlags {
+            bits: CsCell::new(0),
+        }
+    }
+
+    /// Returns all flags that are currently set.
+    #[inline]
+    pub fn get(&self, cs: CriticalSection<'_>) -> u32 {
+        self.bits.get(cs)
+    }
+
+    /// Sets the flags in `mask`.
+    #[inline]
+    pub fn set(&self, cs: CriticalSection<'_>, mask: u32) {
+        self.bits.update(cs, |bits| bits | mask)
+    }
+
+    /// Clears the flags in `mask`.
+    #[inline]
+    pub fn clear(&self, cs: CriticalSection<'_>, mask: u32) {
+        self.bits.update(cs, |bits| bits & !mask)
+    }
+
+    /// Clears the flags in `mask`, returning those of them that were set.
+    #[inline]
+    pub fn fetch_and_clear(&self, cs: CriticalSection<'_>, mask: u32) -> u32 {
+        let bits = self.bits.get(cs);
+        self.bits.set(cs, bits & !mask);
+        bits & mask
+    }
+
+    /// Returns `true` if any of the flags in `mask` is set.
+    #[inline]
+    pub fn any(&self, cs: CriticalSection<'_>, mask: u32) -> bool {
+        self.bits.get(cs) & mask != 0
+    }
+
+    /// Returns `true` if all of the flags in `mask` are set.
+    #[inline]
+    pub fn all(&self, cs: CriticalSection<'_>, mask: u32) -> bool {
+        self.bits.get(cs) & mask == mask
+    }
+
+    /// If any of the flags in `mask` is set, clears all of them and returns those that were set.
+    ///
+    /// Returns 0 if none of them is set. This is the same as
+    /// [`fetch_and_clear`](EventFlags::fetch_and_clear).
+    #[inline]
+    pub fn take_any(&self, cs: CriticalSection<'_>, mask: u32) -> u32 {
+        self.fetch_and_clear(cs, mask)
+    }
+
+    /// If all of the flags in `mask` are set, clears them and returns `true`.
+    ///
+    /// Otherwise, leaves the flags untouched and returns `false`.
+    #[inline]
+    pub fn take_all(&self, cs: CriticalSection<'_>, mask: u32) -> bool {
+        let all = self.all(cs, mask);
+        if all {
+            self.clear(cs, mask);
+        }
+        all
+    }
+}
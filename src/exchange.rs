@@ -0,0 +1,87 @@
+use critical_section::CriticalSection;
+
+use crate::CsCell;
+
+/// A single-slot mailbox where the latest value wins.
+///
+/// The producer, e.g. an interrupt handler, [`publish`](Exchange::publish)es values, and the
+/// consumer [`take`](Exchange::take)s the most recent one. Unlike a queue, publishing never
+/// fails: an unread value is overwritten, which is reported to both sides.
+///
+/// ```
+/// use bare_metal::Exchange;
+/// use critical_section::CriticalSection;
+///
+/// static ADC: Exchange<u16> = Exchange::new();
+///
+/// let cs = unsafe { CriticalSection::new() };
+/// // In the interrupt handler
+/// assert!(!ADC.publish(cs, 100));
+/// assert!(ADC.publish(cs, 101));
+///
+/// // In the main loop
+/// assert!(ADC.overwritten(cs));
+/// assert_eq!(ADC.take(cs), Some(101));
+/// assert!(!ADC.overwritten(cs));
+/// assert_eq!(ADC.take(cs), None);
+/// ```
+#[derive(Debug)]
+pub struct Exchange<T> {
+    value: CsCell<Option<T>>,
+    overwritten: CsCell<bool>,
+}
+
+impl<T> Exchange<T> {
+    /// Creates an empty mailbox.
+    #[inline]
+    pub const fn new() -> Self {
+        Exchange {
+            value: CsCell::new(None),
+            overwritten: CsCell::new(false),
+        }
+    }
+
+    /// Stores `val`, dropping any value that hasn't been taken yet.
+    ///
+    /// Returns `true` if an unread value was overwritten.
+    #[inline]
+    pub fn publish(&self, cs: CriticalSection<'_>, val: T) -> bool {
+        let overwritten = self.value.replace(cs, Some(val)).is_some();
+        if overwritten {
+            self.overwritten.set(cs, true);
+        }
+        overwritten
+    }
+
+    /// Takes the latest value, returning `None` if nothing was published since the last call.
+    ///
+    /// This also resets the [`overwritten`](Exchange::overwritten) indicator.
+    #[inline]
+    pub fn take(&self, cs: CriticalSection<'_>) -> Option<T> {
+        self.overwritten.set(cs, false);
+        self.value.take(cs)
+    }
+
+    /// Returns `true` if a value was overwritten before it could be taken since the last call to
+    /// [`take`](Exchange::take).
+    #[inline]
+    pub fn overwritten(&self, cs: CriticalSection<'_>) -> bool {
+        self.overwritten.get(cs)
+    }
+
+    /// Returns `true` if there is a value that hasn't been taken yet.
+    #[inline]
+    pub fn has_value(&self, cs: CriticalSection<'_>) -> bool {
+        // `Cell` can't be inspected without moving the value out, so put it back afterwards
+        let value = self.value.take(cs);
+        let has_value = value.is_some();
+        self.value.set(cs, value);
+        has_value
+    }
+}
+
+impl<T> Default for Exchange<T> {
+    fn default() -> Self {
+        Exchange::new()
+    }
+}
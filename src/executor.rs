@@ -0,0 +1,92 @@
+//! A minimal executor that runs a single future to completion.
+//!
+//! [`block_on`] polls the future whenever it has been woken, and calls the idle hook registered
+//! with [`set_idle_hook`] in between, e.g. to execute `WFI` so that the core sleeps until the
+//! next interrupt. Any waker created by the crate's primitives, like [`WakerSlot`] or
+//! [`AsyncSignal`], works with it.
+//!
+//! ```
+//! use bare_metal::executor;
+//! use bare_metal::AsyncSignal;
+//!
+//! static BUTTON: AsyncSignal<bool> = AsyncSignal::new();
+//!
+//! // On a Cortex-M, this would execute `cortex_m::asm::wfi()`
+//! fn idle() {}
+//!
+//! executor::set_idle_hook(idle);
+//! # BUTTON.signal(true);
+//! let pressed = executor::block_on(BUTTON.wait());
+//! # assert!(pressed);
+//! ```
+//!
+//! [`WakerSlot`]: crate::WakerSlot
+//! [`AsyncSignal`]: crate::AsyncSignal
+
+use core::future::Future;
+use core::pin::pin;
+use core::sync::atomic::{AtomicUsize, Ordering};
+use core::task::{Context, Poll, RawWaker, RawWakerVTable, Waker};
+
+use crate::Signal;
+
+// all wakers of `block_on` share this flag, so a waker that outlives its `block_on` call can't
+// dangle; the worst it can do is cause an extra poll
+static WOKEN: Signal = Signal::new();
+
+static IDLE_HOOK: AtomicUsize = AtomicUsize::new(0);
+
+const VTABLE: RawWakerVTable = RawWakerVTable::new(clone_raw, wake_raw, wake_raw, drop_raw);
+const RAW: RawWaker = RawWaker::new(core::ptr::null(), &VTABLE);
+
+fn clone_raw(_: *const ()) -> RawWaker {
+    RAW
+}
+
+fn wake_raw(_: *const ()) {
+    WOKEN.signal();
+}
+
+fn drop_raw(_: *const ()) {}
+
+/// Registers the function that [`block_on`] calls while the future can't make progress.
+///
+/// The hook is called inside a critical section, after checking that the future hasn't been
+/// woken, and must return once an interrupt is pending. `WFI` does this on ARM and RISC-V cores
+/// even with interrupts masked, so the wake-up by an interrupt handler can't be missed. Without a
+/// hook, [`block_on`] busy-waits.
+///
+/// This is meant to be called once, by the final application.
+pub fn set_idle_hook(hook: fn()) {
+    IDLE_HOOK.store(hook as usize, Ordering::Relaxed);
+}
+
+fn idle() {
+    match IDLE_HOOK.load(Ordering::Relaxed) {
+        0 => core::hint::spin_loop(),
+        // SAFETY: the only non-zero values ever stored are `fn()` pointers
+        hook => unsafe { core::mem::transmute::<usize, fn()>(hook)() },
+    }
+}
+
+/// Runs `future` to completion on the current core, sleeping with the idle hook in between
+/// polls.
+pub fn block_on<F: Future>(future: F) -> F::Output {
+    let mut future = pin!(future);
+    // SAFETY: the vtable functions don't use the data pointer, and are thread-safe
+    let waker = unsafe { Waker::from_raw(RAW) };
+    let mut cx = Context::from_waker(&waker);
+    loop {
+        WOKEN.clear();
+        if let Poll::Ready(output) = future.as_mut().poll(&mut cx) {
+            return output;
+        }
+        while !WOKEN.check_and_clear() {
+            crate::with_cs(|_cs| {
+                if !WOKEN.is_signaled() {
+                    idle();
+                }
+            });
+        }
+    }
+}
@@ -0,0 +1,161 @@
+//! Compiler and hardware memory barriers.
+//!
+//! Drivers need barriers around DMA transfers and some MMIO sequences, but the instructions
+//! that implement them are architecture specific, e.g. `DSB`, `DMB` and `ISB` on ARM. The
+//! [`Barriers`] trait names them, and the free functions of this module forward to the
+//! implementation that the final application registered with [`set_barriers`]. Until one is
+//! registered, all of them are compiler fences, see [`CompilerFence`].
+//!
+//! ```
+//! use bare_metal::fence::{self, Barriers};
+//! use core::sync::atomic::{self, Ordering};
+//!
+//! struct CortexM;
+//!
+//! unsafe impl Barriers for CortexM {
+//!     fn data_sync() {
+//!         // On a Cortex-M, this would execute `cortex_m::asm::dsb()`
+//!         atomic::fence(Ordering::SeqCst);
+//!     }
+//!
+//!     fn data_memory() {
+//!         atomic::fence(Ordering::SeqCst);
+//!     }
+//!
+//!     fn instruction_sync() {
+//!         atomic::fence(Ordering::SeqCst);
+//!     }
+//! }
+//!
+//! fence::set_barriers::<CortexM>();
+//!
+//! // In a driver, after setting up a DMA descriptor
+//! fence::data_sync();
+//! ```
+//!
+//! Code that is generic over `B: Barriers` can call the implementation directly instead, which
+//! avoids the indirect call.
+
+use core::ptr;
+use core::sync::atomic::{self, AtomicPtr, Ordering};
+
+/// Memory barriers of an architecture.
+///
+/// # Safety
+///
+/// Every method must at least act as `compiler_fence(Ordering::SeqCst)`. Code may rely on this
+/// for soundness; what the methods guarantee beyond that is specific to the architecture.
+pub unsafe trait Barriers {
+    /// Keeps the compiler from reordering memory accesses across this point.
+    ///
+    /// This doesn't emit any instruction, and usually doesn't need to be overridden.
+    #[inline]
+    fn compiler_fence() {
+        atomic::compiler_fence(Ordering::SeqCst);
+    }
+
+    /// Waits until all preceding memory accesses have completed, e.g. `DSB`.
+    fn data_sync();
+
+    /// Keeps the hardware from reordering memory accesses across this point, e.g. `DMB`.
+    fn data_memory();
+
+    /// Flushes the pipeline, so that later instructions observe the effects of preceding ones,
+    /// e.g. `ISB`.
+    fn instruction_sync();
+}
+
+/// [`Barriers`] that are all compiler fences.
+///
+/// This is enough on single-core systems without caches or DMA, and is used until an
+/// implementation is registered with [`set_barriers`].
+#[derive(Debug)]
+pub struct CompilerFence;
+
+unsafe impl Barriers for CompilerFence {
+    #[inline]
+    fn data_sync() {
+        atomic::compiler_fence(Ordering::SeqCst);
+    }
+
+    #[inline]
+    fn data_memory() {
+        atomic::compiler_fence(Ordering::SeqCst);
+    }
+
+    #[inline]
+    fn instruction_sync() {
+        atomic::compiler_fence(Ordering::SeqCst);
+    }
+}
+
+struct Table {
+    compiler_fence: fn(),
+    data_sync: fn(),
+    data_memory: fn(),
+    instruction_sync: fn(),
+}
+
+struct TableOf<B>(B);
+
+impl<B: Barriers> TableOf<B> {
+    const TABLE: &'static Table = &Table {
+        compiler_fence: B::compiler_fence,
+        data_sync: B::data_sync,
+        data_memory: B::data_memory,
+        instruction_sync: B::instruction_sync,
+    };
+}
+
+static BARRIERS: AtomicPtr<Table> = AtomicPtr::new(ptr::null_mut());
+
+/// Registers the barriers used by the free functions of this module.
+///
+/// This is meant to be called once, by the final application, before any driver that needs
+/// hardware barriers is used.
+pub fn set_barriers<B: Barriers>() {
+    let table: *const Table = TableOf::<B>::TABLE;
+    BARRIERS.store(table.cast_mut(), Ordering::Release);
+}
+
+#[inline]
+fn barriers() -> Option<&'static Table> {
+    // SAFETY: the only non-null values ever stored point to `'static` tables
+    unsafe { BARRIERS.load(Ordering::Acquire).as_ref() }
+}
+
+/// Calls [`Barriers::compiler_fence`] of the registered barriers.
+#[inline]
+pub fn compiler_fence() {
+    match barriers() {
+        Some(table) => (table.compiler_fence)(),
+        None => CompilerFence::compiler_fence(),
+    }
+}
+
+/// Calls [`Barriers::data_sync`] of the registered barriers.
+#[inline]
+pub fn data_sync() {
+    match barriers() {
+        Some(table) => (table.data_sync)(),
+        None => CompilerFence::data_sync(),
+    }
+}
+
+/// Calls [`Barriers::data_memory`] of the registered barriers.
+#[inline]
+pub fn data_memory() {
+    match barriers() {
+        Some(table) => (table.data_memory)(),
+        None => CompilerFence::data_memory(),
+    }
+}
+
+/// Calls [`Barriers::instruction_sync`] of the registered barriers.
+#[inline]
+pub fn instruction_sync() {
+    match barriers() {
+        Some(table) => (table.instruction_sync)(),
+        None => CompilerFence::instruction_sync(),
+    }
+}
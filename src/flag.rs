@@ -0,0 +1,95 @@
+use core::fmt;
+
+use crate::atomic::native::AtomicUsize;
+use crate::atomic::Ordering;
+
+/// A flag for notifying one context from others, using only atomic loads and stores.
+///
+/// This is an alternative to [`Signal`](crate::Signal) for targets without atomic
+/// compare-and-swap operations, such as `thumbv6m`, where `Signal` needs a critical section to
+/// clear the flag. `AtomicFlag` never enters a critical section: it counts the calls of
+/// [`set`](AtomicFlag::set), and remembers how many of them the checking context has already
+/// seen.
+///
+/// The flag is meant to be checked and cleared by a single context, e.g. the main loop, and set
+/// from contexts that can't preempt each other, e.g. interrupt handlers of the same priority.
+/// If two `set` calls race with each other, one of them may be lost.
+///
+/// ```
+/// use bare_metal::AtomicFlag;
+///
+/// static DATA_READY: AtomicFlag = AtomicFlag::new();
+///
+/// // In the interrupt handler
+/// DATA_READY.set();
+///
+/// // In the main loop
+/// assert!(DATA_READY.is_set());
+/// assert!(DATA_READY.check_and_clear());
+/// assert!(!DATA_READY.check_and_clear());
+/// ```
+pub struct AtomicFlag {
+    // only written by the contexts that set the flag
+    sets: AtomicUsize,
+    // only written by the context that clears the flag
+    seen: AtomicUsize,
+}
+
+impl AtomicFlag {
+    const_fn! {
+        /// Creates a flag that is not set.
+        #[inline]
+        pub const fn new() -> Self {
+            AtomicFlag {
+                sets: AtomicUsize::new(0),
+                seen: AtomicUsize::new(0),
+            }
+        }
+    }
+
+    /// Sets the flag.
+    #[inline]
+    pub fn set(&self) {
+        let sets = self.sets.load(Ordering::Relaxed);
+        self.sets.store(sets.wrapping_add(1), Ordering::Release);
+    }
+
+    /// Returns `true` if the flag is set, without clearing it.
+    #[inline]
+    pub fn is_set(&self) -> bool {
+        self.sets.load(Ordering::Acquire) != self.seen.load(Ordering::Relaxed)
+    }
+
+    /// Clears the flag and returns whether it was set.
+    #[inline]
+    pub fn check_and_clear(&self) -> bool {
+        let sets = self.sets.load(Ordering::Acquire);
+        if sets == self.seen.load(Ordering::Relaxed) {
+            false
+        } else {
+            self.seen.store(sets, Ordering::Relaxed);
+            true
+        }
+    }
+
+    /// Clears the flag.
+    #[inline]
+    pub fn clear(&self) {
+        self.seen
+            .store(self.sets.load(Ordering::Acquire), Ordering::Relaxed);
+    }
+}
+
+impl Default for AtomicFlag {
+    fn default() -> Self {
+        AtomicFlag::new()
+    }
+}
+
+impl fmt::Debug for AtomicFlag {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("AtomicFlag")
+            .field("set", &self.is_set())
+            .finish()
+    }
+}
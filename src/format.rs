@@ -0,0 +1,306 @@
+// `defmt::Format` implementations, mirroring the `Debug` implementations of the same types.
+//
+// Guards format the value they give access to, so that it can be logged directly. Everything else
+// only reports what its `Debug` implementation reports through the public API; in particular, the
+// data protected by a lock or a cell is never read, because that could race with its owner.
+//
+// The types behind the `std` feature are left out, since `defmt` targets `no_std` firmware.
+//
+// This module isn't called `defmt`, because the `defmt` macros refer to the crate as `defmt::`.
+
+use ::defmt::{Format, Formatter};
+use core::sync::atomic::Ordering;
+
+use crate::*;
+
+// Formats a type as its name, like `f.debug_struct(name).finish_non_exhaustive()` does.
+macro_rules! opaque {
+    ($($name:literal => [$($generics:tt)*] $ty:ty $(where $($bound:tt)*)?;)*) => {$(
+        impl<$($generics)*> Format for $ty $(where $($bound)*)? {
+            fn format(&self, f: Formatter<'_>) {
+                ::defmt::write!(f, "{=str} {{ .. }}", $name)
+            }
+        }
+    )*};
+}
+
+// Formats a guard as the value it dereferences to.
+macro_rules! forward {
+    ($([$($generics:tt)*] $ty:ty $(where $($bound:tt)*)?;)*) => {$(
+        impl<$($generics)*> Format for $ty $(where $($bound)*)? {
+            fn format(&self, f: Formatter<'_>) {
+                Format::format(&**self, f)
+            }
+        }
+    )*};
+}
+
+opaque! {
+    "AsyncMutex" => [T, const WAITERS: usize] AsyncMutex<T, WAITERS>;
+    "AsyncMutexLock" => [T, const WAITERS: usize] AsyncMutexLock<'_, T, WAITERS>;
+    "AsyncSignal" => [T] AsyncSignal<T>;
+    "AsyncSignalWait" => [T] AsyncSignalWait<'_, T>;
+    "BipBuffer" => [const N: usize] bip::BipBuffer<N>;
+    "Producer" => [const N: usize] bip::Producer<'_, N>;
+    "Consumer" => [const N: usize] bip::Consumer<'_, N>;
+    "AtomicBitSet" => [const WORDS: usize] AtomicBitSet<WORDS>;
+    "BitSetDrain" => [const WORDS: usize] BitSetDrain<'_, WORDS>;
+    "BrandedMutex" => [T] BrandedMutex<'_, T>;
+    "BusManager" => [T] BusManager<T>;
+    "BusHandle" => [T] BusHandle<'_, T>;
+    "CsCell" => [T] CsCell<T>;
+    "CsRefCell" => [T] CsRefCell<T>;
+    "CsGuard" => [] cs::CsGuard;
+    "BrandedCs" => [] cs::BrandedCs<'_>;
+    "DoubleBuffer" => [T] double_buffer::DoubleBuffer<T>;
+    "Writer" => [T] double_buffer::Writer<'_, T>;
+    "Reader" => [T] double_buffer::Reader<'_, T>;
+    "EventFlags" => [] EventFlags;
+    "Exchange" => [T] Exchange<T>;
+    "HandlerTable" => [const N: usize] HandlerTable<N>;
+    "Deferred" => [T, S] Deferred<T, S>;
+    "MaskGuard" => [C: InterruptController, I: InterruptNumber] MaskGuard<C, I>;
+    "CsLazy" => [T, F] CsLazy<T, F>;
+    "Mutex" => [T, P] lock::Mutex<T, P>;
+    "Queue" => [T, const N: usize] mpsc::Queue<T, N>;
+    "Channel" => [T] oneshot::Channel<T>;
+    "Sender" => [T] oneshot::Sender<'_, T>;
+    "Receiver" => [T] oneshot::Receiver<'_, T>;
+    "ReentrantMutex" => [T, C] ReentrantMutex<T, C>;
+    "RO" => [T] register::RO<T>;
+    "WO" => [T] register::WO<T>;
+    "RW" => [T] register::RW<T>;
+    "BitAccess" => [B] register::BitAccess<B>;
+    "MmioBlock" => [T] register::MmioBlock<T>;
+    "Reg" => [REG: register::RegisterSpec] register::Reg<REG>;
+    "RwLock" => [T] RwLock<T>;
+    "Semaphore" => [const MAX: usize] Semaphore<MAX>;
+    "Signal" => [] Signal;
+    "Singleton" => [T] Singleton<T>;
+    "InitStatic" => [T] InitStatic<T>;
+    "Producer" => [T, const N: usize] spsc::Producer<'_, T, N>;
+    "Consumer" => [T, const N: usize] spsc::Consumer<'_, T, N>;
+    "Recv" => [T, const N: usize] spsc::Recv<'_, '_, T, N>;
+    "VolatileCell" => [T] VolatileCell<T>;
+    "WakerSlot" => [] WakerSlot;
+    "ZstMutex" => [T] ZstMutex<T>;
+}
+
+#[cfg(feature = "embedded-hal")]
+opaque! {
+    "SpiHandle" => [T, CS, D] SpiHandle<'_, T, CS, D>;
+}
+
+forward! {
+    [T: Format, const WAITERS: usize] AsyncMutexGuard<'_, T, WAITERS>;
+    [T: Format] CsRefMut<'_, T>;
+    [T: Format] double_buffer::Front<'_, '_, T>;
+    [T: Format] MutexGuard<'_, T>;
+    [T: Format] PoolBox<'_, T>;
+    [T: Format, C: CoreId] ReentrantMutexGuard<'_, T, C>;
+    [T: Format] RwLockReadGuard<'_, T>;
+    [T: Format] RwLockWriteGuard<'_, T>;
+}
+
+#[cfg(target_has_atomic = "8")]
+forward! {
+    [T: Format] SpinLockGuard<'_, T>;
+}
+
+impl<const SIZE: usize> Format for BumpArena<SIZE> {
+    fn format(&self, f: Formatter<'_>) {
+        ::defmt::write!(
+            f,
+            "BumpArena {{ used: {=usize}, sealed: {=bool}, .. }}",
+            self.used(),
+            self.is_sealed()
+        )
+    }
+}
+
+impl<const MAX: usize, const WAITERS: usize> Format for AsyncSemaphore<MAX, WAITERS> {
+    fn format(&self, f: Formatter<'_>) {
+        ::defmt::write!(
+            f,
+            "AsyncSemaphore {{ available: {=usize}, .. }}",
+            self.available()
+        )
+    }
+}
+
+impl<const N: usize, W> Format for Barrier<N, W> {
+    fn format(&self, f: Formatter<'_>) {
+        ::defmt::write!(f, "Barrier {{ participants: {=usize}, .. }}", N)
+    }
+}
+
+impl<const N: usize> Format for bip::GrantW<'_, '_, N> {
+    fn format(&self, f: Formatter<'_>) {
+        ::defmt::write!(f, "GrantW {{ len: {=usize}, .. }}", self.len())
+    }
+}
+
+impl<const N: usize> Format for bip::GrantR<'_, '_, N> {
+    fn format(&self, f: Formatter<'_>) {
+        ::defmt::write!(f, "GrantR {{ len: {=usize}, .. }}", self.len())
+    }
+}
+
+impl Format for BrandToken<'_> {
+    fn format(&self, f: Formatter<'_>) {
+        ::defmt::write!(f, "BrandToken")
+    }
+}
+
+impl<T: Format, A: align::Alignment> Format for CachePadded<T, A> {
+    fn format(&self, f: Formatter<'_>) {
+        ::defmt::write!(f, "CachePadded({})", &**self)
+    }
+}
+
+impl<T, P, const CEILING: u8> Format for CeilingMutex<T, P, CEILING> {
+    fn format(&self, f: Formatter<'_>) {
+        ::defmt::write!(f, "CeilingMutex {{ ceiling: {=u8}, .. }}", CEILING)
+    }
+}
+
+impl<const LEVEL: u8> Format for ThresholdToken<'_, LEVEL> {
+    fn format(&self, f: Formatter<'_>) {
+        ::defmt::write!(f, "ThresholdToken {{ level: {=u8} }}", LEVEL)
+    }
+}
+
+impl<T, const CEILING: u8> Format for ThresholdMutex<T, CEILING> {
+    fn format(&self, f: Formatter<'_>) {
+        ::defmt::write!(f, "ThresholdMutex {{ ceiling: {=u8}, .. }}", CEILING)
+    }
+}
+
+impl Format for CsAtomicBool {
+    fn format(&self, f: Formatter<'_>) {
+        Format::format(&self.load(Ordering::Relaxed), f)
+    }
+}
+
+macro_rules! cs_atomic {
+    ($($name:ident)*) => {$(
+        impl Format for $name {
+            fn format(&self, f: Formatter<'_>) {
+                Format::format(&self.load(Ordering::Relaxed), f)
+            }
+        }
+    )*};
+}
+
+cs_atomic! { CsAtomicU8 CsAtomicU16 CsAtomicU32 CsAtomicUsize }
+
+impl<const N: usize, const ALIGN: usize> Format for dma::DmaBuffer<N, ALIGN>
+where
+    align::ConstAlign<ALIGN>: align::SupportedAlignment,
+{
+    fn format(&self, f: Formatter<'_>) {
+        ::defmt::write!(
+            f,
+            "DmaBuffer {{ len: {=usize}, align: {=usize}, .. }}",
+            N,
+            ALIGN
+        )
+    }
+}
+
+impl Format for AtomicFlag {
+    fn format(&self, f: Formatter<'_>) {
+        ::defmt::write!(f, "AtomicFlag {{ set: {=bool} }}", self.is_set())
+    }
+}
+
+#[cfg(feature = "embedded-hal")]
+impl<B: Format, P: Format> Format for SpiHandleError<B, P> {
+    fn format(&self, f: Formatter<'_>) {
+        match self {
+            SpiHandleError::Bus(e) => ::defmt::write!(f, "Bus({})", e),
+            SpiHandleError::ChipSelect(e) => ::defmt::write!(f, "ChipSelect({})", e),
+        }
+    }
+}
+
+#[cfg(feature = "alloc")]
+impl<const SIZE: usize, const N: usize> Format for PoolAllocator<SIZE, N> {
+    fn format(&self, f: Formatter<'_>) {
+        ::defmt::write!(
+            f,
+            "PoolAllocator {{ available: {=usize}, .. }}",
+            self.available()
+        )
+    }
+}
+
+impl<T: Format> Format for InitCell<T> {
+    fn format(&self, f: Formatter<'_>) {
+        ::defmt::write!(f, "InitCell({})", self.try_get())
+    }
+}
+
+#[cfg(feature = "instrument")]
+impl Format for instrument::Stats {
+    fn format(&self, f: Formatter<'_>) {
+        ::defmt::write!(
+            f,
+            "Stats {{ entries: {=u32}, max_duration: {=u32}, borrow_failures: {=u32}, \
+             max_latency: {=u32} }}",
+            self.entries,
+            self.max_duration,
+            self.borrow_failures,
+            self.max_latency
+        )
+    }
+}
+
+impl<const BITS: u8> Format for Priority<BITS> {
+    fn format(&self, f: Formatter<'_>) {
+        ::defmt::write!(f, "Priority {{ level: {=u8} }}", self.level())
+    }
+}
+
+impl<T, const N: usize> Format for mpmc::Queue<T, N> {
+    fn format(&self, f: Formatter<'_>) {
+        ::defmt::write!(f, "Queue {{ len: {=usize}, .. }}", self.len())
+    }
+}
+
+impl Format for oneshot::Canceled {
+    fn format(&self, f: Formatter<'_>) {
+        ::defmt::write!(f, "Canceled")
+    }
+}
+
+impl<T, const N: usize> Format for Pool<T, N> {
+    fn format(&self, f: Formatter<'_>) {
+        ::defmt::write!(f, "Pool {{ available: {=usize}, .. }}", self.available())
+    }
+}
+
+impl<T: Copy + Format> Format for SeqLock<T> {
+    fn format(&self, f: Formatter<'_>) {
+        ::defmt::write!(f, "SeqLock {{ value: {} }}", self.read())
+    }
+}
+
+impl<T> Format for Taken<T> {
+    fn format(&self, f: Formatter<'_>) {
+        ::defmt::write!(f, "Taken")
+    }
+}
+
+#[cfg(target_has_atomic = "8")]
+impl<T> Format for SpinLock<T> {
+    fn format(&self, f: Formatter<'_>) {
+        ::defmt::write!(f, "SpinLock {{ locked: {=bool}, .. }}", self.is_locked())
+    }
+}
+
+impl<T, const N: usize> Format for spsc::Queue<T, N> {
+    fn format(&self, f: Formatter<'_>) {
+        ::defmt::write!(f, "Queue {{ len: {=usize}, .. }}", self.len())
+    }
+}
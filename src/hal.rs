@@ -0,0 +1,177 @@
+//! `embedded-hal` implementations for [`BusHandle`].
+
+use core::fmt;
+
+use embedded_hal::delay::DelayNs;
+use embedded_hal::digital::OutputPin;
+use embedded_hal::i2c::{self, AddressMode, I2c};
+use embedded_hal::spi::{self, ErrorKind, SpiBus, SpiDevice};
+
+use crate::BusHandle;
+
+impl<T: i2c::ErrorType> i2c::ErrorType for BusHandle<'_, T> {
+    type Error = T::Error;
+}
+
+// each call is forwarded inside a single critical section, so the transactions of drivers
+// sharing the bus never interleave
+impl<A: AddressMode, T: I2c<A>> I2c<A> for BusHandle<'_, T> {
+    #[inline]
+    fn read(&mut self, address: A, read: &mut [u8]) -> Result<(), Self::Error> {
+        self.lock(|bus| bus.read(address, read))
+    }
+
+    #[inline]
+    fn write(&mut self, address: A, write: &[u8]) -> Result<(), Self::Error> {
+        self.lock(|bus| bus.write(address, write))
+    }
+
+    #[inline]
+    fn write_read(&mut self, address: A, write: &[u8], read: &mut [u8]) -> Result<(), Self::Error> {
+        self.lock(|bus| bus.write_read(address, write, read))
+    }
+
+    #[inline]
+    fn transaction(
+        &mut self,
+        address: A,
+        operations: &mut [i2c::Operation<'_>],
+    ) -> Result<(), Self::Error> {
+        self.lock(|bus| bus.transaction(address, operations))
+    }
+}
+
+/// A device on an SPI bus shared through a [`BusManager`](crate::BusManager).
+///
+/// This pairs a [`BusHandle`] to an [`SpiBus`] with the chip select pin of one device, and a
+/// delay for the [`DelayNs`](spi::Operation::DelayNs) operations of its transactions. Each
+/// transaction runs in a single critical section, from asserting chip select to deasserting it
+/// after flushing the bus. This type is only available with the `embedded-hal` feature.
+///
+/// ```
+/// use bare_metal::{BusManager, SpiHandle};
+/// use core::convert::Infallible;
+/// use embedded_hal::{delay::DelayNs, digital, spi, spi::SpiDevice};
+///
+/// // stand-ins for the HAL's types
+/// struct Spi {
+///     sent: Vec<u8>,
+/// }
+///
+/// impl spi::ErrorType for Spi {
+///     type Error = Infallible;
+/// }
+///
+/// impl spi::SpiBus for Spi {
+///     # fn read(&mut self, _: &mut [u8]) -> Result<(), Infallible> { Ok(()) }
+///     fn write(&mut self, words: &[u8]) -> Result<(), Infallible> {
+///         self.sent.extend_from_slice(words);
+///         Ok(())
+///     }
+///     // ...
+///     # fn transfer(&mut self, _: &mut [u8], _: &[u8]) -> Result<(), Infallible> { Ok(()) }
+///     # fn transfer_in_place(&mut self, _: &mut [u8]) -> Result<(), Infallible> { Ok(()) }
+///     # fn flush(&mut self) -> Result<(), Infallible> { Ok(()) }
+/// }
+///
+/// struct Pin;
+/// # impl digital::ErrorType for Pin { type Error = Infallible; }
+/// # impl digital::OutputPin for Pin {
+/// #     fn set_low(&mut self) -> Result<(), Infallible> { Ok(()) }
+/// #     fn set_high(&mut self) -> Result<(), Infallible> { Ok(()) }
+/// # }
+/// struct Delay;
+/// # impl DelayNs for Delay { fn delay_ns(&mut self, _: u32) {} }
+///
+/// static SPI: BusManager<Spi> = BusManager::new(Spi { sent: Vec::new() });
+///
+/// // each driver takes its own `SpiDevice`
+/// let mut display = SpiHandle::new(SPI.acquire(), Pin, Delay);
+/// let mut flash = SpiHandle::new(SPI.acquire(), Pin, Delay);
+/// display.write(&[0x2c]).unwrap();
+/// flash.write(&[0x06]).unwrap();
+/// assert_eq!(SPI.acquire().lock(|spi| spi.sent.clone()), [0x2c, 0x06]);
+/// ```
+pub struct SpiHandle<'a, T, CS, D> {
+    bus: BusHandle<'a, T>,
+    cs: CS,
+    delay: D,
+}
+
+impl<'a, T, CS, D> SpiHandle<'a, T, CS, D> {
+    /// Creates a device that selects itself on `bus` with the `cs` pin.
+    ///
+    /// The pin should already be set high.
+    #[inline]
+    pub fn new(bus: BusHandle<'a, T>, cs: CS, delay: D) -> Self {
+        SpiHandle { bus, cs, delay }
+    }
+
+    /// Returns the handle to the bus, the chip select pin and the delay.
+    #[inline]
+    pub fn into_parts(self) -> (BusHandle<'a, T>, CS, D) {
+        (self.bus, self.cs, self.delay)
+    }
+}
+
+impl<T, CS, D> fmt::Debug for SpiHandle<'_, T, CS, D> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("SpiHandle").finish_non_exhaustive()
+    }
+}
+
+/// The error of an [`SpiHandle`].
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum SpiHandleError<B, P> {
+    /// The bus failed.
+    Bus(B),
+    /// Setting the chip select pin failed.
+    ChipSelect(P),
+}
+
+impl<B: spi::Error, P: fmt::Debug> spi::Error for SpiHandleError<B, P> {
+    fn kind(&self) -> ErrorKind {
+        match self {
+            SpiHandleError::Bus(err) => err.kind(),
+            SpiHandleError::ChipSelect(_) => ErrorKind::ChipSelectFault,
+        }
+    }
+}
+
+impl<T: spi::ErrorType, CS: OutputPin, D> spi::ErrorType for SpiHandle<'_, T, CS, D> {
+    type Error = SpiHandleError<T::Error, CS::Error>;
+}
+
+impl<W, T, CS, D> SpiDevice<W> for SpiHandle<'_, T, CS, D>
+where
+    W: Copy + 'static,
+    T: SpiBus<W>,
+    CS: OutputPin,
+    D: DelayNs,
+{
+    fn transaction(&mut self, operations: &mut [spi::Operation<'_, W>]) -> Result<(), Self::Error> {
+        let SpiHandle { bus, cs, delay } = self;
+        bus.lock(|bus| {
+            cs.set_low().map_err(SpiHandleError::ChipSelect)?;
+            let result = operations
+                .iter_mut()
+                .try_for_each(|operation| match operation {
+                    spi::Operation::Read(words) => bus.read(words),
+                    spi::Operation::Write(words) => bus.write(words),
+                    spi::Operation::Transfer(read, write) => bus.transfer(read, write),
+                    spi::Operation::TransferInPlace(words) => bus.transfer_in_place(words),
+                    spi::Operation::DelayNs(ns) => {
+                        bus.flush()?;
+                        delay.delay_ns(*ns);
+                        Ok(())
+                    }
+                });
+            // deselect the device even if an operation failed
+            let flushed = bus.flush();
+            let deselected = cs.set_high();
+            result.map_err(SpiHandleError::Bus)?;
+            flushed.map_err(SpiHandleError::Bus)?;
+            deselected.map_err(SpiHandleError::ChipSelect)
+        })
+    }
+}
@@ -0,0 +1,115 @@
+//! `GlobalAlloc` implementations for [`Pool`] and [`BumpArena`] allocators.
+
+use core::alloc::{GlobalAlloc, Layout};
+use core::fmt;
+use core::mem::MaybeUninit;
+use core::ptr::{self, NonNull};
+
+use crate::{BumpArena, Pool};
+
+// the alignment of the chunks of a `PoolAllocator`
+const CHUNK_ALIGN: usize = 8;
+
+#[repr(C, align(8))]
+struct Chunk<const SIZE: usize> {
+    _bytes: [MaybeUninit<u8>; SIZE],
+}
+
+/// A tiny heap of `N` chunks of `SIZE` bytes, to be used as the `#[global_allocator]`.
+///
+/// Every allocation takes one chunk of a [`Pool`], so it must be at most `SIZE` bytes large and
+/// aligned to at most 8 bytes; other allocations fail. This is enough for a few boxed callbacks
+/// or small collections without pulling in a separate allocator crate. Allocating and freeing
+/// each take one short critical section. This type is only available with the `alloc` feature.
+///
+/// ```no_run
+/// extern crate alloc;
+///
+/// use alloc::boxed::Box;
+/// use bare_metal::PoolAllocator;
+///
+/// #[global_allocator]
+/// static HEAP: PoolAllocator<32, 16> = PoolAllocator::new();
+///
+/// let callback: Box<dyn Fn() -> u32> = Box::new(|| 42);
+/// assert_eq!(callback(), 42);
+/// ```
+///
+/// The allocator can also be used directly.
+///
+/// ```
+/// use bare_metal::PoolAllocator;
+/// use core::alloc::{GlobalAlloc, Layout};
+///
+/// static HEAP: PoolAllocator<32, 1> = PoolAllocator::new();
+///
+/// unsafe {
+///     let layout = Layout::new::<[u32; 4]>();
+///     let ptr = HEAP.alloc(layout);
+///     assert!(!ptr.is_null());
+///     assert!(HEAP.alloc(layout).is_null());
+///     HEAP.dealloc(ptr, layout);
+///     assert!(HEAP.alloc(Layout::new::<[u32; 16]>()).is_null());
+/// }
+/// ```
+pub struct PoolAllocator<const SIZE: usize, const N: usize> {
+    pool: Pool<Chunk<SIZE>, N>,
+}
+
+impl<const SIZE: usize, const N: usize> PoolAllocator<SIZE, N> {
+    /// Creates an allocator whose chunks are all free.
+    #[inline]
+    pub const fn new() -> Self {
+        PoolAllocator { pool: Pool::new() }
+    }
+
+    /// Returns the number of free chunks.
+    #[inline]
+    pub fn available(&self) -> usize {
+        self.pool.available()
+    }
+}
+
+unsafe impl<const SIZE: usize, const N: usize> GlobalAlloc for PoolAllocator<SIZE, N> {
+    unsafe fn alloc(&self, layout: Layout) -> *mut u8 {
+        if layout.size() > SIZE || layout.align() > CHUNK_ALIGN {
+            return ptr::null_mut();
+        }
+        match self.pool.alloc_raw() {
+            Some(chunk) => chunk.as_ptr().cast(),
+            None => ptr::null_mut(),
+        }
+    }
+
+    unsafe fn dealloc(&self, ptr: *mut u8, _layout: Layout) {
+        // SAFETY: `ptr` was returned by `alloc`, which only hands out chunks of the pool
+        self.pool.free_raw(NonNull::new_unchecked(ptr.cast()));
+    }
+}
+
+impl<const SIZE: usize, const N: usize> Default for PoolAllocator<SIZE, N> {
+    fn default() -> Self {
+        PoolAllocator::new()
+    }
+}
+
+impl<const SIZE: usize, const N: usize> fmt::Debug for PoolAllocator<SIZE, N> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("PoolAllocator")
+            .field("available", &self.available())
+            .finish_non_exhaustive()
+    }
+}
+
+/// A [`BumpArena`] can be used as the `#[global_allocator]` of programs that only allocate
+/// during start-up.
+///
+/// Deallocating never frees any memory, and once the arena is sealed every allocation fails.
+/// This implementation is only available with the `alloc` feature.
+unsafe impl<const SIZE: usize> GlobalAlloc for BumpArena<SIZE> {
+    unsafe fn alloc(&self, layout: Layout) -> *mut u8 {
+        self.reserve(layout).unwrap_or(ptr::null_mut())
+    }
+
+    unsafe fn dealloc(&self, _ptr: *mut u8, _layout: Layout) {}
+}
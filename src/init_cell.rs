@@ -0,0 +1,152 @@
+use core::cell::UnsafeCell;
+use core::fmt;
+use core::mem::MaybeUninit;
+use core::sync::atomic::{AtomicBool, Ordering};
+
+/// A cell that is written once during start-up and read without synchronization afterwards.
+///
+/// This is meant for `static`s that are set up in `main` before interrupts are enabled (or
+/// before other cores are started). Initializing the cell is `unsafe` because nothing keeps
+/// another context from reading it concurrently; once it is initialized, every read is a plain
+/// shared reference with no critical section or lock involved.
+///
+/// ```
+/// use bare_metal::InitCell;
+///
+/// struct Config {
+///     baud: u32,
+/// }
+///
+/// static CONFIG: InitCell<Config> = InitCell::new();
+///
+/// // In `main`, before interrupts are enabled
+/// unsafe { CONFIG.init(Config { baud: 115_200 }) };
+///
+/// // In an interrupt handler
+/// assert_eq!(CONFIG.get().baud, 115_200);
+/// ```
+pub struct InitCell<T> {
+    initialized: AtomicBool,
+    value: UnsafeCell<MaybeUninit<T>>,
+}
+
+impl<T> InitCell<T> {
+    /// Creates a new, uninitialized cell.
+    #[inline]
+    pub const fn new() -> Self {
+        InitCell {
+            initialized: AtomicBool::new(false),
+            value: UnsafeCell::new(MaybeUninit::uninit()),
+        }
+    }
+
+    /// Initializes the cell and returns a reference to the value.
+    ///
+    /// # Safety
+    ///
+    /// No other context may access the cell while this call is in progress. In practice this
+    /// means calling it before enabling the interrupts, or starting the cores, that use the cell.
+    ///
+    /// # Panics
+    ///
+    /// Panics if the cell is already initialized.
+    #[inline]
+    #[track_caller]
+    pub unsafe fn init(&self, value: T) -> &T {
+        if self.initialized.load(Ordering::Relaxed) {
+            already_initialized();
+        }
+        let slot = &mut *self.value.get();
+        let value = slot.write(value);
+        self.initialized.store(true, Ordering::Release);
+        value
+    }
+
+    /// Returns a reference to the value, or `None` if the cell isn't initialized yet.
+    #[inline]
+    pub fn try_get(&self) -> Option<&T> {
+        if self.initialized.load(Ordering::Acquire) {
+            // SAFETY: the value was written before the flag and is never written again
+            Some(unsafe { (*self.value.get()).assume_init_ref() })
+        } else {
+            None
+        }
+    }
+
+    /// Returns a reference to the value.
+    ///
+    /// # Panics
+    ///
+    /// Panics if the cell isn't initialized yet.
+    #[inline]
+    #[track_caller]
+    pub fn get(&self) -> &T {
+        match self.try_get() {
+            Some(value) => value,
+            None => uninitialized(),
+        }
+    }
+
+    /// Returns `true` if the cell has been initialized.
+    #[inline]
+    pub fn is_initialized(&self) -> bool {
+        self.initialized.load(Ordering::Acquire)
+    }
+
+    /// Returns a mutable reference to the value, or `None` if the cell isn't initialized.
+    ///
+    /// This does not need any `unsafe` since `&mut self` already guarantees exclusive access.
+    #[inline]
+    pub fn get_mut(&mut self) -> Option<&mut T> {
+        if *self.initialized.get_mut() {
+            Some(unsafe { self.value.get_mut().assume_init_mut() })
+        } else {
+            None
+        }
+    }
+
+    /// Consumes the cell, returning the value if it was initialized.
+    #[inline]
+    pub fn into_inner(mut self) -> Option<T> {
+        if core::mem::replace(self.initialized.get_mut(), false) {
+            Some(unsafe { self.value.get().read().assume_init() })
+        } else {
+            None
+        }
+    }
+}
+
+#[cold]
+#[track_caller]
+fn already_initialized() -> ! {
+    panic!("InitCell already initialized")
+}
+
+#[cold]
+#[track_caller]
+fn uninitialized() -> ! {
+    panic!("InitCell not initialized")
+}
+
+impl<T> Default for InitCell<T> {
+    fn default() -> Self {
+        InitCell::new()
+    }
+}
+
+impl<T: fmt::Debug> fmt::Debug for InitCell<T> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_tuple("InitCell").field(&self.try_get()).finish()
+    }
+}
+
+impl<T> Drop for InitCell<T> {
+    fn drop(&mut self) {
+        if *self.initialized.get_mut() {
+            unsafe { self.value.get_mut().assume_init_drop() }
+        }
+    }
+}
+
+// NOTE the value is only written once, by `init`, whose contract rules out concurrent access
+unsafe impl<T> Sync for InitCell<T> where T: Send + Sync {}
@@ -0,0 +1,374 @@
+//! Measurement of the time spent in critical sections.
+//!
+//! With the `instrument` feature enabled, every critical section entered by this crate, and
+//! every one entered through [`with`], is timestamped on entry and exit. The longest duration
+//! observed so far is available from [`max_duration`], which helps to find the worst-case time
+//! during which interrupts are masked.
+//!
+//! The timestamps come from a `fn() -> u32` that reads a cycle counter, which the final
+//! application registers at run time with [`set_cycle_counter`]. Until one is registered,
+//! nothing is measured.
+//!
+//! Critical sections entered directly through `critical_section::with` are not measured.
+//!
+//! [`stats`] reports aggregate counters, like the number of critical sections that were entered
+//! and how often borrowing a value failed, for health monitoring.
+//!
+//! During development, [`set_budget`] turns this into a watchdog: a critical section that lasts
+//! longer than the budget panics, or calls a hook, when it ends. This catches mistakes like a
+//! blocking delay inside a critical section early.
+//!
+//! [`set_mask_hooks`] registers callbacks for when the outermost measured critical section is
+//! entered and left, e.g. to toggle a pin for a logic analyzer. Calling [`interrupt_entered`] at
+//! the start of interrupt handlers records how long an interrupt that became pending during a
+//! critical section waited after the critical section ended, see [`set_pending_check`].
+//!
+//! ```
+//! use bare_metal::instrument;
+//! use core::sync::atomic::{AtomicU32, Ordering};
+//!
+//! static CYCLES: AtomicU32 = AtomicU32::new(0);
+//!
+//! fn now() -> u32 {
+//!     // On a Cortex-M, this would read `DWT::cycle_count()`
+//!     CYCLES.fetch_add(10, Ordering::Relaxed)
+//! }
+//!
+//! instrument::set_cycle_counter(now);
+//! instrument::with(|_cs| {});
+//! assert_eq!(instrument::max_duration(), 10);
+//! ```
+//!
+//! This module is only available when the `instrument` feature is enabled.
+
+use core::cell::Cell;
+
+use critical_section::{CriticalSection, Mutex};
+
+#[derive(Clone, Copy)]
+struct State {
+    now: Option<fn() -> u32>,
+    stats: Stats,
+    budget: Option<Budget>,
+    depth: usize,
+    on_mask: Option<fn()>,
+    on_unmask: Option<fn()>,
+    pending: Option<fn() -> bool>,
+    unmasked_at: Option<u32>,
+}
+
+#[derive(Clone, Copy)]
+struct Budget {
+    limit: u32,
+    on_exceeded: Option<fn(u32)>,
+}
+
+static STATE: Mutex<Cell<State>> = Mutex::new(Cell::new(State {
+    now: None,
+    stats: Stats {
+        entries: 0,
+        max_duration: 0,
+        borrow_failures: 0,
+        max_latency: 0,
+    },
+    budget: None,
+    depth: 0,
+    on_mask: None,
+    on_unmask: None,
+    pending: None,
+    unmasked_at: None,
+}));
+
+/// Aggregate counters collected by this module.
+///
+/// The counters wrap around on overflow.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+#[non_exhaustive]
+pub struct Stats {
+    /// The number of critical sections entered.
+    pub entries: u32,
+    /// The longest critical section duration observed, in counter ticks.
+    ///
+    /// This stays zero until a cycle counter is registered with [`set_cycle_counter`].
+    pub max_duration: u32,
+    /// The number of times borrowing a value failed because it was already borrowed.
+    ///
+    /// This counts failed borrows of [`CsRefCell`](crate::CsRefCell)s, and of
+    /// `Mutex<RefCell<T>>`s through [`MutexRefCellExt`](crate::MutexRefCellExt).
+    pub borrow_failures: u32,
+    /// The longest time from the end of an outermost critical section during which an interrupt
+    /// became pending to the next call of [`interrupt_entered`], in counter ticks.
+    ///
+    /// This stays zero until a check for pending interrupts is registered with
+    /// [`set_pending_check`].
+    pub max_latency: u32,
+}
+
+/// Returns the counters collected so far.
+///
+/// ```
+/// use bare_metal::instrument;
+///
+/// let before = instrument::stats().entries;
+/// instrument::with(|_cs| {});
+/// assert_eq!(instrument::stats().entries, before + 1);
+/// ```
+pub fn stats() -> Stats {
+    critical_section::with(|cs| STATE.borrow(cs).get().stats)
+}
+
+/// Resets all counters to zero, returning their previous values.
+pub fn reset_stats() -> Stats {
+    update(|state| core::mem::take(&mut state.stats))
+}
+
+/// Registers the function used to timestamp critical sections.
+///
+/// `now` must return the value of a free-running counter. The counter may wrap around;
+/// durations are computed with wrapping arithmetic, so they are correct as long as a critical
+/// section is shorter than a full period of the counter.
+///
+/// This is meant to be called once, by the final application. Calling it again replaces the
+/// function and resets the longest observed duration.
+pub fn set_cycle_counter(now: fn() -> u32) {
+    update(|state| {
+        state.now = Some(now);
+        state.stats.max_duration = 0;
+    })
+}
+
+/// Sets the longest a critical section may last, in counter ticks.
+///
+/// When a critical section that lasted longer than `budget` ends, `on_exceeded` is called with
+/// its duration, after the critical section has been left. Without a hook, this panics instead.
+///
+/// ```should_panic
+/// use bare_metal::instrument;
+/// use core::sync::atomic::{AtomicU32, Ordering};
+///
+/// static CYCLES: AtomicU32 = AtomicU32::new(0);
+///
+/// instrument::set_cycle_counter(|| CYCLES.fetch_add(10, Ordering::Relaxed));
+/// instrument::set_budget(Some(5), None);
+/// // Panics: "critical section took 10 ticks, the budget is 5"
+/// instrument::with(|_cs| {});
+/// ```
+///
+/// Passing `None` as `budget` disables the check.
+pub fn set_budget(budget: Option<u32>, on_exceeded: Option<fn(u32)>) {
+    update(|state| state.budget = budget.map(|limit| Budget { limit, on_exceeded }))
+}
+
+/// Registers callbacks for entering and leaving critical sections.
+///
+/// `on_mask` is called after the outermost measured critical section has been entered, and
+/// `on_unmask` right before it is left. Both are called inside the critical section. Nesting is
+/// only known for the critical sections measured by this module, so if the outermost one is
+/// entered directly through `critical_section::with`, the callbacks are called for the
+/// outermost measured one inside of it instead.
+pub fn set_mask_hooks(on_mask: Option<fn()>, on_unmask: Option<fn()>) {
+    update(|state| {
+        state.on_mask = on_mask;
+        state.on_unmask = on_unmask;
+    })
+}
+
+/// Registers the function that tells whether an interrupt is pending.
+///
+/// `pending` is called right before the outermost measured critical section is left, and should
+/// return whether any interrupt is waiting to be serviced, e.g. by reading the `ISRPENDING` bit of
+/// the `ICSR` register on a Cortex-M. Only then does the next call of [`interrupt_entered`]
+/// record a latency, so that interrupts that arrive after the critical section aren't counted.
+///
+/// Passing `None` stops recording latencies.
+pub fn set_pending_check(pending: Option<fn() -> bool>) {
+    update(|state| {
+        state.pending = pending;
+        state.unmasked_at = None;
+    })
+}
+
+/// Records that an interrupt handler was entered.
+///
+/// Call this at the start of interrupt handlers. If an interrupt was pending when the outermost
+/// measured critical section ended, as reported by the function registered with
+/// [`set_pending_check`], the first call afterwards records the time since the end of the
+/// critical section. The longest one is reported as [`Stats::max_latency`].
+///
+/// This is how long a pending interrupt waited after interrupts were unmasked again. The time it
+/// spent pending before that isn't known, but it is at most the duration of the critical section.
+///
+/// ```
+/// use bare_metal::instrument;
+/// use core::sync::atomic::{AtomicBool, AtomicU32, Ordering};
+///
+/// static CYCLES: AtomicU32 = AtomicU32::new(0);
+/// static PENDING: AtomicBool = AtomicBool::new(false);
+///
+/// instrument::set_cycle_counter(|| CYCLES.fetch_add(10, Ordering::Relaxed));
+/// instrument::set_pending_check(Some(|| PENDING.load(Ordering::Relaxed)));
+/// instrument::with(|_cs| {
+///     // an interrupt becomes pending here
+///     PENDING.store(true, Ordering::Relaxed);
+/// });
+///
+/// // In the interrupt handler
+/// PENDING.store(false, Ordering::Relaxed);
+/// instrument::interrupt_entered();
+/// assert_eq!(instrument::stats().max_latency, 10);
+///
+/// // Nothing was pending at the end of this one
+/// instrument::with(|_cs| {});
+/// CYCLES.fetch_add(1000, Ordering::Relaxed);
+/// instrument::interrupt_entered();
+/// assert_eq!(instrument::stats().max_latency, 10);
+/// ```
+pub fn interrupt_entered() {
+    update(|state| {
+        if let (Some(now), Some(unmasked_at)) = (state.now, state.unmasked_at.take()) {
+            let latency = now().wrapping_sub(unmasked_at);
+            if latency > state.stats.max_latency {
+                state.stats.max_latency = latency;
+            }
+        }
+    })
+}
+
+fn update<R>(f: impl FnOnce(&mut State) -> R) -> R {
+    critical_section::with(|cs| {
+        let state = STATE.borrow(cs);
+        let mut current = state.get();
+        let r = f(&mut current);
+        state.set(current);
+        r
+    })
+}
+
+#[inline]
+pub(crate) fn start(cs: CriticalSection<'_>) -> Option<u32> {
+    let state = STATE.borrow(cs);
+    let mut current = state.get();
+    current.stats.entries = current.stats.entries.wrapping_add(1);
+    current.depth += 1;
+    state.set(current);
+    if current.depth == 1 {
+        if let Some(on_mask) = current.on_mask {
+            on_mask();
+        }
+    }
+    current.now.map(|now| now())
+}
+
+#[inline]
+pub(crate) fn borrow_failed(cs: CriticalSection<'_>) {
+    let state = STATE.borrow(cs);
+    let mut current = state.get();
+    current.stats.borrow_failures = current.stats.borrow_failures.wrapping_add(1);
+    state.set(current);
+}
+
+/// A critical section that exceeded its budget.
+///
+/// This is reported after the critical section has been left, so that neither the hook nor the
+/// panic runs with interrupts masked.
+#[must_use]
+pub(crate) struct Overrun {
+    duration: u32,
+    budget: u32,
+    on_exceeded: Option<fn(u32)>,
+}
+
+impl Overrun {
+    #[track_caller]
+    pub(crate) fn report(self) {
+        match self.on_exceeded {
+            Some(hook) => hook(self.duration),
+            None => panic!(
+                "critical section took {} ticks, the budget is {}",
+                self.duration, self.budget
+            ),
+        }
+    }
+}
+
+#[inline]
+pub(crate) fn record(cs: CriticalSection<'_>, start: Option<u32>) -> Option<Overrun> {
+    let state = STATE.borrow(cs);
+    let mut current = state.get();
+    current.depth = current.depth.saturating_sub(1);
+    let outermost = current.depth == 0;
+    let end = current.now.map(|now| now());
+    if outermost {
+        current.unmasked_at = end.filter(|_| current.pending.is_some_and(|pending| pending()));
+    }
+    let duration = match (start, end) {
+        (Some(start), Some(end)) => Some(end.wrapping_sub(start)),
+        _ => None,
+    };
+    if let Some(duration) = duration {
+        if duration > current.stats.max_duration {
+            current.stats.max_duration = duration;
+        }
+    }
+    state.set(current);
+    if outermost {
+        if let Some(on_unmask) = current.on_unmask {
+            on_unmask();
+        }
+    }
+    let duration = duration?;
+    match current.budget {
+        Some(budget) if duration > budget.limit => Some(Overrun {
+            duration,
+            budget: budget.limit,
+            on_exceeded: budget.on_exceeded,
+        }),
+        _ => None,
+    }
+}
+
+/// Executes `f` in a critical section and records how long the critical section lasted.
+///
+/// This is a drop-in replacement for `critical_section::with`.
+#[inline]
+#[track_caller]
+pub fn with<R>(f: impl FnOnce(CriticalSection<'_>) -> R) -> R {
+    // records the end of the critical section even if `f` panics, like `CsGuard` does, so that
+    // the nesting depth stays balanced
+    struct Measure<'a, 'cs> {
+        cs: CriticalSection<'cs>,
+        start: Option<u32>,
+        overrun: &'a mut Option<Overrun>,
+    }
+
+    impl Drop for Measure<'_, '_> {
+        #[inline]
+        fn drop(&mut self) {
+            *self.overrun = record(self.cs, self.start);
+        }
+    }
+
+    let mut overrun = None;
+    let r = critical_section::with(|cs| {
+        let _measure = Measure {
+            cs,
+            start: start(cs),
+            overrun: &mut overrun,
+        };
+        f(cs)
+    });
+    if let Some(overrun) = overrun {
+        overrun.report();
+    }
+    r
+}
+
+/// Returns the longest critical section duration observed so far, in counter ticks.
+pub fn max_duration() -> u32 {
+    critical_section::with(|cs| STATE.borrow(cs).get().stats.max_duration)
+}
+
+/// Resets the longest observed duration to zero, returning the previous value.
+pub fn reset_max_duration() -> u32 {
+    update(|state| core::mem::replace(&mut state.stats.max_duration, 0))
+}
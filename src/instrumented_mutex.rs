@@ -0,0 +1,312 @@
+use core::cell::{BorrowError, BorrowMutError, Ref, RefCell, RefMut};
+use core::fmt;
+use core::ops::{Deref, DerefMut};
+use std::sync::{Mutex as StdMutex, MutexGuard, PoisonError};
+use std::thread::{self, ThreadId};
+use std::vec::Vec;
+
+use critical_section::CriticalSection;
+
+/// A `Mutex<RefCell<T>>` that records every access for test assertions.
+///
+/// It has the same borrowing rules as `Mutex<RefCell<T>>`, but each borrow, release and failed
+/// borrow is appended to a log together with the thread that made it, so that integration tests
+/// can check that a code path never double-borrows, or that the data is only ever accessed from
+/// one context. On the host, each thread stands in for a context such as an interrupt handler.
+/// This type is only available with the `std` feature.
+///
+/// ```
+/// use bare_metal::{AccessKind, InstrumentedMutex};
+/// use critical_section::CriticalSection;
+///
+/// static STATE: InstrumentedMutex<u32> = InstrumentedMutex::new(0);
+///
+/// let cs = unsafe { CriticalSection::new() };
+/// *STATE.borrow_mut(cs) += 1;
+/// {
+///     let _reader = STATE.borrow(cs);
+///     assert!(STATE.try_borrow_mut(cs).is_err());
+/// }
+///
+/// let kinds: Vec<_> = STATE.events().iter().map(|event| event.kind).collect();
+/// assert_eq!(
+///     kinds,
+///     [
+///         AccessKind::BorrowMut,
+///         AccessKind::Release,
+///         AccessKind::Borrow,
+///         AccessKind::BorrowMutFailed,
+///         AccessKind::Release,
+///     ]
+/// );
+/// assert_eq!(STATE.failures(), 1);
+/// assert_eq!(STATE.contexts(), [std::thread::current().id()]);
+/// ```
+pub struct InstrumentedMutex<T> {
+    value: RefCell<T>,
+    events: StdMutex<Vec<AccessEvent>>,
+}
+
+/// An access to an [`InstrumentedMutex`].
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct AccessEvent {
+    /// What happened.
+    pub kind: AccessKind,
+    /// The thread that made the access.
+    pub context: ThreadId,
+}
+
+/// The kind of an [`AccessEvent`].
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub enum AccessKind {
+    /// The data was borrowed.
+    Borrow,
+    /// The data was borrowed mutably.
+    BorrowMut,
+    /// A borrow was released.
+    Release,
+    /// Borrowing failed because the data was borrowed mutably.
+    BorrowFailed,
+    /// Borrowing mutably failed because the data was already borrowed.
+    BorrowMutFailed,
+}
+
+impl AccessKind {
+    /// Returns `true` for a failed borrow.
+    #[inline]
+    pub const fn is_failure(self) -> bool {
+        matches!(self, AccessKind::BorrowFailed | AccessKind::BorrowMutFailed)
+    }
+}
+
+impl<T> InstrumentedMutex<T> {
+    /// Creates a new `InstrumentedMutex` containing `value`, with an empty log.
+    #[inline]
+    pub const fn new(value: T) -> Self {
+        InstrumentedMutex {
+            value: RefCell::new(value),
+            events: StdMutex::new(Vec::new()),
+        }
+    }
+
+    fn log(&self) -> MutexGuard<'_, Vec<AccessEvent>> {
+        // a failed assertion in another test must not hide the log
+        self.events.lock().unwrap_or_else(PoisonError::into_inner)
+    }
+
+    fn record(&self, kind: AccessKind) {
+        self.log().push(AccessEvent {
+            kind,
+            context: thread::current().id(),
+        });
+    }
+
+    /// Borrows the data for the duration of the critical section.
+    ///
+    /// # Panics
+    ///
+    /// Panics if the data is borrowed mutably. The failure is recorded before panicking. For a
+    /// non-panicking variant, use [`try_borrow`](InstrumentedMutex::try_borrow).
+    #[inline]
+    #[track_caller]
+    pub fn borrow<'cs>(&'cs self, cs: CriticalSection<'cs>) -> InstrumentedRef<'cs, T> {
+        match self.try_borrow(cs) {
+            Ok(guard) => guard,
+            Err(_) => already_mutably_borrowed(),
+        }
+    }
+
+    /// Borrows the data for the duration of the critical section, returning an error if it is
+    /// borrowed mutably.
+    pub fn try_borrow<'cs>(
+        &'cs self,
+        _cs: CriticalSection<'cs>,
+    ) -> Result<InstrumentedRef<'cs, T>, BorrowError> {
+        match self.value.try_borrow() {
+            Ok(inner) => {
+                self.record(AccessKind::Borrow);
+                Ok(InstrumentedRef { inner, mutex: self })
+            }
+            Err(err) => {
+                self.record(AccessKind::BorrowFailed);
+                Err(err)
+            }
+        }
+    }
+
+    /// Mutably borrows the data for the duration of the critical section.
+    ///
+    /// # Panics
+    ///
+    /// Panics if the data is already borrowed. The failure is recorded before panicking. For a
+    /// non-panicking variant, use [`try_borrow_mut`](InstrumentedMutex::try_borrow_mut).
+    #[inline]
+    #[track_caller]
+    pub fn borrow_mut<'cs>(&'cs self, cs: CriticalSection<'cs>) -> InstrumentedRefMut<'cs, T> {
+        match self.try_borrow_mut(cs) {
+            Ok(guard) => guard,
+            Err(_) => already_borrowed(),
+        }
+    }
+
+    /// Mutably borrows the data for the duration of the critical section, returning an error if
+    /// it is already borrowed.
+    pub fn try_borrow_mut<'cs>(
+        &'cs self,
+        _cs: CriticalSection<'cs>,
+    ) -> Result<InstrumentedRefMut<'cs, T>, BorrowMutError> {
+        match self.value.try_borrow_mut() {
+            Ok(inner) => {
+                self.record(AccessKind::BorrowMut);
+                Ok(InstrumentedRefMut { inner, mutex: self })
+            }
+            Err(err) => {
+                self.record(AccessKind::BorrowMutFailed);
+                Err(err)
+            }
+        }
+    }
+
+    /// Returns a copy of the log.
+    pub fn events(&self) -> Vec<AccessEvent> {
+        self.log().clone()
+    }
+
+    /// Returns the log and starts a new, empty one.
+    pub fn take_events(&self) -> Vec<AccessEvent> {
+        core::mem::take(&mut *self.log())
+    }
+
+    /// Returns the number of failed borrows in the log.
+    pub fn failures(&self) -> usize {
+        self.log()
+            .iter()
+            .filter(|event| event.kind.is_failure())
+            .count()
+    }
+
+    /// Returns the threads that borrowed the data, or tried to, in the order of their first
+    /// access.
+    pub fn contexts(&self) -> Vec<ThreadId> {
+        let mut contexts = Vec::new();
+        for event in self.log().iter() {
+            if !contexts.contains(&event.context) {
+                contexts.push(event.context);
+            }
+        }
+        contexts
+    }
+
+    /// Returns a mutable reference to the data, without recording an access.
+    ///
+    /// This does not require a critical section since `&mut self` already guarantees exclusive
+    /// access.
+    #[inline]
+    pub fn get_mut(&mut self) -> &mut T {
+        self.value.get_mut()
+    }
+
+    /// Consumes the `InstrumentedMutex`, returning the data.
+    #[inline]
+    pub fn into_inner(self) -> T {
+        self.value.into_inner()
+    }
+}
+
+impl<T: Default> Default for InstrumentedMutex<T> {
+    fn default() -> Self {
+        InstrumentedMutex::new(T::default())
+    }
+}
+
+impl<T> fmt::Debug for InstrumentedMutex<T> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("InstrumentedMutex")
+            .field("events", &self.log().len())
+            .finish_non_exhaustive()
+    }
+}
+
+// NOTE the data is only accessed while a critical section is held, which serializes all
+// accesses, and the log is behind its own lock. As with `Mutex`, the data must be `Send`
+// because it can be handed over to a different thread.
+unsafe impl<T> Sync for InstrumentedMutex<T> where T: Send {}
+
+/// A borrow of the data in an [`InstrumentedMutex`].
+///
+/// The release is recorded when this guard is dropped.
+pub struct InstrumentedRef<'cs, T> {
+    inner: Ref<'cs, T>,
+    mutex: &'cs InstrumentedMutex<T>,
+}
+
+impl<T> Deref for InstrumentedRef<'_, T> {
+    type Target = T;
+
+    #[inline]
+    fn deref(&self) -> &T {
+        &self.inner
+    }
+}
+
+impl<T> Drop for InstrumentedRef<'_, T> {
+    fn drop(&mut self) {
+        self.mutex.record(AccessKind::Release);
+    }
+}
+
+impl<T: fmt::Debug> fmt::Debug for InstrumentedRef<'_, T> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        (**self).fmt(f)
+    }
+}
+
+/// A mutable borrow of the data in an [`InstrumentedMutex`].
+///
+/// The release is recorded when this guard is dropped.
+pub struct InstrumentedRefMut<'cs, T> {
+    inner: RefMut<'cs, T>,
+    mutex: &'cs InstrumentedMutex<T>,
+}
+
+impl<T> Deref for InstrumentedRefMut<'_, T> {
+    type Target = T;
+
+    #[inline]
+    fn deref(&self) -> &T {
+        &self.inner
+    }
+}
+
+impl<T> DerefMut for InstrumentedRefMut<'_, T> {
+    #[inline]
+    fn deref_mut(&mut self) -> &mut T {
+        &mut self.inner
+    }
+}
+
+impl<T> Drop for InstrumentedRefMut<'_, T> {
+    fn drop(&mut self) {
+        self.mutex.record(AccessKind::Release);
+    }
+}
+
+impl<T: fmt::Debug> fmt::Debug for InstrumentedRefMut<'_, T> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        (**self).fmt(f)
+    }
+}
+
+#[cold]
+#[inline(never)]
+#[track_caller]
+fn already_borrowed() -> ! {
+    panic!("already borrowed")
+}
+
+#[cold]
+#[inline(never)]
+#[track_caller]
+fn already_mutably_borrowed() -> ! {
+    panic!("already mutably borrowed")
+}
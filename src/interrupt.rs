@@ -0,0 +1,579 @@
+use core::cell::UnsafeCell;
+use core::fmt;
+use core::marker::PhantomData;
+
+use critical_section::CriticalSection;
+
+use crate::Exchange;
+
+/// An interrupt number.
+///
+/// This is implemented by the interrupt enumerations of device crates (PACs), so that drivers and
+/// interrupt controller abstractions can refer to interrupts in an architecture-neutral way.
+/// It replaces the `Nr` trait of earlier versions of this crate.
+///
+/// ```
+/// use bare_metal::InterruptNumber;
+///
+/// #[derive(Clone, Copy)]
+/// #[repr(u16)]
+/// enum Interrupt {
+///     Tim2 = 28,
+///     Usart1 = 37,
+/// }
+///
+/// // SAFETY: the discriminants are the interrupt numbers of the device
+/// unsafe impl InterruptNumber for Interrupt {
+///     fn number(self) -> u16 {
+///         self as u16
+///     }
+///
+///     fn name(self) -> Option<&'static str> {
+///         Some(match self {
+///             Interrupt::Tim2 => "TIM2",
+///             Interrupt::Usart1 => "USART1",
+///         })
+///     }
+/// }
+///
+/// assert_eq!(Interrupt::Usart1.number(), 37);
+/// assert_eq!(Interrupt::Tim2.name(), Some("TIM2"));
+/// ```
+///
+/// # Safety
+///
+/// `number` must only ever return numbers of interrupts that exist on the device. Interrupt
+/// controller abstractions may use the number unchecked, e.g. to index into registers.
+pub unsafe trait InterruptNumber: Copy {
+    /// Returns the number of the interrupt.
+    fn number(self) -> u16;
+
+    /// Returns the name of the interrupt, if known.
+    #[inline]
+    fn name(self) -> Option<&'static str> {
+        None
+    }
+}
+
+/// An interrupt controller, like the NVIC of Cortex-M or the PLIC of RISC-V.
+///
+/// Driver crates can be generic over this trait to manage their interrupt lines without
+/// depending on a specific architecture crate. Priorities are logical: a higher number means a
+/// more urgent interrupt, regardless of how the hardware encodes it.
+///
+/// ```
+/// use bare_metal::{InterruptController, InterruptNumber};
+///
+/// fn start_transfer<C: InterruptController, I: InterruptNumber>(irq: I) {
+///     C::unpend(irq);
+///     // SAFETY: the driver's interrupt handler doesn't break any critical section
+///     unsafe { C::enable(irq) };
+/// }
+/// ```
+pub trait InterruptController {
+    /// Enables the interrupt.
+    ///
+    /// # Safety
+    ///
+    /// Enabling an interrupt can break critical sections that rely on it being disabled.
+    unsafe fn enable<I: InterruptNumber>(interrupt: I);
+
+    /// Disables the interrupt.
+    fn disable<I: InterruptNumber>(interrupt: I);
+
+    /// Returns `true` if the interrupt is enabled.
+    fn is_enabled<I: InterruptNumber>(interrupt: I) -> bool;
+
+    /// Sets the interrupt pending, so that its handler runs once it is enabled and its priority
+    /// allows it.
+    fn pend<I: InterruptNumber>(interrupt: I);
+
+    /// Clears the pending state of the interrupt.
+    fn unpend<I: InterruptNumber>(interrupt: I);
+
+    /// Returns `true` if the interrupt is pending.
+    fn is_pending<I: InterruptNumber>(interrupt: I) -> bool;
+
+    /// Sets the logical priority of the interrupt.
+    ///
+    /// Controllers with fewer priority levels than `u8` only use the most significant bits of
+    /// `priority`, so several logical priorities map to the same level.
+    ///
+    /// # Safety
+    ///
+    /// Changing the priority of an interrupt can break priority-based critical sections, like
+    /// the ones of [`CeilingMutex`](crate::CeilingMutex).
+    unsafe fn set_priority<I: InterruptNumber>(interrupt: I, priority: u8);
+
+    /// Sets the priority of the interrupt to a [`Priority`] with `BITS` bits.
+    ///
+    /// The level of `priority` becomes the `BITS` most significant bits of the logical priority
+    /// passed to [`set_priority`](InterruptController::set_priority). For a controller with
+    /// `BITS` bits of priority, every `Priority<BITS>` is a distinct level.
+    ///
+    /// ```
+    /// use bare_metal::{InterruptController, InterruptNumber, Priority};
+    ///
+    /// // e.g. a Cortex-M device that implements 4 bits of priority
+    /// fn configure<C: InterruptController, I: InterruptNumber>(irq: I) {
+    ///     // SAFETY: the interrupt isn't used by any priority-based critical section
+    ///     unsafe { C::set_typed_priority(irq, Priority::<4>::new_const(12)) };
+    /// }
+    /// ```
+    ///
+    /// # Safety
+    ///
+    /// See [`set_priority`](InterruptController::set_priority).
+    #[inline]
+    unsafe fn set_typed_priority<I: InterruptNumber, const BITS: u8>(
+        interrupt: I,
+        priority: Priority<BITS>,
+    ) {
+        let shift = 8u8.saturating_sub(BITS);
+        Self::set_priority(interrupt, (u16::from(priority.level()) << shift) as u8);
+    }
+}
+
+/// Masks a single interrupt until the guard is dropped.
+///
+/// This protects data shared with one interrupt handler without masking every other interrupt,
+/// as a critical section would. The interrupt is disabled when the guard is created, and
+/// enabled again when it is dropped if it was enabled before.
+///
+/// ```
+/// use bare_metal::{InterruptController, InterruptNumber, MaskGuard};
+///
+/// fn reconfigure<C: InterruptController, I: InterruptNumber>(tim2: I) {
+///     // SAFETY: no other guard for this interrupt outlives this one
+///     let _masked = unsafe { MaskGuard::<C, I>::new(tim2) };
+///     // touch the state shared with the TIM2 handler
+/// }
+/// ```
+pub struct MaskGuard<C: InterruptController, I: InterruptNumber> {
+    interrupt: I,
+    was_enabled: bool,
+    _controller: PhantomData<fn() -> C>,
+}
+
+impl<C: InterruptController, I: InterruptNumber> MaskGuard<C, I> {
+    /// Disables `interrupt`, remembering whether it was enabled.
+    ///
+    /// # Safety
+    ///
+    /// Dropping the guard may enable the interrupt. Guards for the same interrupt must be
+    /// dropped in the reverse order in which they were created, and the interrupt must not be
+    /// disabled through other means that rely on it staying disabled after the guard is
+    /// dropped.
+    #[inline]
+    pub unsafe fn new(interrupt: I) -> Self {
+        let was_enabled = C::is_enabled(interrupt);
+        C::disable(interrupt);
+        MaskGuard {
+            interrupt,
+            was_enabled,
+            _controller: PhantomData,
+        }
+    }
+
+    /// Returns the masked interrupt.
+    #[inline]
+    pub fn interrupt(&self) -> I {
+        self.interrupt
+    }
+}
+
+impl<C: InterruptController, I: InterruptNumber> Drop for MaskGuard<C, I> {
+    #[inline]
+    fn drop(&mut self) {
+        if self.was_enabled {
+            // SAFETY: this restores the state from before the guard was created
+            unsafe { C::enable(self.interrupt) }
+        }
+    }
+}
+
+impl<C: InterruptController, I: InterruptNumber> fmt::Debug for MaskGuard<C, I> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("MaskGuard")
+            .field("interrupt", &self.interrupt.number())
+            .field("was_enabled", &self.was_enabled)
+            .finish()
+    }
+}
+
+/// A table of interrupt handlers that are registered at run time.
+///
+/// Architecture crates and RTOSes can use this to implement dynamic vectoring: the handler that
+/// is installed in the vector table calls [`dispatch`](HandlerTable::dispatch) with the number
+/// of the active interrupt, which calls the handler registered for it. Handlers may be plain
+/// functions or functions that take a `&'static` context.
+///
+/// ```
+/// use bare_metal::HandlerTable;
+/// use core::sync::atomic::{AtomicU32, Ordering};
+/// use critical_section::CriticalSection;
+///
+/// static HANDLERS: HandlerTable<32> = HandlerTable::new();
+/// static TICKS: AtomicU32 = AtomicU32::new(0);
+///
+/// fn on_tick(ticks: &'static AtomicU32) {
+///     ticks.fetch_add(1, Ordering::Relaxed);
+/// }
+///
+/// let cs = unsafe { CriticalSection::new() };
+/// HANDLERS.register_with_context(cs, 15, on_tick, &TICKS);
+///
+/// // In the interrupt handler installed in the vector table
+/// assert!(HANDLERS.dispatch(15));
+/// assert!(!HANDLERS.dispatch(16));
+/// assert_eq!(TICKS.load(Ordering::Relaxed), 1);
+/// ```
+pub struct HandlerTable<const N: usize> {
+    // only accessed inside a critical section
+    handlers: UnsafeCell<[Option<Handler>; N]>,
+}
+
+#[derive(Clone, Copy)]
+struct Handler {
+    trampoline: unsafe fn(*const (), *const ()),
+    function: *const (),
+    context: *const (),
+}
+
+unsafe fn call_plain(function: *const (), _context: *const ()) {
+    let function = core::mem::transmute::<*const (), fn()>(function);
+    function()
+}
+
+unsafe fn call_with_context<C: 'static>(function: *const (), context: *const ()) {
+    let function = core::mem::transmute::<*const (), fn(&'static C)>(function);
+    function(&*(context as *const C))
+}
+
+impl<const N: usize> HandlerTable<N> {
+    /// Creates a table without any registered handlers.
+    #[inline]
+    pub const fn new() -> Self {
+        HandlerTable {
+            handlers: UnsafeCell::new([None; N]),
+        }
+    }
+
+    #[inline]
+    #[track_caller]
+    fn slot<'cs>(&'cs self, _cs: CriticalSection<'cs>, index: usize) -> &'cs mut Option<Handler> {
+        // SAFETY: the critical section rules out concurrent access, and no reference into the
+        // table outlives a method call
+        unsafe { &mut (*self.handlers.get())[index] }
+    }
+
+    /// Registers `handler` for the interrupt `index`, returning `true` if it replaced another
+    /// handler.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `index` is `N` or greater.
+    #[inline]
+    #[track_caller]
+    pub fn register(&self, cs: CriticalSection<'_>, index: usize, handler: fn()) -> bool {
+        self.slot(cs, index)
+            .replace(Handler {
+                trampoline: call_plain,
+                function: handler as *const (),
+                context: core::ptr::null(),
+            })
+            .is_some()
+    }
+
+    /// Registers `handler` for the interrupt `index`, to be called with `context`. Returns
+    /// `true` if it replaced another handler.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `index` is `N` or greater.
+    #[inline]
+    #[track_caller]
+    pub fn register_with_context<C: Sync + 'static>(
+        &self,
+        cs: CriticalSection<'_>,
+        index: usize,
+        handler: fn(&'static C),
+        context: &'static C,
+    ) -> bool {
+        self.slot(cs, index)
+            .replace(Handler {
+                trampoline: call_with_context::<C>,
+                function: handler as *const (),
+                context: context as *const C as *const (),
+            })
+            .is_some()
+    }
+
+    /// Removes the handler for the interrupt `index`, returning `true` if there was one.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `index` is `N` or greater.
+    #[inline]
+    #[track_caller]
+    pub fn unregister(&self, cs: CriticalSection<'_>, index: usize) -> bool {
+        self.slot(cs, index).take().is_some()
+    }
+
+    /// Returns `true` if a handler is registered for the interrupt `index`.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `index` is `N` or greater.
+    #[inline]
+    #[track_caller]
+    pub fn is_registered(&self, cs: CriticalSection<'_>, index: usize) -> bool {
+        self.slot(cs, index).is_some()
+    }
+
+    /// Calls the handler registered for the interrupt `index`, returning `false` if there is
+    /// none or `index` is out of range.
+    ///
+    /// The handler is looked up inside a short critical section, and called outside of it.
+    #[inline]
+    pub fn dispatch(&self, index: usize) -> bool {
+        if index >= N {
+            return false;
+        }
+        match crate::with_cs(|cs| *self.slot(cs, index)) {
+            Some(handler) => {
+                // SAFETY: the trampoline was stored together with a function and context of
+                // the types it expects
+                unsafe { (handler.trampoline)(handler.function, handler.context) };
+                true
+            }
+            None => false,
+        }
+    }
+}
+
+impl<const N: usize> Default for HandlerTable<N> {
+    fn default() -> Self {
+        HandlerTable::new()
+    }
+}
+
+impl<const N: usize> fmt::Debug for HandlerTable<N> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("HandlerTable").finish_non_exhaustive()
+    }
+}
+
+// NOTE the table is only accessed inside critical sections, and the contexts it stores are
+// `Sync`
+unsafe impl<const N: usize> Sync for HandlerTable<N> {}
+
+/// An interrupt that can be triggered from software.
+///
+/// Pending a software interrupt is a common way to move work out of a high-priority interrupt
+/// handler into a lower-priority one. See [`Deferred`] for passing data along.
+pub trait SoftwareInterrupt {
+    /// Sets the interrupt pending.
+    fn pend();
+
+    /// Clears the pending state of the interrupt.
+    fn unpend();
+
+    /// Returns `true` if the interrupt is pending.
+    fn is_pending() -> bool;
+}
+
+/// A payload handed to the handler of the software interrupt `S`.
+///
+/// [`post`](Deferred::post) stores the payload in an [`Exchange`] and pends the software
+/// interrupt, whose handler [`take`](Deferred::take)s it. If the handler hasn't run by the time
+/// the next payload is posted, the older payload is overwritten.
+///
+/// ```
+/// use bare_metal::{Deferred, SoftwareInterrupt};
+/// use core::sync::atomic::{AtomicBool, Ordering};
+/// use critical_section::CriticalSection;
+///
+/// static PENDING: AtomicBool = AtomicBool::new(false);
+///
+/// // a stand-in for a real software interrupt, e.g. one pended through the NVIC
+/// struct Swi0;
+///
+/// impl SoftwareInterrupt for Swi0 {
+///     fn pend() {
+///         PENDING.store(true, Ordering::Relaxed);
+///     }
+///     fn unpend() {
+///         PENDING.store(false, Ordering::Relaxed);
+///     }
+///     fn is_pending() -> bool {
+///         PENDING.load(Ordering::Relaxed)
+///     }
+/// }
+///
+/// static RX: Deferred<[u8; 8], Swi0> = Deferred::new();
+///
+/// let cs = unsafe { CriticalSection::new() };
+/// // In the high-priority UART handler
+/// RX.post(cs, *b"AT+RST\r\n");
+/// assert!(Swi0::is_pending());
+///
+/// // In the handler of the software interrupt
+/// assert_eq!(RX.take(cs), Some(*b"AT+RST\r\n"));
+/// ```
+pub struct Deferred<T, S> {
+    mailbox: Exchange<T>,
+    _interrupt: PhantomData<fn() -> S>,
+}
+
+impl<T, S> Deferred<T, S> {
+    /// Creates a `Deferred` without a payload.
+    #[inline]
+    pub const fn new() -> Self {
+        Deferred {
+            mailbox: Exchange::new(),
+            _interrupt: PhantomData,
+        }
+    }
+
+    /// Takes the payload, if any.
+    ///
+    /// This is meant to be called from the handler of the software interrupt.
+    #[inline]
+    pub fn take(&self, cs: CriticalSection<'_>) -> Option<T> {
+        self.mailbox.take(cs)
+    }
+}
+
+impl<T, S: SoftwareInterrupt> Deferred<T, S> {
+    /// Stores `val` and pends the software interrupt, returning `true` if an unread payload was
+    /// overwritten.
+    #[inline]
+    pub fn post(&self, cs: CriticalSection<'_>, val: T) -> bool {
+        let overwritten = self.mailbox.publish(cs, val);
+        S::pend();
+        overwritten
+    }
+}
+
+impl<T, S> Default for Deferred<T, S> {
+    fn default() -> Self {
+        Deferred::new()
+    }
+}
+
+impl<T, S> fmt::Debug for Deferred<T, S> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("Deferred").finish_non_exhaustive()
+    }
+}
+
+/// A logical interrupt priority for a controller with `BITS` bits of priority.
+///
+/// Priorities are ordered by urgency: a greater `Priority` preempts a lesser one, whatever the
+/// encoding used by the hardware. Levels range from 0, the least urgent, to
+/// [`MAX`](Priority::MAX), `2^BITS - 1`. Architecture crates convert them to and from the
+/// hardware encoding by implementing [`PriorityEncoding`].
+///
+/// ```
+/// use bare_metal::Priority;
+///
+/// // e.g. a Cortex-M device that implements 4 bits of priority
+/// type Prio = Priority<4>;
+///
+/// const HIGH: Prio = Prio::new_const(12);
+/// assert!(HIGH > Prio::new(3).unwrap());
+/// assert_eq!(Prio::new(16), None);
+/// assert_eq!(Prio::MAX.level(), 15);
+/// ```
+///
+/// Out-of-range constants are rejected at compile time:
+///
+/// ```compile_fail
+/// use bare_metal::Priority;
+///
+/// const TOO_HIGH: Priority<4> = Priority::new_const(16);
+/// ```
+#[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct Priority<const BITS: u8> {
+    level: u8,
+}
+
+impl<const BITS: u8> Priority<BITS> {
+    /// The least urgent priority.
+    pub const MIN: Self = Priority { level: 0 };
+
+    /// The most urgent priority.
+    pub const MAX: Self = Priority {
+        level: Self::MAX_LEVEL,
+    };
+
+    const MAX_LEVEL: u8 = {
+        assert!(BITS <= 8, "priorities have at most 8 bits");
+        ((1u16 << BITS) - 1) as u8
+    };
+
+    /// Creates a priority, or returns `None` if `level` is greater than
+    /// [`MAX`](Priority::MAX).
+    #[inline]
+    pub const fn new(level: u8) -> Option<Self> {
+        if level <= Self::MAX_LEVEL {
+            Some(Priority { level })
+        } else {
+            None
+        }
+    }
+
+    /// Creates a priority.
+    ///
+    /// This is meant for constants, where an out-of-range `level` is a compile error.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `level` is greater than [`MAX`](Priority::MAX).
+    #[inline]
+    #[track_caller]
+    pub const fn new_const(level: u8) -> Self {
+        match Self::new(level) {
+            Some(priority) => priority,
+            None => panic!("priority level out of range"),
+        }
+    }
+
+    /// Returns the logical level of the priority.
+    #[inline]
+    pub const fn level(self) -> u8 {
+        self.level
+    }
+}
+
+/// A conversion between logical [`Priority`]s and the encoding used by an interrupt
+/// controller.
+///
+/// ```
+/// use bare_metal::{Priority, PriorityEncoding};
+///
+/// // the NVIC: lower values are more urgent, and the implemented bits are the most
+/// // significant ones
+/// struct Nvic;
+///
+/// impl PriorityEncoding<3> for Nvic {
+///     fn encode(priority: Priority<3>) -> u8 {
+///         (7 - priority.level()) << 5
+///     }
+///
+///     fn decode(raw: u8) -> Priority<3> {
+///         Priority::new_const(7 - (raw >> 5))
+///     }
+/// }
+///
+/// assert_eq!(Nvic::encode(Priority::MAX), 0x00);
+/// assert_eq!(Nvic::decode(0xe0), Priority::MIN);
+/// ```
+pub trait PriorityEncoding<const BITS: u8> {
+    /// Converts a logical priority to the hardware encoding.
+    fn encode(priority: Priority<BITS>) -> u8;
+
+    /// Converts a priority in the hardware encoding to a logical one.
+    fn decode(raw: u8) -> Priority<BITS>;
+}
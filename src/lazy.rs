@@ -0,0 +1,121 @@
+use core::cell::UnsafeCell;
+use core::fmt;
+use core::ops::Deref;
+
+use critical_section::CriticalSection;
+
+/// A value that is lazily initialized inside a critical section on first access.
+///
+/// This is the critical-section equivalent of `std::sync::LazyLock`: the initializer runs
+/// exactly once, the first time the value is accessed, and every later access returns the same
+/// value. It removes the need for `Mutex<RefCell<Option<T>>>` and `unwrap` for data that
+/// doesn't change after it has been created.
+///
+/// Accessing the value through [`Deref`] enters a critical section to check whether it is
+/// initialized; [`CsLazy::get`] does the same using an existing critical section.
+///
+/// ```
+/// use bare_metal::CsLazy;
+///
+/// static SQUARES: CsLazy<[u32; 16]> = CsLazy::new(|| {
+///     let mut table = [0; 16];
+///     for (i, entry) in table.iter_mut().enumerate() {
+///         *entry = (i * i) as u32;
+///     }
+///     table
+/// });
+///
+/// assert_eq!(SQUARES[4], 16);
+/// ```
+pub struct CsLazy<T, F = fn() -> T> {
+    state: UnsafeCell<State<T, F>>,
+}
+
+enum State<T, F> {
+    Uninit(F),
+    Init(T),
+    Running,
+}
+
+impl<T, F> CsLazy<T, F> {
+    /// Creates a new lazy value with the given initializing function.
+    #[inline]
+    pub const fn new(f: F) -> Self {
+        CsLazy {
+            state: UnsafeCell::new(State::Uninit(f)),
+        }
+    }
+}
+
+impl<T, F: FnOnce() -> T> CsLazy<T, F> {
+    /// Returns a reference to the value, initializing it first if necessary.
+    ///
+    /// # Panics
+    ///
+    /// Panics if the initializer tries to access the value it is initializing, or if it panicked
+    /// during an earlier access.
+    #[track_caller]
+    pub fn get(&self, _cs: CriticalSection<'_>) -> &T {
+        // SAFETY: the state is only modified while a critical section is held, which we are in.
+        // Once it is `Init` it never changes again, so references to the value stay valid.
+        match unsafe { &*self.state.get() } {
+            State::Init(value) => return value,
+            State::Running => poisoned(),
+            State::Uninit(_) => {}
+        }
+
+        // SAFETY: the value is not initialized yet, so no references into the state exist
+        let f = match core::mem::replace(unsafe { &mut *self.state.get() }, State::Running) {
+            State::Uninit(f) => f,
+            _ => unreachable!(),
+        };
+        let value = f();
+        // SAFETY: as above; a reentrant access from within `f` would have panicked
+        let state = unsafe { &mut *self.state.get() };
+        *state = State::Init(value);
+        match state {
+            State::Init(value) => value,
+            _ => unreachable!(),
+        }
+    }
+
+    /// Returns a reference to the value, initializing it first if necessary.
+    ///
+    /// This is equivalent to `Deref::deref`, and enters a critical section.
+    #[inline]
+    #[track_caller]
+    pub fn force(this: &Self) -> &T {
+        crate::with_cs(|cs| this.get(cs))
+    }
+}
+
+impl<T, F: FnOnce() -> T> Deref for CsLazy<T, F> {
+    type Target = T;
+
+    #[inline]
+    #[track_caller]
+    fn deref(&self) -> &T {
+        CsLazy::force(self)
+    }
+}
+
+impl<T, F> fmt::Debug for CsLazy<T, F> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("CsLazy").finish_non_exhaustive()
+    }
+}
+
+#[cold]
+#[inline(never)]
+#[track_caller]
+fn poisoned() -> ! {
+    panic!("CsLazy instance accessed during or after a failed initialization")
+}
+
+// NOTE the initializer may run in any context, and the value is then shared with all of them
+unsafe impl<T, F> Sync for CsLazy<T, F>
+where
+    T: Send + Sync,
+    F: Send,
+{
+}
@@ -1,11 +1,207 @@
 //! Abstractions common to bare metal systems.
 //!
-//! This crate is superseded by the [`critical-section`](critical_section) crate.
+//! This crate is superseded by the [`critical-section`](critical_section) crate. What remains
+//! here are conveniences built on top of it.
+//!
+//! # Model checking
+//!
+//! When built with `RUSTFLAGS="--cfg loom"`, the lock-free primitives use the atomics of
+//! [`loom`](https://docs.rs/loom), so that their memory orderings can be checked by loom models;
+//! the crate's own models are in `tests/loom.rs`. The spinlock and the queues also use loom's
+//! cells, which check the accesses to the data they protect. The data of the other primitives,
+//! like the byte ranges of a `BipBuffer` or the value in a `SeqLock`, is not tracked. Crates that
+//! build on these primitives can model check their own code under the same cfg. In that
+//! configuration, the `const fn` constructors of the affected types are plain functions, and the
+//! `executor` module is not available.
 
 #![deny(missing_docs)]
 #![no_std]
 #![doc(html_root_url = "https://docs.rs/bare-metal/1.0")]
 
+#[cfg(feature = "std")]
+extern crate std;
+
+// Declares a `const fn` that is a plain `fn` when model checking with `loom`, whose atomics
+// can't be created in constant contexts.
+macro_rules! const_fn {
+    ($(#[$attr:meta])* $vis:vis const fn $name:ident $($rest:tt)*) => {
+        #[cfg(not(loom))]
+        $(#[$attr])*
+        $vis const fn $name $($rest)*
+
+        #[cfg(loom)]
+        $(#[$attr])*
+        $vis fn $name $($rest)*
+    };
+}
+
+mod access;
+pub mod align;
+mod arena;
+mod async_mutex;
+mod async_semaphore;
+mod async_signal;
+mod atomic;
+mod barrier;
+pub mod bip;
+mod bitset;
+mod branded;
+mod bus;
+mod cache_padded;
+mod ceiling;
+mod cell;
+pub mod cs;
+mod cs_atomic;
+#[cfg(feature = "deadlock-detection")]
+pub mod deadlock;
+pub mod dma;
+pub mod double_buffer;
+mod event_flags;
+mod exchange;
+#[cfg(not(loom))]
+pub mod executor;
+pub mod fence;
+mod flag;
+#[cfg(feature = "defmt")]
+mod format;
+#[cfg(feature = "embedded-hal")]
+mod hal;
+#[cfg(feature = "alloc")]
+mod heap;
+mod init_cell;
+#[cfg(feature = "instrument")]
+pub mod instrument;
+#[cfg(feature = "std")]
+mod instrumented_mutex;
+mod interrupt;
+mod lazy;
+pub mod lock;
+#[cfg(feature = "std")]
+mod mock;
+pub mod mpmc;
+pub mod mpsc;
+mod mutex;
+pub mod oneshot;
+mod pin_mutex;
+mod pool;
+mod reentrant;
+pub mod register;
+mod rwlock;
+mod semaphore;
+mod seqlock;
+#[cfg(feature = "serde")]
+pub mod serde;
+mod signal;
+#[cfg(feature = "single-context")]
+pub mod single_context;
+mod singleton;
+#[cfg(target_has_atomic = "8")]
+mod spin;
+pub mod spsc;
+mod volatile;
+mod waiters;
+mod waker;
+mod zst_mutex;
+
+pub use crate::access::ExclusiveAccess;
+pub use crate::arena::BumpArena;
+pub use crate::async_mutex::{AsyncMutex, AsyncMutexGuard, AsyncMutexLock};
+pub use crate::async_semaphore::{AsyncSemaphore, AsyncSemaphoreAcquire};
+pub use crate::async_signal::{AsyncSignal, AsyncSignalWait};
+pub use crate::barrier::{Barrier, Spin, WaitHook};
+pub use crate::bitset::{AtomicBitSet, BitSetDrain};
+pub use crate::branded::{BrandToken, BrandedMutex};
+pub use crate::bus::{BusHandle, BusManager};
+pub use crate::cache_padded::CachePadded;
+pub use crate::ceiling::{
+    with_threshold, CeilingMutex, PriorityThreshold, ThresholdMutex, ThresholdToken,
+};
+pub use crate::cell::{CsCell, CsRefCell, CsRefMut};
+pub use crate::cs_atomic::{CsAtomicBool, CsAtomicU16, CsAtomicU32, CsAtomicU8, CsAtomicUsize};
+pub use crate::event_flags::EventFlags;
+pub use crate::exchange::Exchange;
+pub use crate::flag::AtomicFlag;
+#[cfg(feature = "embedded-hal")]
+pub use crate::hal::{SpiHandle, SpiHandleError};
+#[cfg(feature = "alloc")]
+pub use crate::heap::PoolAllocator;
+pub use crate::init_cell::InitCell;
+#[cfg(feature = "std")]
+pub use crate::instrumented_mutex::{
+    AccessEvent, AccessKind, InstrumentedMutex, InstrumentedRef, InstrumentedRefMut,
+};
+pub use crate::interrupt::{
+    Deferred, HandlerTable, InterruptController, InterruptNumber, MaskGuard, Priority,
+    PriorityEncoding, SoftwareInterrupt,
+};
+pub use crate::lazy::CsLazy;
+#[cfg(feature = "std")]
+pub use crate::mock::{MockCall, MockInterruptController};
+pub use crate::mutex::{
+    MutexCellExt, MutexCellOptionExt, MutexGroup, MutexGuard, MutexOnceCellExt, MutexOptionExt,
+    MutexRefCellExt,
+};
+pub use crate::pin_mutex::PinMutex;
+pub use crate::pool::{Pool, PoolBox};
+pub use crate::reentrant::{CoreId, ReentrantMutex, ReentrantMutexGuard};
+pub use crate::rwlock::{RwLock, RwLockReadGuard, RwLockWriteGuard};
+pub use crate::semaphore::Semaphore;
+pub use crate::seqlock::SeqLock;
+pub use crate::signal::Signal;
+pub use crate::singleton::{InitStatic, Singleton, Taken};
+#[cfg(target_has_atomic = "8")]
+pub use crate::spin::{SpinLock, SpinLockGuard};
+pub use crate::volatile::VolatileCell;
+pub use crate::waker::WakerSlot;
+pub use crate::zst_mutex::ZstMutex;
+
+// used by the exported macros
+#[doc(hidden)]
+pub mod __private {
+    pub use critical_section;
+
+    pub const fn ceiling(priorities: &[u8]) -> u8 {
+        let mut ceiling = 0;
+        let mut i = 0;
+        while i < priorities.len() {
+            if priorities[i] > ceiling {
+                ceiling = priorities[i];
+            }
+            i += 1;
+        }
+        ceiling
+    }
+
+    pub const fn contains(priorities: &[u8], priority: u8) -> bool {
+        let mut i = 0;
+        while i < priorities.len() {
+            if priorities[i] == priority {
+                return true;
+            }
+            i += 1;
+        }
+        false
+    }
+}
+
+/// Executes `f` in a critical section, recording its duration if the `instrument` feature is
+/// enabled and tracking it if the `deadlock-detection` feature is enabled.
+#[inline]
+#[track_caller]
+fn with_cs<R>(f: impl FnOnce(critical_section::CriticalSection<'_>) -> R) -> R {
+    #[cfg(feature = "deadlock-detection")]
+    deadlock::wait_cs();
+    #[cfg(feature = "deadlock-detection")]
+    let f = |cs: critical_section::CriticalSection<'_>| {
+        let _held = deadlock::HeldCs::new();
+        f(cs)
+    };
+    #[cfg(feature = "instrument")]
+    return instrument::with(f);
+    #[cfg(not(feature = "instrument"))]
+    critical_section::with(f)
+}
+
 /// Critical section token.
 ///
 /// An instance of this type indicates that the current thread is executing code within a critical
@@ -56,12 +252,32 @@ pub type CriticalSection<'cs> = critical_section::CriticalSection<'cs>;
 ///     let _ = FOO.borrow(cs).take();
 ///     // Call this
 ///     let _ = FOO.take(cs);
+///     // Read-modify-write without holding on to a borrow guard
+///     let old = FOO.replace_with(cs, |x| *x + 1);
+///     assert_eq!(FOO.take(cs), old + 1);
 ///     // `RefCell::borrow` and `RefCell::borrow_mut` are renamed to
 ///     // `borrow_ref` and `borrow_ref_mut` to avoid name collisions
 ///     let _: &mut i32 = &mut *FOO.borrow_ref_mut(cs);
 /// }
 /// ```
 ///
+/// The forwarding methods available on `Mutex<RefCell<T>>` are `borrow_ref`, `borrow_ref_mut`,
+/// `replace`, `replace_with` and `take`. More are provided by [`MutexRefCellExt`].
+///
+/// # Exclusive access
+///
+/// A `&mut Mutex<T>` already guarantees that nothing else can access the data, so
+/// `Mutex::get_mut` hands out `&mut T` without requiring a critical section. This is useful
+/// to set up the data before the mutex is shared. Likewise, `Mutex::into_inner` consumes the
+/// mutex and gives back the data.
+///
+/// ```
+/// # use bare_metal::Mutex;
+/// let mut counter = Mutex::new(0);
+/// *counter.get_mut() += 1;
+/// assert_eq!(counter.into_inner(), 1);
+/// ```
+///
 /// [`std::sync::Mutex`]: https://doc.rust-lang.org/std/sync/struct.Mutex.html
 /// [interior mutability]: https://doc.rust-lang.org/reference/interior-mutability.html
 #[deprecated(since = "1.1.0", note = "use `critical_section::Mutex` instead")]
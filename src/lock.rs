@@ -0,0 +1,276 @@
+//! A mutex whose locking strategy is chosen by the application.
+//!
+//! Driver and HAL crates can store shared state in a [`Mutex<T, P>`](Mutex) that is generic over
+//! the [`LockPolicy`] `P`, and let the application decide how access is serialized: with a
+//! critical section ([`CriticalSectionPolicy`], the default), with a spinlock
+//! ([`SpinPolicy`]), or not at all ([`SingleContextPolicy`]).
+//!
+//! ```
+//! use bare_metal::lock::{CriticalSectionPolicy, LockPolicy, Mutex};
+//!
+//! struct Driver<P: LockPolicy = CriticalSectionPolicy> {
+//!     state: Mutex<u32, P>,
+//! }
+//!
+//! impl<P: LockPolicy> Driver<P> {
+//!     fn poll(&self) -> u32 {
+//!         self.state.lock(|state| {
+//!             *state += 1;
+//!             *state
+//!         })
+//!     }
+//! }
+//!
+//! let driver = Driver { state: Mutex::new(0) };
+//! assert_eq!(driver.poll(), 1);
+//! ```
+
+use core::cell::{Cell, UnsafeCell};
+use core::fmt;
+
+/// A strategy for serializing access to a [`Mutex`].
+///
+/// Besides backing [`Mutex`], the trait can be used on its own, so that driver and middleware
+/// crates are generic over the locking strategy instead of calling [`critical_section::with`]
+/// directly. It is implemented by the policies in this module, by a
+/// [`SpinLock<()>`](crate::SpinLock), and by references to any policy, so that several drivers
+/// can share one lock.
+///
+/// ```
+/// use bare_metal::lock::LockPolicy;
+/// use bare_metal::SpinLock;
+/// use core::sync::atomic::{AtomicU32, Ordering};
+///
+/// struct Uart<L> {
+///     lock: L,
+///     sent: AtomicU32,
+/// }
+///
+/// impl<L: LockPolicy> Uart<L> {
+///     fn send(&self, _bytes: &[u8]) {
+///         self.lock.lock(|| {
+///             // program the peripheral without being interrupted halfway
+///             self.sent.fetch_add(1, Ordering::Relaxed);
+///         })
+///     }
+/// }
+///
+/// static LOCK: SpinLock<()> = SpinLock::new(());
+///
+/// let uart = Uart { lock: &LOCK, sent: AtomicU32::new(0) };
+/// uart.send(b"hello");
+/// assert_eq!(uart.sent.load(Ordering::Relaxed), 1);
+/// ```
+///
+/// # Safety
+///
+/// While `lock` is running `f`, no other call to `lock` on the same policy value may be running
+/// `f`, whether in another interrupt priority level, another thread or another core.
+#[doc(alias = "RawLock")]
+#[doc(alias = "RawMutex")]
+pub unsafe trait LockPolicy {
+    /// Calls `f` with exclusive access to the resources protected by this policy.
+    fn lock<R>(&self, f: impl FnOnce() -> R) -> R;
+}
+
+unsafe impl<P: LockPolicy + ?Sized> LockPolicy for &P {
+    #[inline]
+    fn lock<R>(&self, f: impl FnOnce() -> R) -> R {
+        (**self).lock(f)
+    }
+}
+
+/// Serializes access with [`critical_section::with`].
+///
+/// This is the right choice for data shared between `main` and interrupt handlers.
+#[derive(Clone, Copy, Debug, Default)]
+pub struct CriticalSectionPolicy;
+
+impl CriticalSectionPolicy {
+    /// Creates the policy.
+    #[inline]
+    pub const fn new() -> Self {
+        CriticalSectionPolicy
+    }
+}
+
+unsafe impl LockPolicy for CriticalSectionPolicy {
+    #[inline]
+    fn lock<R>(&self, f: impl FnOnce() -> R) -> R {
+        crate::with_cs(|_| f())
+    }
+}
+
+/// Serializes access with a [`SpinLock`](crate::SpinLock).
+///
+/// This doesn't mask interrupts, so it is only suitable for data shared between contexts that
+/// can't preempt each other, like the threads running on different cores. An interrupt handler
+/// that spins on a lock held by the code it preempted never returns.
+#[cfg(target_has_atomic = "8")]
+#[derive(Debug, Default)]
+pub struct SpinPolicy {
+    lock: crate::SpinLock<()>,
+}
+
+#[cfg(target_has_atomic = "8")]
+impl SpinPolicy {
+    const_fn! {
+        /// Creates the policy.
+        #[inline]
+        pub const fn new() -> Self {
+            SpinPolicy {
+                lock: crate::SpinLock::new(()),
+            }
+        }
+    }
+}
+
+#[cfg(target_has_atomic = "8")]
+unsafe impl LockPolicy for SpinPolicy {
+    #[inline]
+    fn lock<R>(&self, f: impl FnOnce() -> R) -> R {
+        let _guard = self.lock.lock();
+        f()
+    }
+}
+
+// the same as `SpinPolicy`, for a lock that is shared with other code
+#[cfg(target_has_atomic = "8")]
+unsafe impl LockPolicy for crate::SpinLock<()> {
+    #[inline]
+    fn lock<R>(&self, f: impl FnOnce() -> R) -> R {
+        let _guard = crate::SpinLock::lock(self);
+        f()
+    }
+}
+
+/// Does not serialize access at all.
+///
+/// This has no runtime cost, and is only sound if the program has a single execution context:
+/// no interrupt handlers and no other cores touching the data.
+#[derive(Clone, Copy, Debug)]
+pub struct SingleContextPolicy {
+    _private: (),
+}
+
+impl SingleContextPolicy {
+    /// Creates the policy.
+    ///
+    /// # Safety
+    ///
+    /// Every [`Mutex`] that uses the returned policy must only ever be accessed from a single
+    /// execution context.
+    #[inline]
+    pub const unsafe fn new() -> Self {
+        SingleContextPolicy { _private: () }
+    }
+}
+
+unsafe impl LockPolicy for SingleContextPolicy {
+    #[inline]
+    fn lock<R>(&self, f: impl FnOnce() -> R) -> R {
+        f()
+    }
+}
+
+/// A mutex that serializes access using the [`LockPolicy`] `P`.
+///
+/// Unlike [`critical_section::Mutex`], this provides interior mutability on its own: the data is
+/// only reachable through [`lock`](Mutex::lock), which hands out `&mut T` for the duration of a
+/// closure.
+pub struct Mutex<T, P = CriticalSectionPolicy> {
+    policy: P,
+    // only accessed while the policy is locked
+    locked: Cell<bool>,
+    value: UnsafeCell<T>,
+}
+
+impl<T> Mutex<T> {
+    /// Creates a new mutex that uses a critical section.
+    #[inline]
+    pub const fn new(value: T) -> Self {
+        Mutex::with_policy(value, CriticalSectionPolicy)
+    }
+}
+
+impl<T, P> Mutex<T, P> {
+    /// Creates a new mutex that uses `policy`.
+    #[inline]
+    pub const fn with_policy(value: T, policy: P) -> Self {
+        Mutex {
+            policy,
+            locked: Cell::new(false),
+            value: UnsafeCell::new(value),
+        }
+    }
+
+    /// Returns a mutable reference to the data.
+    ///
+    /// This does not need to lock the mutex since `&mut self` already guarantees exclusive
+    /// access.
+    #[inline]
+    pub fn get_mut(&mut self) -> &mut T {
+        self.value.get_mut()
+    }
+
+    /// Consumes the mutex, returning the data.
+    #[inline]
+    pub fn into_inner(self) -> T {
+        self.value.into_inner()
+    }
+}
+
+impl<T, P: LockPolicy> Mutex<T, P> {
+    /// Locks the mutex and calls `f` with a mutable reference to the data.
+    ///
+    /// # Panics
+    ///
+    /// Panics if called from within `f` of another `lock` call on the same mutex.
+    #[inline]
+    #[track_caller]
+    pub fn lock<R>(&self, f: impl FnOnce(&mut T) -> R) -> R {
+        self.policy.lock(|| {
+            if self.locked.replace(true) {
+                already_locked();
+            }
+            let _unlock = Unlock(&self.locked);
+            // SAFETY: the policy serializes access, and the flag rules out reentrant calls
+            f(unsafe { &mut *self.value.get() })
+        })
+    }
+}
+
+struct Unlock<'a>(&'a Cell<bool>);
+
+impl Drop for Unlock<'_> {
+    #[inline]
+    fn drop(&mut self) {
+        self.0.set(false);
+    }
+}
+
+#[cold]
+#[track_caller]
+fn already_locked() -> ! {
+    panic!("Mutex already locked")
+}
+
+impl<T: Default> Default for Mutex<T> {
+    fn default() -> Self {
+        Mutex::new(T::default())
+    }
+}
+
+impl<T, P> fmt::Debug for Mutex<T, P> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("Mutex").finish_non_exhaustive()
+    }
+}
+
+// NOTE the data and the flag are only accessed while the policy is locked
+unsafe impl<T, P> Sync for Mutex<T, P>
+where
+    T: Send,
+    P: LockPolicy + Sync,
+{
+}
@@ -0,0 +1,216 @@
+use std::boxed::Box;
+use std::cell::RefCell;
+use std::collections::BTreeMap;
+use std::vec::Vec;
+
+use crate::{InterruptController, InterruptNumber};
+
+/// An [`InterruptController`] for host-side tests, which records every call and runs interrupt
+/// handlers on request.
+///
+/// Drivers that are generic over the controller can be instantiated with
+/// `MockInterruptController` in unit tests. Each test thread has its own state, so tests that run
+/// in parallel don't see each other's interrupts. Handlers registered with
+/// [`set_handler`](MockInterruptController::set_handler) only run when the test calls
+/// [`fire`](MockInterruptController::fire) or
+/// [`run_pending`](MockInterruptController::run_pending), so that the interleaving stays
+/// deterministic. This type is only available with the `std` feature.
+///
+/// ```
+/// use bare_metal::{InterruptController, InterruptNumber, MockCall, MockInterruptController};
+/// use std::cell::Cell;
+/// use std::rc::Rc;
+///
+/// #[derive(Clone, Copy)]
+/// struct Tim2;
+///
+/// unsafe impl InterruptNumber for Tim2 {
+///     fn number(self) -> u16 {
+///         28
+///     }
+/// }
+///
+/// // driver code under test
+/// fn start<C: InterruptController>() {
+///     C::unpend(Tim2);
+///     unsafe { C::enable(Tim2) };
+/// }
+///
+/// type Nvic = MockInterruptController;
+///
+/// let ticks = Rc::new(Cell::new(0));
+/// let counter = ticks.clone();
+/// Nvic::set_handler(Tim2, move || counter.set(counter.get() + 1));
+///
+/// start::<Nvic>();
+/// assert_eq!(Nvic::take_calls(), [MockCall::Unpend(28), MockCall::Enable(28)]);
+///
+/// Nvic::pend(Tim2);
+/// assert_eq!(Nvic::run_pending(), 1);
+/// assert_eq!(ticks.get(), 1);
+/// assert!(!Nvic::is_pending(Tim2));
+/// ```
+#[derive(Clone, Copy, Debug, Default)]
+pub struct MockInterruptController;
+
+/// A call made to the [`MockInterruptController`], with the number of the interrupt.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum MockCall {
+    /// [`InterruptController::enable`]
+    Enable(u16),
+    /// [`InterruptController::disable`]
+    Disable(u16),
+    /// [`InterruptController::pend`]
+    Pend(u16),
+    /// [`InterruptController::unpend`]
+    Unpend(u16),
+    /// [`InterruptController::set_priority`], with the priority, also made by
+    /// [`InterruptController::set_typed_priority`].
+    SetPriority(u16, u8),
+}
+
+#[derive(Default)]
+struct Line {
+    enabled: bool,
+    pending: bool,
+    priority: u8,
+    // `None` while the handler runs
+    handler: Option<Box<dyn FnMut()>>,
+}
+
+#[derive(Default)]
+struct State {
+    lines: BTreeMap<u16, Line>,
+    calls: Vec<MockCall>,
+}
+
+std::thread_local! {
+    static STATE: RefCell<State> = RefCell::new(State::default());
+}
+
+impl MockInterruptController {
+    fn with<R>(f: impl FnOnce(&mut State) -> R) -> R {
+        STATE.with(|state| f(&mut state.borrow_mut()))
+    }
+
+    fn line<R, I: InterruptNumber>(interrupt: I, f: impl FnOnce(&mut Line) -> R) -> R {
+        Self::with(|state| f(state.lines.entry(interrupt.number()).or_default()))
+    }
+
+    fn record(call: MockCall) {
+        Self::with(|state| state.calls.push(call));
+    }
+
+    /// Registers `handler` as the handler of `interrupt`, replacing any previous one.
+    pub fn set_handler<I: InterruptNumber>(interrupt: I, handler: impl FnMut() + 'static) {
+        Self::line(interrupt, |line| line.handler = Some(Box::new(handler)));
+    }
+
+    /// Returns the logical priority of `interrupt`, which is 0 unless it was set.
+    pub fn priority<I: InterruptNumber>(interrupt: I) -> u8 {
+        Self::line(interrupt, |line| line.priority)
+    }
+
+    /// Returns the calls made on this thread since the last call to
+    /// [`take_calls`](MockInterruptController::take_calls) or
+    /// [`reset`](MockInterruptController::reset), and forgets them.
+    pub fn take_calls() -> Vec<MockCall> {
+        Self::with(|state| core::mem::take(&mut state.calls))
+    }
+
+    /// Runs the handler of `interrupt` right away, regardless of whether it is enabled or
+    /// pending, and clears its pending state. Returns `false` if it has no handler.
+    ///
+    /// The handler may call the controller, but if it fires its own interrupt again, that call
+    /// returns `false`.
+    pub fn fire<I: InterruptNumber>(interrupt: I) -> bool {
+        Self::fire_number(interrupt.number())
+    }
+
+    fn fire_number(number: u16) -> bool {
+        let handler = Self::with(|state| {
+            let line = state.lines.entry(number).or_default();
+            line.pending = false;
+            line.handler.take()
+        });
+        let Some(mut handler) = handler else {
+            return false;
+        };
+        handler();
+        Self::with(|state| {
+            let line = state.lines.entry(number).or_default();
+            // keep a handler that was registered by the handler itself
+            line.handler.get_or_insert(handler);
+        });
+        true
+    }
+
+    /// Runs the handlers of all interrupts that are enabled and pending, most urgent first,
+    /// until none is left, and returns how many handlers ran.
+    ///
+    /// Interrupts that are pended by the handlers run as well. Pending interrupts without a
+    /// handler are cleared without running anything.
+    pub fn run_pending() -> usize {
+        let mut ran = 0;
+        loop {
+            let next = Self::with(|state| {
+                state
+                    .lines
+                    .iter()
+                    .filter(|(_, line)| line.enabled && line.pending)
+                    // the lowest number wins among interrupts of the same priority
+                    .max_by_key(|(&number, line)| (line.priority, core::cmp::Reverse(number)))
+                    .map(|(&number, _)| number)
+            });
+            let Some(number) = next else {
+                return ran;
+            };
+            if Self::fire_number(number) {
+                ran += 1;
+            }
+        }
+    }
+
+    /// Clears the state of all interrupts, their handlers and the recorded calls on this
+    /// thread.
+    pub fn reset() {
+        // drop the handlers outside of the borrow, in case they own something that uses the mock
+        let old = Self::with(core::mem::take);
+        drop(old);
+    }
+}
+
+impl InterruptController for MockInterruptController {
+    unsafe fn enable<I: InterruptNumber>(interrupt: I) {
+        Self::record(MockCall::Enable(interrupt.number()));
+        Self::line(interrupt, |line| line.enabled = true);
+    }
+
+    fn disable<I: InterruptNumber>(interrupt: I) {
+        Self::record(MockCall::Disable(interrupt.number()));
+        Self::line(interrupt, |line| line.enabled = false);
+    }
+
+    fn is_enabled<I: InterruptNumber>(interrupt: I) -> bool {
+        Self::line(interrupt, |line| line.enabled)
+    }
+
+    fn pend<I: InterruptNumber>(interrupt: I) {
+        Self::record(MockCall::Pend(interrupt.number()));
+        Self::line(interrupt, |line| line.pending = true);
+    }
+
+    fn unpend<I: InterruptNumber>(interrupt: I) {
+        Self::record(MockCall::Unpend(interrupt.number()));
+        Self::line(interrupt, |line| line.pending = false);
+    }
+
+    fn is_pending<I: InterruptNumber>(interrupt: I) -> bool {
+        Self::line(interrupt, |line| line.pending)
+    }
+
+    unsafe fn set_priority<I: InterruptNumber>(interrupt: I, priority: u8) {
+        Self::record(MockCall::SetPriority(interrupt.number(), priority));
+        Self::line(interrupt, |line| line.priority = priority);
+    }
+}
@@ -0,0 +1,219 @@
+//! A lock-free bounded multi-producer multi-consumer queue.
+//!
+//! [`mpsc::Queue`](crate::mpsc::Queue) serializes every operation with a critical section, and
+//! with it all cores. This queue instead uses Dmitry Vyukov's bounded MPMC algorithm: every slot
+//! carries a sequence number that tells producers and consumers whether it is free or full, so
+//! any number of cores and interrupt handlers can enqueue and dequeue at the same time without
+//! masking interrupts.
+//!
+//! ```
+//! use bare_metal::mpmc::Queue;
+//!
+//! static EVENTS: Queue<u8, 2> = Queue::new();
+//!
+//! // On any core, or in any interrupt handler
+//! EVENTS.enqueue(1).unwrap();
+//! EVENTS.enqueue(2).unwrap();
+//! assert_eq!(EVENTS.enqueue(3), Err(3));
+//!
+//! // Likewise
+//! assert_eq!(EVENTS.dequeue(), Some(1));
+//! assert_eq!(EVENTS.dequeue(), Some(2));
+//! assert_eq!(EVENTS.dequeue(), None);
+//! ```
+//!
+//! The queue is lock-free on targets with atomic compare-and-swap operations. Elsewhere, the
+//! atomics enter a short critical section each, see the `portable-atomic` feature.
+
+use core::fmt;
+use core::mem::MaybeUninit;
+
+use crate::atomic::{AtomicUsize, Ordering, UnsafeCell};
+use crate::CachePadded;
+
+struct Slot<T> {
+    // the sequence number minus the index of the slot, so that all slots start out as zero
+    sequence: AtomicUsize,
+    value: UnsafeCell<MaybeUninit<T>>,
+}
+
+impl<T> Slot<T> {
+    #[cfg(not(loom))]
+    #[allow(clippy::declare_interior_mutable_const)]
+    const EMPTY: Self = Slot {
+        sequence: AtomicUsize::new(0),
+        value: UnsafeCell::new(MaybeUninit::uninit()),
+    };
+
+    #[cfg(not(loom))]
+    const fn array<const N: usize>() -> [Self; N] {
+        [Self::EMPTY; N]
+    }
+
+    #[cfg(loom)]
+    fn array<const N: usize>() -> [Self; N] {
+        core::array::from_fn(|_| Slot {
+            sequence: AtomicUsize::new(0),
+            value: UnsafeCell::new(MaybeUninit::uninit()),
+        })
+    }
+}
+
+/// A lock-free bounded multi-producer multi-consumer queue that can hold `N` elements.
+///
+/// `N` must be a power of two.
+///
+/// Enqueuing into a full queue and dequeuing from an empty queue fail right away instead of
+/// waiting. When a context is interrupted halfway through an operation, the slot it was working
+/// on looks full to producers and empty to consumers until it resumes, so an interrupt handler
+/// may see the queue full or empty a little early, but never waits for the context it
+/// interrupted.
+pub struct Queue<T, const N: usize> {
+    // position of the next element to dequeue
+    head: CachePadded<AtomicUsize>,
+    // position of the next element to enqueue
+    tail: CachePadded<AtomicUsize>,
+    slots: [Slot<T>; N],
+}
+
+impl<T, const N: usize> Queue<T, N> {
+    const_fn! {
+        /// Creates an empty queue.
+        ///
+        /// # Panics
+        ///
+        /// Panics if `N` is not a power of two.
+        #[inline]
+        pub const fn new() -> Self {
+            assert!(N.is_power_of_two(), "the capacity must be a power of two");
+            Queue {
+                head: CachePadded::new(AtomicUsize::new(0)),
+                tail: CachePadded::new(AtomicUsize::new(0)),
+                slots: Slot::array(),
+            }
+        }
+    }
+
+    // returns the slot for `position` and its sequence number
+    #[inline]
+    fn slot(&self, position: usize) -> (&Slot<T>, usize) {
+        let index = position & (N - 1);
+        let slot = &self.slots[index];
+        let sequence = slot.sequence.load(Ordering::Acquire).wrapping_add(index);
+        (slot, sequence)
+    }
+
+    /// Adds an element to the back of the queue, returning it if the queue is full.
+    pub fn enqueue(&self, val: T) -> Result<(), T> {
+        let mut position = self.tail.load(Ordering::Relaxed);
+        loop {
+            let (slot, sequence) = self.slot(position);
+            match sequence.wrapping_sub(position) as isize {
+                0 => match self.tail.compare_exchange_weak(
+                    position,
+                    position.wrapping_add(1),
+                    Ordering::Relaxed,
+                    Ordering::Relaxed,
+                ) {
+                    Ok(_) => {
+                        // SAFETY: winning the compare-and-swap gives exclusive access to the free
+                        // slot until its sequence number is updated
+                        slot.value.with_mut(|value| unsafe { (*value).write(val) });
+                        slot.sequence.store(
+                            position.wrapping_add(1).wrapping_sub(position & (N - 1)),
+                            Ordering::Release,
+                        );
+                        return Ok(());
+                    }
+                    Err(actual) => position = actual,
+                },
+                // the slot still holds the element from the previous round
+                diff if diff < 0 => return Err(val),
+                // another producer took the slot, try again with the new tail
+                _ => position = self.tail.load(Ordering::Relaxed),
+            }
+        }
+    }
+
+    /// Removes the element at the front of the queue, returning `None` if it is empty.
+    pub fn dequeue(&self) -> Option<T> {
+        let mut position = self.head.load(Ordering::Relaxed);
+        loop {
+            let (slot, sequence) = self.slot(position);
+            match sequence.wrapping_sub(position.wrapping_add(1)) as isize {
+                0 => match self.head.compare_exchange_weak(
+                    position,
+                    position.wrapping_add(1),
+                    Ordering::Relaxed,
+                    Ordering::Relaxed,
+                ) {
+                    Ok(_) => {
+                        // SAFETY: winning the compare-and-swap gives exclusive access to the full
+                        // slot until its sequence number is updated
+                        let val = slot
+                            .value
+                            .with(|value| unsafe { (*value).assume_init_read() });
+                        slot.sequence.store(
+                            position.wrapping_add(N).wrapping_sub(position & (N - 1)),
+                            Ordering::Release,
+                        );
+                        return Some(val);
+                    }
+                    Err(actual) => position = actual,
+                },
+                // the slot hasn't been filled yet
+                diff if diff < 0 => return None,
+                // another consumer emptied the slot, try again with the new head
+                _ => position = self.head.load(Ordering::Relaxed),
+            }
+        }
+    }
+
+    /// Returns the number of elements in the queue.
+    ///
+    /// The result is only a snapshot when other contexts use the queue at the same time.
+    #[inline]
+    pub fn len(&self) -> usize {
+        let head = self.head.load(Ordering::Relaxed);
+        let tail = self.tail.load(Ordering::Relaxed);
+        tail.wrapping_sub(head).min(N)
+    }
+
+    /// Returns `true` if the queue is empty.
+    ///
+    /// The result is only a snapshot when other contexts use the queue at the same time.
+    #[inline]
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    /// Returns the maximum number of elements the queue can hold.
+    #[inline]
+    pub const fn capacity(&self) -> usize {
+        N
+    }
+}
+
+impl<T, const N: usize> Default for Queue<T, N> {
+    fn default() -> Self {
+        Queue::new()
+    }
+}
+
+impl<T, const N: usize> Drop for Queue<T, N> {
+    fn drop(&mut self) {
+        while self.dequeue().is_some() {}
+    }
+}
+
+impl<T, const N: usize> fmt::Debug for Queue<T, N> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("Queue")
+            .field("len", &self.len())
+            .finish_non_exhaustive()
+    }
+}
+
+// NOTE elements are moved between contexts, and the sequence numbers give exactly one context
+// access to a slot at a time
+unsafe impl<T, const N: usize> Sync for Queue<T, N> where T: Send {}
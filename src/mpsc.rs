@@ -0,0 +1,176 @@
+//! A bounded multi-producer single-consumer queue based on critical sections.
+//!
+//! Any number of contexts, e.g. several interrupt handlers, can push elements into the queue,
+//! which the main loop drains. Every operation runs inside a short critical section.
+//!
+//! ```
+//! use bare_metal::mpsc::Queue;
+//!
+//! static EVENTS: Queue<u8, 2> = Queue::new();
+//!
+//! // In the interrupt handlers
+//! EVENTS.enqueue(1).unwrap();
+//! EVENTS.enqueue(2).unwrap();
+//! assert_eq!(EVENTS.enqueue(3), Err(3));
+//!
+//! // In the main loop
+//! assert_eq!(EVENTS.overflows(), 1);
+//! while let Some(event) = EVENTS.dequeue() {
+//!     // handle `event`
+//! #   let _ = event;
+//! }
+//! ```
+
+use core::cell::UnsafeCell;
+use core::fmt;
+use core::mem::MaybeUninit;
+
+use critical_section::CriticalSection;
+
+struct State<T, const N: usize> {
+    // index of the first element
+    head: usize,
+    len: usize,
+    overflows: usize,
+    buffer: MaybeUninit<[T; N]>,
+}
+
+/// A bounded multi-producer single-consumer queue that can hold `N` elements.
+///
+/// Elements that don't fit into the queue are rejected and counted, see
+/// [`overflows`](Queue::overflows).
+pub struct Queue<T, const N: usize> {
+    state: UnsafeCell<State<T, N>>,
+}
+
+impl<T, const N: usize> Queue<T, N> {
+    /// Creates an empty queue.
+    #[inline]
+    pub const fn new() -> Self {
+        Queue {
+            state: UnsafeCell::new(State {
+                head: 0,
+                len: 0,
+                overflows: 0,
+                buffer: MaybeUninit::uninit(),
+            }),
+        }
+    }
+
+    #[inline]
+    fn with<R>(&self, f: impl FnOnce(&mut State<T, N>) -> R) -> R {
+        crate::with_cs(|cs| self.with_cs(cs, f))
+    }
+
+    #[inline]
+    fn with_cs<R>(&self, _cs: CriticalSection<'_>, f: impl FnOnce(&mut State<T, N>) -> R) -> R {
+        // SAFETY: the state is only accessed inside critical sections, and `f` can't reach it
+        // again through `self`
+        f(unsafe { &mut *self.state.get() })
+    }
+
+    /// Adds an element to the back of the queue.
+    ///
+    /// If the queue is full, the overflow counter is incremented and the element is returned.
+    #[inline]
+    pub fn enqueue(&self, val: T) -> Result<(), T> {
+        crate::with_cs(|cs| self.enqueue_cs(cs, val))
+    }
+
+    /// Like [`enqueue`](Queue::enqueue), but uses an existing critical section.
+    pub fn enqueue_cs(&self, cs: CriticalSection<'_>, val: T) -> Result<(), T> {
+        self.with_cs(cs, |state| {
+            if state.len == N {
+                state.overflows = state.overflows.wrapping_add(1);
+                return Err(val);
+            }
+            let index = (state.head + state.len) % N;
+            // SAFETY: `index` is smaller than `N` and the slot is free
+            unsafe { (state.buffer.as_mut_ptr() as *mut T).add(index).write(val) };
+            state.len += 1;
+            Ok(())
+        })
+    }
+
+    /// Removes the element at the front of the queue, returning `None` if it is empty.
+    #[inline]
+    pub fn dequeue(&self) -> Option<T> {
+        crate::with_cs(|cs| self.dequeue_cs(cs))
+    }
+
+    /// Like [`dequeue`](Queue::dequeue), but uses an existing critical section.
+    pub fn dequeue_cs(&self, cs: CriticalSection<'_>) -> Option<T> {
+        self.with_cs(cs, |state| {
+            if state.len == 0 {
+                return None;
+            }
+            // SAFETY: `head` is smaller than `N` and the slot holds an element
+            let val = unsafe { (state.buffer.as_ptr() as *const T).add(state.head).read() };
+            state.head = (state.head + 1) % N;
+            state.len -= 1;
+            Some(val)
+        })
+    }
+
+    /// Returns the number of elements in the queue.
+    #[inline]
+    pub fn len(&self) -> usize {
+        self.with(|state| state.len)
+    }
+
+    /// Returns `true` if the queue is empty.
+    #[inline]
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    /// Returns the maximum number of elements the queue can hold.
+    #[inline]
+    pub const fn capacity(&self) -> usize {
+        N
+    }
+
+    /// Returns how many elements were rejected because the queue was full.
+    ///
+    /// The counter wraps around on overflow.
+    #[inline]
+    pub fn overflows(&self) -> usize {
+        self.with(|state| state.overflows)
+    }
+
+    /// Resets the overflow counter, returning its previous value.
+    #[inline]
+    pub fn reset_overflows(&self) -> usize {
+        self.with(|state| core::mem::replace(&mut state.overflows, 0))
+    }
+}
+
+impl<T, const N: usize> Default for Queue<T, N> {
+    fn default() -> Self {
+        Queue::new()
+    }
+}
+
+impl<T, const N: usize> Drop for Queue<T, N> {
+    fn drop(&mut self) {
+        let state = self.state.get_mut();
+        for i in 0..state.len {
+            let index = (state.head + i) % N;
+            // SAFETY: the slot holds an element which is never used again
+            unsafe {
+                (state.buffer.as_mut_ptr() as *mut T)
+                    .add(index)
+                    .drop_in_place()
+            };
+        }
+    }
+}
+
+impl<T, const N: usize> fmt::Debug for Queue<T, N> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("Queue").finish_non_exhaustive()
+    }
+}
+
+// NOTE elements are moved between contexts, all accesses happen inside critical sections
+unsafe impl<T, const N: usize> Sync for Queue<T, N> where T: Send {}
@@ -0,0 +1,822 @@
+//! Convenience methods for [`Mutex`](critical_section::Mutex).
+//!
+//! [`Mutex`](critical_section::Mutex) is defined in the `critical-section` crate, so the methods
+//! in this module are provided as extension traits. Bring them into scope with a `use` to call
+//! them as if they were inherent methods.
+
+use core::cell::{BorrowError, BorrowMutError, Cell, OnceCell, Ref, RefCell, RefMut};
+use core::fmt;
+use core::mem::ManuallyDrop;
+use core::ops::{Deref, DerefMut};
+
+use critical_section::{CriticalSection, Mutex, RestoreState};
+
+mod sealed {
+    pub trait Sealed {}
+}
+
+/// Methods that forward to [`Cell`] on a `Mutex<Cell<T>>`.
+///
+/// This mirrors the inherent `impl` block `critical-section` has for `Mutex<RefCell<T>>`.
+///
+/// ```
+/// use bare_metal::MutexCellExt;
+/// use core::cell::Cell;
+/// use critical_section::{CriticalSection, Mutex};
+///
+/// static COUNTER: Mutex<Cell<u32>> = Mutex::new(Cell::new(0));
+///
+/// let cs = unsafe { CriticalSection::new() };
+/// // Instead of calling this
+/// COUNTER.borrow(cs).set(1);
+/// // Call this
+/// COUNTER.set(cs, 2);
+/// COUNTER.update(cs, |x| x + 1);
+/// assert_eq!(COUNTER.replace(cs, 0), 3);
+/// assert_eq!(COUNTER.get(cs), 0);
+/// ```
+pub trait MutexCellExt<T>: sealed::Sealed {
+    /// Borrow the data and call [`Cell::get`]
+    ///
+    /// This is equivalent to `self.borrow(cs).get()`
+    fn get(&self, cs: CriticalSection<'_>) -> T
+    where
+        T: Copy;
+
+    /// Borrow the data and call [`Cell::set`]
+    ///
+    /// This is equivalent to `self.borrow(cs).set(val)`
+    fn set(&self, cs: CriticalSection<'_>, val: T);
+
+    /// Borrow the data and call [`Cell::replace`]
+    ///
+    /// This is equivalent to `self.borrow(cs).replace(val)`
+    fn replace(&self, cs: CriticalSection<'_>, val: T) -> T;
+
+    /// Borrow the data of both mutexes and call [`Cell::swap`]
+    ///
+    /// This is equivalent to `self.borrow(cs).swap(other.borrow(cs))`
+    fn swap(&self, cs: CriticalSection<'_>, other: &Self);
+
+    /// Borrow the data and update it using `f`
+    ///
+    /// This is equivalent to `self.borrow(cs).set(f(self.borrow(cs).get()))`
+    fn update<F>(&self, cs: CriticalSection<'_>, f: F)
+    where
+        T: Copy,
+        F: FnOnce(T) -> T;
+
+    /// Borrow the data and return a copy of it
+    ///
+    /// This is the same as [`get`](MutexCellExt::get), named to match
+    /// [`MutexRefCellExt::clone_inner`].
+    fn clone_inner(&self, cs: CriticalSection<'_>) -> T
+    where
+        T: Copy;
+
+    /// Borrow the data and compare it to `other`
+    ///
+    /// This is equivalent to `self.borrow(cs).get() == *other`
+    fn eq_inner(&self, cs: CriticalSection<'_>, other: &T) -> bool
+    where
+        T: Copy + PartialEq;
+}
+
+impl<T> sealed::Sealed for Mutex<Cell<T>> {}
+
+impl<T> MutexCellExt<T> for Mutex<Cell<T>> {
+    #[inline]
+    fn get(&self, cs: CriticalSection<'_>) -> T
+    where
+        T: Copy,
+    {
+        self.borrow(cs).get()
+    }
+
+    #[inline]
+    fn set(&self, cs: CriticalSection<'_>, val: T) {
+        self.borrow(cs).set(val)
+    }
+
+    #[inline]
+    fn replace(&self, cs: CriticalSection<'_>, val: T) -> T {
+        self.borrow(cs).replace(val)
+    }
+
+    #[inline]
+    fn swap(&self, cs: CriticalSection<'_>, other: &Self) {
+        self.borrow(cs).swap(other.borrow(cs))
+    }
+
+    #[inline]
+    fn update<F>(&self, cs: CriticalSection<'_>, f: F)
+    where
+        T: Copy,
+        F: FnOnce(T) -> T,
+    {
+        let cell = self.borrow(cs);
+        cell.set(f(cell.get()))
+    }
+
+    #[inline]
+    fn clone_inner(&self, cs: CriticalSection<'_>) -> T
+    where
+        T: Copy,
+    {
+        self.borrow(cs).get()
+    }
+
+    #[inline]
+    fn eq_inner(&self, cs: CriticalSection<'_>, other: &T) -> bool
+    where
+        T: Copy + PartialEq,
+    {
+        self.borrow(cs).get() == *other
+    }
+}
+
+/// Additional methods that forward to [`RefCell`] on a `Mutex<RefCell<T>>`.
+///
+/// These complement the inherent methods `critical-section` already provides for
+/// `Mutex<RefCell<T>>`.
+///
+/// ```
+/// use bare_metal::MutexRefCellExt;
+/// use core::cell::RefCell;
+/// use critical_section::{CriticalSection, Mutex};
+///
+/// static STATE: Mutex<RefCell<u32>> = Mutex::new(RefCell::new(0));
+///
+/// let cs = unsafe { CriticalSection::new() };
+/// let guard = STATE.borrow_ref_mut(cs);
+/// // A second borrow is reported instead of panicking
+/// assert!(STATE.try_borrow_ref(cs).is_err());
+/// assert!(STATE.try_borrow_ref_mut(cs).is_err());
+/// drop(guard);
+/// assert!(STATE.try_borrow_ref_mut(cs).is_ok());
+/// ```
+///
+/// The `*_map` methods narrow the borrow guard down to a part of the protected data.
+///
+/// ```
+/// use bare_metal::MutexRefCellExt;
+/// use core::cell::RefCell;
+/// use critical_section::{CriticalSection, Mutex};
+///
+/// struct Driver {
+///     errors: u32,
+///     buffer: [u8; 4],
+/// }
+///
+/// static DRIVER: Mutex<RefCell<Driver>> = Mutex::new(RefCell::new(Driver {
+///     errors: 0,
+///     buffer: [0; 4],
+/// }));
+///
+/// let cs = unsafe { CriticalSection::new() };
+/// *DRIVER.borrow_ref_mut_map(cs, |d| &mut d.errors) += 1;
+/// let buffer = DRIVER.borrow_ref_map(cs, |d| &d.buffer[..]);
+/// assert_eq!(buffer.len(), 4);
+/// ```
+///
+/// `swap` exchanges the contents of two mutexes inside a single critical section, e.g. to flip
+/// the buffers of a double-buffering scheme.
+///
+/// ```
+/// use bare_metal::MutexRefCellExt;
+/// use core::cell::RefCell;
+/// use critical_section::{CriticalSection, Mutex};
+///
+/// static FRONT: Mutex<RefCell<[u8; 2]>> = Mutex::new(RefCell::new([0; 2]));
+/// static BACK: Mutex<RefCell<[u8; 2]>> = Mutex::new(RefCell::new([1; 2]));
+///
+/// let cs = unsafe { CriticalSection::new() };
+/// FRONT.swap(cs, &BACK);
+/// assert_eq!(*FRONT.borrow_ref(cs), [1; 2]);
+/// assert_eq!(*BACK.borrow_ref(cs), [0; 2]);
+/// ```
+///
+/// `with_ref` and `with_ref_mut` release the borrow before they return, so the result of the
+/// closure is not tied to a borrow guard.
+///
+/// ```
+/// use bare_metal::MutexRefCellExt;
+/// use core::cell::RefCell;
+/// use critical_section::{CriticalSection, Mutex};
+///
+/// static SAMPLES: Mutex<RefCell<[u16; 4]>> = Mutex::new(RefCell::new([1, 2, 3, 4]));
+///
+/// let cs = unsafe { CriticalSection::new() };
+/// let sum: u16 = SAMPLES.with_ref(cs, |samples| samples.iter().sum());
+/// SAMPLES.with_ref_mut(cs, |samples| samples[0] = sum);
+/// assert_eq!(SAMPLES.borrow_ref(cs)[0], 10);
+///
+/// // Take a snapshot for diagnostics
+/// let snapshot = SAMPLES.clone_inner(cs);
+/// assert!(SAMPLES.eq_inner(cs, &snapshot));
+///
+/// // SAFETY: no other borrow of `SAMPLES` exists while `samples` is alive
+/// let samples = unsafe { SAMPLES.borrow_ref_mut_unchecked(cs) };
+/// samples[1] = 0;
+/// ```
+///
+/// `lock` enters the critical section itself, so no token has to be passed around.
+///
+/// ```
+/// use bare_metal::MutexRefCellExt;
+/// use core::cell::RefCell;
+/// use critical_section::Mutex;
+///
+/// static COUNT: Mutex<RefCell<u32>> = Mutex::new(RefCell::new(0));
+///
+/// let count = COUNT.lock(|count| {
+///     *count += 1;
+///     *count
+/// });
+/// assert_eq!(count, 1);
+/// ```
+pub trait MutexRefCellExt<T>: sealed::Sealed {
+    /// Borrow the data and call [`RefCell::try_borrow`]
+    ///
+    /// This is equivalent to `self.borrow(cs).try_borrow()`
+    fn try_borrow_ref<'cs>(&'cs self, cs: CriticalSection<'cs>)
+        -> Result<Ref<'cs, T>, BorrowError>;
+
+    /// Borrow the data and call [`RefCell::try_borrow_mut`]
+    ///
+    /// This is equivalent to `self.borrow(cs).try_borrow_mut()`
+    fn try_borrow_ref_mut<'cs>(
+        &'cs self,
+        cs: CriticalSection<'cs>,
+    ) -> Result<RefMut<'cs, T>, BorrowMutError>;
+
+    /// Borrow the data and call [`Ref::map`] on the guard
+    ///
+    /// This is equivalent to `Ref::map(self.borrow(cs).borrow(), f)`
+    ///
+    /// # Panics
+    ///
+    /// This call could panic. See the documentation for [`RefCell::borrow`]
+    /// for more details.
+    fn borrow_ref_map<'cs, U, F>(&'cs self, cs: CriticalSection<'cs>, f: F) -> Ref<'cs, U>
+    where
+        U: ?Sized,
+        F: FnOnce(&T) -> &U;
+
+    /// Borrow the data and call [`RefMut::map`] on the guard
+    ///
+    /// This is equivalent to `RefMut::map(self.borrow(cs).borrow_mut(), f)`
+    ///
+    /// # Panics
+    ///
+    /// This call could panic. See the documentation for [`RefCell::borrow_mut`]
+    /// for more details.
+    fn borrow_ref_mut_map<'cs, U, F>(&'cs self, cs: CriticalSection<'cs>, f: F) -> RefMut<'cs, U>
+    where
+        U: ?Sized,
+        F: FnOnce(&mut T) -> &mut U;
+
+    /// Borrow the data of both mutexes and call [`RefCell::swap`]
+    ///
+    /// This is equivalent to `self.borrow(cs).swap(other.borrow(cs))`
+    ///
+    /// # Panics
+    ///
+    /// This call could panic. See the documentation for [`RefCell::swap`]
+    /// for more details.
+    fn swap(&self, cs: CriticalSection<'_>, other: &Self);
+
+    /// Borrow the data, call `f` on it and release the borrow again
+    ///
+    /// This is equivalent to `f(&self.borrow(cs).borrow())`
+    ///
+    /// # Panics
+    ///
+    /// This call could panic. See the documentation for [`RefCell::borrow`]
+    /// for more details.
+    fn with_ref<R, F>(&self, cs: CriticalSection<'_>, f: F) -> R
+    where
+        F: FnOnce(&T) -> R;
+
+    /// Mutably borrow the data, call `f` on it and release the borrow again
+    ///
+    /// This is equivalent to `f(&mut self.borrow(cs).borrow_mut())`
+    ///
+    /// # Panics
+    ///
+    /// This call could panic. See the documentation for [`RefCell::borrow_mut`]
+    /// for more details.
+    fn with_ref_mut<R, F>(&self, cs: CriticalSection<'_>, f: F) -> R
+    where
+        F: FnOnce(&mut T) -> R;
+
+    /// Borrow the data and return a clone of it
+    ///
+    /// This is equivalent to `self.borrow(cs).borrow().clone()`
+    ///
+    /// # Panics
+    ///
+    /// This call could panic. See the documentation for [`RefCell::borrow`]
+    /// for more details.
+    fn clone_inner(&self, cs: CriticalSection<'_>) -> T
+    where
+        T: Clone;
+
+    /// Borrow the data and compare it to `other`
+    ///
+    /// This is equivalent to `*self.borrow(cs).borrow() == *other`
+    ///
+    /// # Panics
+    ///
+    /// This call could panic. See the documentation for [`RefCell::borrow`]
+    /// for more details.
+    fn eq_inner(&self, cs: CriticalSection<'_>, other: &T) -> bool
+    where
+        T: PartialEq;
+
+    /// Mutably borrow the data without checking or updating the borrow flag
+    ///
+    /// This skips the runtime check of [`RefCell::borrow_mut`] and its panic path. It is meant
+    /// for hot paths, like a high-rate interrupt handler, where the check has a measurable cost.
+    ///
+    /// # Safety
+    ///
+    /// For as long as the returned reference is alive, the caller must ensure that the data is
+    /// not accessed in any other way. In particular, no other borrow, neither one obtained
+    /// through this method nor one tracked by the `RefCell`, may exist at the same time.
+    unsafe fn borrow_ref_mut_unchecked<'cs>(&'cs self, cs: CriticalSection<'cs>) -> &'cs mut T;
+
+    /// Enter a critical section, mutably borrow the data and call `f` on it
+    ///
+    /// This is equivalent to `critical_section::with(|cs| f(&mut self.borrow_ref_mut(cs)))`
+    ///
+    /// # Panics
+    ///
+    /// This call could panic. See the documentation for [`RefCell::borrow_mut`]
+    /// for more details.
+    fn lock<R, F>(&self, f: F) -> R
+    where
+        F: FnOnce(&mut T) -> R;
+
+    /// Enter a critical section and mutably borrow the data, returning a guard that owns both
+    ///
+    /// Dropping the [`MutexGuard`] releases the borrow and then leaves the critical section.
+    /// Unlike [`lock`](MutexRefCellExt::lock), this lets a helper function hand out a locked
+    /// handle to its caller.
+    ///
+    /// ```
+    /// use bare_metal::{MutexGuard, MutexRefCellExt};
+    /// use core::cell::RefCell;
+    /// use critical_section::Mutex;
+    ///
+    /// static BUFFER: Mutex<RefCell<[u8; 4]>> = Mutex::new(RefCell::new([0; 4]));
+    ///
+    /// fn buffer() -> MutexGuard<'static, [u8; 4]> {
+    ///     // SAFETY: the guard is dropped before any other guard is created
+    ///     unsafe { BUFFER.lock_guard() }
+    /// }
+    ///
+    /// buffer()[0] = 1;
+    /// assert_eq!(*buffer(), [1, 0, 0, 0]);
+    /// ```
+    ///
+    /// # Safety
+    ///
+    /// Critical sections must be properly nested. The guard must be dropped before any critical
+    /// section that was entered before it, including the one of an enclosing
+    /// `critical_section::with` call and the one owned by another `MutexGuard`, is left.
+    ///
+    /// # Panics
+    ///
+    /// Panics if the value is currently borrowed. The critical section is left before panicking.
+    unsafe fn lock_guard(&self) -> MutexGuard<'_, T>;
+}
+
+impl<T> sealed::Sealed for Mutex<RefCell<T>> {}
+
+impl<T> MutexRefCellExt<T> for Mutex<RefCell<T>> {
+    #[inline]
+    fn try_borrow_ref<'cs>(
+        &'cs self,
+        cs: CriticalSection<'cs>,
+    ) -> Result<Ref<'cs, T>, BorrowError> {
+        let r = self.borrow(cs).try_borrow();
+        #[cfg(feature = "instrument")]
+        if r.is_err() {
+            crate::instrument::borrow_failed(cs);
+        }
+        r
+    }
+
+    #[inline]
+    fn try_borrow_ref_mut<'cs>(
+        &'cs self,
+        cs: CriticalSection<'cs>,
+    ) -> Result<RefMut<'cs, T>, BorrowMutError> {
+        let r = self.borrow(cs).try_borrow_mut();
+        #[cfg(feature = "instrument")]
+        if r.is_err() {
+            crate::instrument::borrow_failed(cs);
+        }
+        r
+    }
+
+    #[inline]
+    #[track_caller]
+    fn borrow_ref_map<'cs, U, F>(&'cs self, cs: CriticalSection<'cs>, f: F) -> Ref<'cs, U>
+    where
+        U: ?Sized,
+        F: FnOnce(&T) -> &U,
+    {
+        Ref::map(self.borrow(cs).borrow(), f)
+    }
+
+    #[inline]
+    #[track_caller]
+    fn borrow_ref_mut_map<'cs, U, F>(&'cs self, cs: CriticalSection<'cs>, f: F) -> RefMut<'cs, U>
+    where
+        U: ?Sized,
+        F: FnOnce(&mut T) -> &mut U,
+    {
+        RefMut::map(self.borrow(cs).borrow_mut(), f)
+    }
+
+    #[inline]
+    #[track_caller]
+    fn swap(&self, cs: CriticalSection<'_>, other: &Self) {
+        self.borrow(cs).swap(other.borrow(cs))
+    }
+
+    #[inline]
+    #[track_caller]
+    fn with_ref<R, F>(&self, cs: CriticalSection<'_>, f: F) -> R
+    where
+        F: FnOnce(&T) -> R,
+    {
+        f(&self.borrow(cs).borrow())
+    }
+
+    #[inline]
+    #[track_caller]
+    fn with_ref_mut<R, F>(&self, cs: CriticalSection<'_>, f: F) -> R
+    where
+        F: FnOnce(&mut T) -> R,
+    {
+        f(&mut self.borrow(cs).borrow_mut())
+    }
+
+    #[inline]
+    #[track_caller]
+    fn clone_inner(&self, cs: CriticalSection<'_>) -> T
+    where
+        T: Clone,
+    {
+        self.borrow(cs).borrow().clone()
+    }
+
+    #[inline]
+    #[track_caller]
+    fn eq_inner(&self, cs: CriticalSection<'_>, other: &T) -> bool
+    where
+        T: PartialEq,
+    {
+        *self.borrow(cs).borrow() == *other
+    }
+
+    #[inline]
+    unsafe fn borrow_ref_mut_unchecked<'cs>(&'cs self, cs: CriticalSection<'cs>) -> &'cs mut T {
+        &mut *self.borrow(cs).as_ptr()
+    }
+
+    #[inline]
+    #[track_caller]
+    fn lock<R, F>(&self, f: F) -> R
+    where
+        F: FnOnce(&mut T) -> R,
+    {
+        crate::with_cs(|cs| self.with_ref_mut(cs, f))
+    }
+
+    #[inline]
+    #[track_caller]
+    unsafe fn lock_guard(&self) -> MutexGuard<'_, T> {
+        let restore = crate::cs::acquire_raw();
+        match self.borrow(CriticalSection::new()).try_borrow_mut() {
+            Ok(borrow) => MutexGuard {
+                borrow: ManuallyDrop::new(borrow),
+                restore,
+                #[cfg(feature = "instrument")]
+                start: crate::instrument::start(CriticalSection::new()),
+            },
+            Err(_) => {
+                #[cfg(feature = "instrument")]
+                crate::instrument::borrow_failed(CriticalSection::new());
+                crate::cs::release_raw(restore);
+                already_borrowed()
+            }
+        }
+    }
+}
+
+#[cold]
+#[track_caller]
+fn already_borrowed() -> ! {
+    panic!("already borrowed")
+}
+
+/// A critical section and a mutable borrow of a `Mutex<RefCell<T>>`, released together.
+///
+/// This is created by [`MutexRefCellExt::lock_guard`].
+pub struct MutexGuard<'a, T> {
+    borrow: ManuallyDrop<RefMut<'a, T>>,
+    restore: RestoreState,
+    #[cfg(feature = "instrument")]
+    start: Option<u32>,
+}
+
+impl<T> Deref for MutexGuard<'_, T> {
+    type Target = T;
+
+    #[inline]
+    fn deref(&self) -> &T {
+        &self.borrow
+    }
+}
+
+impl<T> DerefMut for MutexGuard<'_, T> {
+    #[inline]
+    fn deref_mut(&mut self) -> &mut T {
+        &mut self.borrow
+    }
+}
+
+impl<T: fmt::Debug> fmt::Debug for MutexGuard<'_, T> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        fmt::Debug::fmt(&**self, f)
+    }
+}
+
+impl<T> Drop for MutexGuard<'_, T> {
+    #[inline]
+    fn drop(&mut self) {
+        // NOTE the borrow must be released while still inside the critical section
+        unsafe {
+            ManuallyDrop::drop(&mut self.borrow);
+            #[cfg(feature = "instrument")]
+            let overrun = crate::instrument::record(CriticalSection::new(), self.start);
+            crate::cs::release_raw(self.restore);
+            #[cfg(feature = "instrument")]
+            if let Some(overrun) = overrun {
+                overrun.report();
+            }
+        }
+    }
+}
+
+/// Methods for the late-initialization pattern `Mutex<RefCell<Option<T>>>`.
+///
+/// Resources that are created in `main` and later used from interrupt handlers are commonly
+/// stored in a `Mutex<RefCell<Option<T>>>`. These methods remove the
+/// `.borrow(cs).borrow_mut().as_mut().unwrap()` chain from such code.
+///
+/// ```
+/// use bare_metal::MutexOptionExt;
+/// use core::cell::RefCell;
+/// use critical_section::{CriticalSection, Mutex};
+///
+/// struct Led(bool);
+///
+/// static LED: Mutex<RefCell<Option<Led>>> = Mutex::new(RefCell::new(None));
+///
+/// let cs = unsafe { CriticalSection::new() };
+/// // Nothing happens until the resource has been moved in
+/// assert_eq!(LED.with_some(cs, |led| led.0 = true), None);
+///
+/// LED.set(cs, Led(false));
+/// // In the interrupt handler
+/// assert_eq!(LED.with_some(cs, |led| { led.0 = !led.0; led.0 }), Some(true));
+///
+/// let led = LED.take_inner(cs).unwrap();
+/// assert!(led.0);
+/// ```
+pub trait MutexOptionExt<T>: sealed::Sealed {
+    /// Borrow the data and replace it with `Some(val)`
+    ///
+    /// This is equivalent to `*self.borrow(cs).borrow_mut() = Some(val)`
+    ///
+    /// # Panics
+    ///
+    /// Panics if the value is currently borrowed.
+    fn set(&self, cs: CriticalSection<'_>, val: T);
+
+    /// Borrow the data and call `f` on it if it is `Some`
+    ///
+    /// This is equivalent to `self.borrow(cs).borrow_mut().as_mut().map(f)`
+    ///
+    /// # Panics
+    ///
+    /// Panics if the value is currently borrowed.
+    fn with_some<R, F>(&self, cs: CriticalSection<'_>, f: F) -> Option<R>
+    where
+        F: FnOnce(&mut T) -> R;
+
+    /// Borrow the data and take it out, leaving `None` in its place
+    ///
+    /// This is equivalent to `self.borrow(cs).borrow_mut().take()`
+    ///
+    /// # Panics
+    ///
+    /// Panics if the value is currently borrowed.
+    fn take_inner(&self, cs: CriticalSection<'_>) -> Option<T>;
+}
+
+impl<T> MutexOptionExt<T> for Mutex<RefCell<Option<T>>> {
+    #[inline]
+    #[track_caller]
+    fn set(&self, cs: CriticalSection<'_>, val: T) {
+        *self.borrow(cs).borrow_mut() = Some(val);
+    }
+
+    #[inline]
+    #[track_caller]
+    fn with_some<R, F>(&self, cs: CriticalSection<'_>, f: F) -> Option<R>
+    where
+        F: FnOnce(&mut T) -> R,
+    {
+        self.borrow(cs).borrow_mut().as_mut().map(f)
+    }
+
+    #[inline]
+    #[track_caller]
+    fn take_inner(&self, cs: CriticalSection<'_>) -> Option<T> {
+        self.borrow(cs).borrow_mut().take()
+    }
+}
+
+/// Methods for movable resources stored in a `Mutex<Cell<Option<T>>>`.
+///
+/// Unlike `Mutex<RefCell<Option<T>>>`, this doesn't need a borrow flag, at the cost of only
+/// being able to move the resource in and out.
+///
+/// ```
+/// use bare_metal::MutexCellOptionExt;
+/// use core::cell::Cell;
+/// use critical_section::{CriticalSection, Mutex};
+///
+/// struct Timer;
+///
+/// static TIMER: Mutex<Cell<Option<Timer>>> = Mutex::new(Cell::new(None));
+///
+/// let cs = unsafe { CriticalSection::new() };
+/// assert!(TIMER.put(cs, Timer).is_none());
+/// // Move the resource out, use it, and move it back in
+/// let timer = TIMER.take(cs).unwrap();
+/// assert!(TIMER.take(cs).is_none());
+/// TIMER.put(cs, timer);
+/// ```
+pub trait MutexCellOptionExt<T>: sealed::Sealed {
+    /// Borrow the data and take it out, leaving `None` in its place
+    ///
+    /// This is equivalent to `self.borrow(cs).take()`
+    fn take(&self, cs: CriticalSection<'_>) -> Option<T>;
+
+    /// Borrow the data and replace it with `Some(val)`, returning the previous value
+    ///
+    /// This is equivalent to `self.borrow(cs).replace(Some(val))`
+    fn put(&self, cs: CriticalSection<'_>, val: T) -> Option<T>;
+}
+
+impl<T> MutexCellOptionExt<T> for Mutex<Cell<Option<T>>> {
+    #[inline]
+    fn take(&self, cs: CriticalSection<'_>) -> Option<T> {
+        self.borrow(cs).take()
+    }
+
+    #[inline]
+    fn put(&self, cs: CriticalSection<'_>, val: T) -> Option<T> {
+        self.borrow(cs).replace(Some(val))
+    }
+}
+
+/// Methods that forward to [`OnceCell`] on a `Mutex<OnceCell<T>>`.
+///
+/// This is the first-class way to share state that is initialized exactly once, instead of the
+/// `Mutex<RefCell<Option<T>>>` workaround.
+///
+/// ```
+/// use bare_metal::MutexOnceCellExt;
+/// use core::cell::OnceCell;
+/// use critical_section::{CriticalSection, Mutex};
+///
+/// static CALIBRATION: Mutex<OnceCell<u16>> = Mutex::new(OnceCell::new());
+///
+/// let cs = unsafe { CriticalSection::new() };
+/// assert_eq!(CALIBRATION.get(cs), None);
+/// assert_eq!(*CALIBRATION.get_or_init(cs, || 1234), 1234);
+/// // The value can only be set once
+/// assert_eq!(CALIBRATION.set(cs, 0), Err(0));
+/// assert_eq!(CALIBRATION.get(cs), Some(&1234));
+/// ```
+pub trait MutexOnceCellExt<T>: sealed::Sealed {
+    /// Borrow the data and call [`OnceCell::get`]
+    ///
+    /// This is equivalent to `self.borrow(cs).get()`
+    fn get<'cs>(&'cs self, cs: CriticalSection<'cs>) -> Option<&'cs T>;
+
+    /// Borrow the data and call [`OnceCell::set`]
+    ///
+    /// This is equivalent to `self.borrow(cs).set(val)`
+    fn set(&self, cs: CriticalSection<'_>, val: T) -> Result<(), T>;
+
+    /// Borrow the data and call [`OnceCell::get_or_init`]
+    ///
+    /// This is equivalent to `self.borrow(cs).get_or_init(f)`
+    ///
+    /// # Panics
+    ///
+    /// This call could panic. See the documentation for [`OnceCell::get_or_init`]
+    /// for more details.
+    fn get_or_init<'cs, F>(&'cs self, cs: CriticalSection<'cs>, f: F) -> &'cs T
+    where
+        F: FnOnce() -> T;
+}
+
+impl<T> sealed::Sealed for Mutex<OnceCell<T>> {}
+
+impl<T> MutexOnceCellExt<T> for Mutex<OnceCell<T>> {
+    #[inline]
+    fn get<'cs>(&'cs self, cs: CriticalSection<'cs>) -> Option<&'cs T> {
+        self.borrow(cs).get()
+    }
+
+    #[inline]
+    fn set(&self, cs: CriticalSection<'_>, val: T) -> Result<(), T> {
+        self.borrow(cs).set(val)
+    }
+
+    #[inline]
+    #[track_caller]
+    fn get_or_init<'cs, F>(&'cs self, cs: CriticalSection<'cs>, f: F) -> &'cs T
+    where
+        F: FnOnce() -> T,
+    {
+        self.borrow(cs).get_or_init(f)
+    }
+}
+
+/// Mutably borrow several `Mutex<RefCell<_>>`s at once.
+///
+/// This is implemented for tuples of up to six references to `Mutex<RefCell<_>>`. All members
+/// are borrowed before the closure runs and released together when it returns.
+///
+/// ```
+/// use bare_metal::MutexGroup;
+/// use core::cell::RefCell;
+/// use critical_section::{CriticalSection, Mutex};
+///
+/// static RX: Mutex<RefCell<u32>> = Mutex::new(RefCell::new(0));
+/// static TX: Mutex<RefCell<u32>> = Mutex::new(RefCell::new(0));
+/// static ERRORS: Mutex<RefCell<u32>> = Mutex::new(RefCell::new(0));
+///
+/// let cs = unsafe { CriticalSection::new() };
+/// let total = (&RX, &TX, &ERRORS).with(cs, |rx, tx, errors| {
+///     *rx += 2;
+///     *tx += 1;
+///     *errors += 1;
+///     *rx + *tx + *errors
+/// });
+/// assert_eq!(total, 4);
+/// ```
+pub trait MutexGroup<F, R>: sealed::Sealed {
+    /// Mutably borrow all members and call `f` on them
+    ///
+    /// # Panics
+    ///
+    /// Panics if any member is currently borrowed, or if the same mutex appears more than once.
+    fn with(self, cs: CriticalSection<'_>, f: F) -> R;
+}
+
+macro_rules! mutex_group {
+    ($($t:ident $m:ident),+) => {
+        impl<'a, $($t),+> sealed::Sealed for ($(&'a Mutex<RefCell<$t>>,)+) {}
+
+        impl<'a, $($t,)+ F, R> MutexGroup<F, R> for ($(&'a Mutex<RefCell<$t>>,)+)
+        where
+            F: FnOnce($(&mut $t),+) -> R,
+        {
+            #[inline]
+            #[track_caller]
+            fn with(self, cs: CriticalSection<'_>, f: F) -> R {
+                let ($($m,)+) = self;
+                $(let mut $m = $m.borrow(cs).borrow_mut();)+
+                f($(&mut $m),+)
+            }
+        }
+    };
+}
+
+mutex_group!(A a, B b);
+mutex_group!(A a, B b, C c);
+mutex_group!(A a, B b, C c, D d);
+mutex_group!(A a, B b, C c, D d, E e);
+mutex_group!(A a, B b, C c, D d, E e, G g);
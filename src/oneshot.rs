@@ -0,0 +1,181 @@
+//! A channel for handing exactly one value from an interrupt handler to a waiting task.
+//!
+//! This fits completion notifications, like a DMA transfer or an ADC conversion finishing: the
+//! [`Sender`] is moved to the interrupt handler, which [`send`](Sender::send)s the result
+//! inside a short critical section, and the task awaits the [`Receiver`]. Without an allocator
+//! the sender and receiver borrow their storage, a [`Channel`], which is
+//! [`split`](Channel::split) into the two halves for every transfer.
+//!
+//! ```
+//! use bare_metal::oneshot::{Canceled, Channel, Receiver};
+//!
+//! async fn wait_for_transfer(done: Receiver<'_, usize>) -> Result<usize, Canceled> {
+//!     done.await
+//! }
+//!
+//! let mut channel = Channel::new();
+//! let (sender, receiver) = channel.split();
+//!
+//! // In the interrupt handler
+//! sender.send(64).unwrap();
+//! # let _ = wait_for_transfer(receiver);
+//! ```
+//!
+//! If the sender is dropped without sending a value, the receiver resolves to
+//! `Err(Canceled)`.
+
+use core::fmt;
+use core::future::Future;
+use core::pin::Pin;
+use core::task::{Context, Poll};
+
+use crate::{CsCell, WakerSlot};
+
+/// The storage for one value sent from a [`Sender`] to a [`Receiver`].
+pub struct Channel<T> {
+    value: CsCell<Option<T>>,
+    // set when either half has been dropped
+    closed: CsCell<bool>,
+    waker: WakerSlot,
+}
+
+impl<T> Channel<T> {
+    const_fn! {
+        /// Creates an empty channel.
+        #[inline]
+        pub const fn new() -> Self {
+            Channel {
+                value: CsCell::new(None),
+                closed: CsCell::new(false),
+                waker: WakerSlot::new(),
+            }
+        }
+    }
+
+    /// Splits the channel into its sender and receiver halves.
+    ///
+    /// Any value left over from a previous split is dropped.
+    #[inline]
+    pub fn split(&mut self) -> (Sender<'_, T>, Receiver<'_, T>) {
+        *self.value.get_mut() = None;
+        *self.closed.get_mut() = false;
+        (Sender { channel: self }, Receiver { channel: self })
+    }
+}
+
+impl<T> Default for Channel<T> {
+    fn default() -> Self {
+        Channel::new()
+    }
+}
+
+impl<T> fmt::Debug for Channel<T> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("Channel").finish_non_exhaustive()
+    }
+}
+
+/// The sending half of a [`Channel`].
+///
+/// Dropping the sender without sending a value cancels the receiver.
+pub struct Sender<'a, T> {
+    channel: &'a Channel<T>,
+}
+
+impl<T> Sender<'_, T> {
+    /// Sends `value` and wakes the receiver, or gives `value` back if the receiver has been
+    /// dropped.
+    #[inline]
+    pub fn send(self, value: T) -> Result<(), T> {
+        crate::with_cs(|cs| {
+            if self.channel.closed.get(cs) {
+                Err(value)
+            } else {
+                self.channel.value.set(cs, Some(value));
+                Ok(())
+            }
+        })
+    }
+
+    /// Returns `true` if the receiver has been dropped.
+    #[inline]
+    pub fn is_canceled(&self) -> bool {
+        crate::with_cs(|cs| self.channel.closed.get(cs))
+    }
+}
+
+impl<T> Drop for Sender<'_, T> {
+    fn drop(&mut self) {
+        crate::with_cs(|cs| self.channel.closed.set(cs, true));
+        self.channel.waker.wake();
+    }
+}
+
+impl<T> fmt::Debug for Sender<'_, T> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("Sender").finish_non_exhaustive()
+    }
+}
+
+/// The receiving half of a [`Channel`], which is a future that resolves to the sent value.
+#[must_use = "futures do nothing unless polled"]
+pub struct Receiver<'a, T> {
+    channel: &'a Channel<T>,
+}
+
+impl<T> Receiver<'_, T> {
+    /// Takes the value if it has been sent, without waiting.
+    ///
+    /// Returns `Ok(None)` if it hasn't been sent yet, and `Err(Canceled)` if the sender has been
+    /// dropped without sending a value.
+    #[inline]
+    pub fn try_recv(&mut self) -> Result<Option<T>, Canceled> {
+        crate::with_cs(|cs| match self.channel.value.take(cs) {
+            Some(value) => Ok(Some(value)),
+            None if self.channel.closed.get(cs) => Err(Canceled),
+            None => Ok(None),
+        })
+    }
+}
+
+impl<T> Future for Receiver<'_, T> {
+    type Output = Result<T, Canceled>;
+
+    fn poll(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Self::Output> {
+        let this = self.get_mut();
+        this.channel.waker.register(cx.waker());
+        // check after registering, so a value sent in between isn't missed
+        match this.try_recv() {
+            Ok(Some(value)) => Poll::Ready(Ok(value)),
+            Ok(None) => Poll::Pending,
+            Err(canceled) => Poll::Ready(Err(canceled)),
+        }
+    }
+}
+
+impl<T> Drop for Receiver<'_, T> {
+    fn drop(&mut self) {
+        let value = crate::with_cs(|cs| {
+            self.channel.closed.set(cs, true);
+            self.channel.value.take(cs)
+        });
+        // drop a value that was sent but never received outside of the critical section
+        drop(value);
+    }
+}
+
+impl<T> fmt::Debug for Receiver<'_, T> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("Receiver").finish_non_exhaustive()
+    }
+}
+
+/// The error returned by a [`Receiver`] whose sender was dropped without sending a value.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct Canceled;
+
+impl fmt::Display for Canceled {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str("the sender was dropped without sending a value")
+    }
+}
@@ -0,0 +1,87 @@
+use core::cell::{Ref, RefCell, RefMut};
+use core::pin::Pin;
+
+use critical_section::{CriticalSection, Mutex};
+
+/// A `Mutex<RefCell<T>>` that hands out pinned mutable borrows.
+///
+/// `Mutex<RefCell<T>>` can't be used to hold `!Unpin` data like futures or intrusive list nodes
+/// because methods such as `replace` and `take` move the data out through a shared reference.
+/// `PinMutex` doesn't provide any such method, so the data is *structurally pinned*: once the
+/// `PinMutex` itself is pinned, the data never moves again until it is dropped.
+///
+/// A `PinMutex` in a `static` can be pinned with [`PinMutex::pin_static`].
+///
+/// ```
+/// use bare_metal::PinMutex;
+/// use core::marker::PhantomPinned;
+/// use critical_section::CriticalSection;
+///
+/// struct Node {
+///     value: u32,
+///     _pinned: PhantomPinned,
+/// }
+///
+/// static NODE: PinMutex<Node> = PinMutex::new(Node {
+///     value: 0,
+///     _pinned: PhantomPinned,
+/// });
+///
+/// let cs = unsafe { CriticalSection::new() };
+/// let mut node = NODE.pin_static().borrow_ref_mut_pinned(cs);
+/// // Only fields that are not structurally pinned may be accessed mutably
+/// unsafe { node.as_mut().get_unchecked_mut().value += 1 };
+/// drop(node);
+/// assert_eq!(NODE.borrow_ref(cs).value, 1);
+/// ```
+pub struct PinMutex<T> {
+    inner: Mutex<RefCell<T>>,
+}
+
+impl<T> PinMutex<T> {
+    /// Creates a new mutex.
+    #[inline]
+    pub const fn new(value: T) -> Self {
+        PinMutex {
+            inner: Mutex::new(RefCell::new(value)),
+        }
+    }
+
+    /// Pins a `PinMutex` that lives for the rest of the program, e.g. one stored in a `static`.
+    #[inline]
+    pub fn pin_static(&'static self) -> Pin<&'static Self> {
+        // SAFETY: a `&'static` reference can't be used to move the value, and nothing else can
+        // obtain ownership of it while the reference exists, so it stays in place forever.
+        unsafe { Pin::new_unchecked(self) }
+    }
+
+    /// Borrow the data and call [`RefCell::borrow`]
+    ///
+    /// # Panics
+    ///
+    /// This call could panic. See the documentation for [`RefCell::borrow`]
+    /// for more details.
+    #[inline]
+    #[track_caller]
+    pub fn borrow_ref<'cs>(&'cs self, cs: CriticalSection<'cs>) -> Ref<'cs, T> {
+        self.inner.borrow_ref(cs)
+    }
+
+    /// Borrow the data and call [`RefCell::borrow_mut`], pinning the returned guard
+    ///
+    /// # Panics
+    ///
+    /// This call could panic. See the documentation for [`RefCell::borrow_mut`]
+    /// for more details.
+    #[inline]
+    #[track_caller]
+    pub fn borrow_ref_mut_pinned<'cs>(
+        self: Pin<&'cs Self>,
+        cs: CriticalSection<'cs>,
+    ) -> Pin<RefMut<'cs, T>> {
+        let guard = self.get_ref().inner.borrow_ref_mut(cs);
+        // SAFETY: `self` is pinned and `PinMutex` offers no way to move the data out of a
+        // shared reference, so the data stays pinned as well.
+        unsafe { Pin::new_unchecked(guard) }
+    }
+}
@@ -0,0 +1,248 @@
+use core::cell::UnsafeCell;
+use core::fmt;
+use core::marker::PhantomData;
+use core::mem::{ManuallyDrop, MaybeUninit};
+use core::ops::{Deref, DerefMut};
+use core::ptr::NonNull;
+
+use crate::CsRefCell;
+
+// a free block holds the index of the next free block, an allocated one the value
+#[repr(C)]
+union Block<T> {
+    value: ManuallyDrop<T>,
+    next: usize,
+}
+
+#[derive(Clone, Copy)]
+struct State {
+    // index of the first block of the free list, or `usize::MAX` if the list is empty
+    free: usize,
+    // number of blocks at the end that have never been allocated, and therefore aren't linked
+    // into the free list yet
+    fresh: usize,
+    used: usize,
+}
+
+/// A pool of `N` fixed-size blocks for values of type `T`.
+///
+/// [`alloc`](Pool::alloc) moves a value into a free block and returns a [`PoolBox`], which
+/// gives the block back to the pool when it's dropped. This gives heap-like ownership of DMA
+/// descriptors, packets and the like without a global allocator. Allocating and freeing
+/// each take one short critical section.
+///
+/// ```
+/// use bare_metal::{Pool, PoolBox};
+///
+/// struct Packet {
+///     len: usize,
+///     data: [u8; 64],
+/// }
+///
+/// static PACKETS: Pool<Packet, 2> = Pool::new();
+///
+/// let mut packet: PoolBox<'static, Packet> = PACKETS
+///     .alloc(Packet { len: 0, data: [0; 64] })
+///     .ok()
+///     .unwrap();
+/// packet.data[0] = 0xaa;
+/// packet.len = 1;
+/// assert_eq!(PACKETS.available(), 1);
+///
+/// drop(packet);
+/// assert_eq!(PACKETS.available(), 2);
+/// ```
+pub struct Pool<T, const N: usize> {
+    state: CsRefCell<State>,
+    blocks: [UnsafeCell<MaybeUninit<Block<T>>>; N],
+}
+
+impl<T, const N: usize> Pool<T, N> {
+    #[allow(clippy::declare_interior_mutable_const)]
+    const FREE: UnsafeCell<MaybeUninit<Block<T>>> = UnsafeCell::new(MaybeUninit::uninit());
+
+    /// Creates a pool whose blocks are all free.
+    #[inline]
+    pub const fn new() -> Self {
+        Pool {
+            state: CsRefCell::new(State {
+                free: usize::MAX,
+                fresh: N,
+                used: 0,
+            }),
+            blocks: [Self::FREE; N],
+        }
+    }
+
+    /// Moves `value` into a free block, or gives it back if all blocks are allocated.
+    pub fn alloc(&self, value: T) -> Result<PoolBox<'_, T>, T> {
+        let Some(index) = self.take_block() else {
+            return Err(value);
+        };
+        let block = self.blocks[index].get().cast::<Block<T>>();
+        // SAFETY: the block was just taken off the free list, so nothing else refers to it
+        unsafe {
+            block.write(Block {
+                value: ManuallyDrop::new(value),
+            })
+        };
+        Ok(PoolBox {
+            // SAFETY: the pointer is derived from a reference
+            block: unsafe { NonNull::new_unchecked(block) },
+            index,
+            state: &self.state,
+            _value: PhantomData,
+        })
+    }
+
+    /// Takes a free block for a value that the caller writes, and frees with
+    /// [`free_raw`](Pool::free_raw).
+    #[cfg(feature = "alloc")]
+    pub(crate) fn alloc_raw(&self) -> Option<NonNull<T>> {
+        let index = self.take_block()?;
+        // SAFETY: the pointer is derived from a reference
+        Some(unsafe { NonNull::new_unchecked(self.blocks[index].get().cast::<T>()) })
+    }
+
+    /// Returns a block taken with [`alloc_raw`](Pool::alloc_raw) to the pool.
+    ///
+    /// # Safety
+    ///
+    /// `ptr` must have been returned by `alloc_raw` of this pool, and must not be used
+    /// afterwards. Any value in the block must have been dropped or moved out.
+    #[cfg(feature = "alloc")]
+    pub(crate) unsafe fn free_raw(&self, ptr: NonNull<T>) {
+        let offset = ptr.as_ptr() as usize - self.blocks.as_ptr() as usize;
+        let index = offset / core::mem::size_of::<UnsafeCell<MaybeUninit<Block<T>>>>();
+        free_block(&self.state, ptr.cast::<Block<T>>(), index);
+    }
+
+    fn take_block(&self) -> Option<usize> {
+        crate::with_cs(|cs| {
+            let mut state = self.state.borrow_mut(cs);
+            let index = if state.free != usize::MAX {
+                let index = state.free;
+                // SAFETY: blocks on the free list hold the index of the next free block
+                state.free = unsafe { (*self.blocks[index].get()).assume_init_ref().next };
+                index
+            } else if state.fresh > 0 {
+                state.fresh -= 1;
+                N - 1 - state.fresh
+            } else {
+                return None;
+            };
+            state.used += 1;
+            Some(index)
+        })
+    }
+
+    /// Returns the number of free blocks.
+    #[inline]
+    pub fn available(&self) -> usize {
+        N - crate::with_cs(|cs| self.state.borrow_mut(cs).used)
+    }
+
+    /// Returns the number of blocks in the pool.
+    #[inline]
+    pub const fn capacity(&self) -> usize {
+        N
+    }
+}
+
+impl<T, const N: usize> Default for Pool<T, N> {
+    fn default() -> Self {
+        Pool::new()
+    }
+}
+
+impl<T, const N: usize> fmt::Debug for Pool<T, N> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("Pool")
+            .field("available", &self.available())
+            .finish_non_exhaustive()
+    }
+}
+
+// NOTE values are moved into the pool by one context and dropped by whichever context owns the
+// `PoolBox`; the free list is only accessed inside critical sections
+unsafe impl<T, const N: usize> Sync for Pool<T, N> where T: Send {}
+
+/// An owned value in a block of a [`Pool`], like a `Box`.
+///
+/// Dropping the `PoolBox` drops the value and returns the block to the pool.
+pub struct PoolBox<'a, T> {
+    block: NonNull<Block<T>>,
+    index: usize,
+    state: &'a CsRefCell<State>,
+    _value: PhantomData<T>,
+}
+
+impl<T> PoolBox<'_, T> {
+    /// Moves the value out of the pool and returns its block.
+    ///
+    /// This is an associated function, so that it doesn't shadow a method of `T`.
+    #[inline]
+    pub fn into_inner(this: Self) -> T {
+        let this = ManuallyDrop::new(this);
+        // SAFETY: the block holds the value, which is never used again
+        let value = unsafe { ManuallyDrop::take(&mut (*this.block.as_ptr()).value) };
+        this.free();
+        value
+    }
+
+    fn free(&self) {
+        // SAFETY: the value has been moved out or dropped, so the block can be reused
+        unsafe { free_block(self.state, self.block, self.index) }
+    }
+}
+
+// Links the block at `index` into the free list.
+//
+// SAFETY (for callers): the block must be allocated, and hold no value.
+unsafe fn free_block<T>(state: &CsRefCell<State>, block: NonNull<Block<T>>, index: usize) {
+    crate::with_cs(|cs| {
+        let mut state = state.borrow_mut(cs);
+        (*block.as_ptr()).next = state.free;
+        state.free = index;
+        state.used -= 1;
+    });
+}
+
+impl<T> Deref for PoolBox<'_, T> {
+    type Target = T;
+
+    #[inline]
+    fn deref(&self) -> &T {
+        // SAFETY: the block holds the value until the `PoolBox` is dropped
+        unsafe { &(*self.block.as_ptr()).value }
+    }
+}
+
+impl<T> DerefMut for PoolBox<'_, T> {
+    #[inline]
+    fn deref_mut(&mut self) -> &mut T {
+        // SAFETY: the block holds the value until the `PoolBox` is dropped, and the `PoolBox`
+        // is its only owner
+        unsafe { &mut (*self.block.as_ptr()).value }
+    }
+}
+
+impl<T> Drop for PoolBox<'_, T> {
+    fn drop(&mut self) {
+        // drop the value outside of the critical section
+        // SAFETY: the block holds the value, which is never used again
+        unsafe { ManuallyDrop::drop(&mut (*self.block.as_ptr()).value) };
+        self.free();
+    }
+}
+
+impl<T: fmt::Debug> fmt::Debug for PoolBox<'_, T> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        fmt::Debug::fmt(&**self, f)
+    }
+}
+
+// NOTE a `PoolBox` owns its value like a `Box`, and the free list it returns the block to is
+// only accessed inside critical sections
+unsafe impl<T> Send for PoolBox<'_, T> where T: Send {}
+unsafe impl<T> Sync for PoolBox<'_, T> where T: Sync {}
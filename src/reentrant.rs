@@ -0,0 +1,188 @@
+use core::cell::Cell;
+use core::fmt;
+use core::marker::PhantomData;
+use core::ops::Deref;
+
+/// Identifies the core the caller is running on.
+///
+/// Architecture or HAL crates implement this trait on a marker type, which is then used as the
+/// `C` parameter of a [`ReentrantMutex`].
+pub trait CoreId {
+    /// Returns the ID of the core this function is called on.
+    fn current() -> usize;
+}
+
+/// A mutex that can be acquired again by the core that already holds it.
+///
+/// The lock records which core owns it using the [`CoreId`] hook `C`. Another core trying to
+/// acquire it spins until it is released, while the owning core can acquire it as many times
+/// as it likes, e.g. on a call path that takes the lock in several places.
+///
+/// The bookkeeping is done inside short critical sections, so on multi-core targets the
+/// `critical-section` implementation must exclude the other cores, e.g. by using a hardware
+/// spinlock. Since code on the owning core, including its interrupt handlers, may hold several
+/// guards at the same time, the lock only hands out shared references. The data must be `Sync`
+/// as well, which rules out a plain `Cell` or `RefCell`; for mutation, put atomics, a
+/// [`CsCell`](crate::CsCell) or [`CsRefCell`](crate::CsRefCell), or a
+/// `critical_section::Mutex<RefCell<_>>` inside it.
+///
+/// ```
+/// use bare_metal::{CoreId, ReentrantMutex};
+///
+/// struct SingleCore;
+///
+/// impl CoreId for SingleCore {
+///     fn current() -> usize {
+///         0
+///     }
+/// }
+///
+/// static LOG: ReentrantMutex<u32, SingleCore> = ReentrantMutex::new(0);
+///
+/// let outer = LOG.lock();
+/// // The same core may lock again
+/// let inner = LOG.lock();
+/// assert_eq!(*outer, *inner);
+/// ```
+///
+/// Mutating the data through a guard:
+///
+/// ```
+/// use bare_metal::{CoreId, CsCell, ReentrantMutex};
+/// # struct SingleCore;
+/// # impl CoreId for SingleCore {
+/// #     fn current() -> usize {
+/// #         0
+/// #     }
+/// # }
+///
+/// static COUNT: ReentrantMutex<CsCell<u32>, SingleCore> = ReentrantMutex::new(CsCell::new(0));
+///
+/// let count = COUNT.lock();
+/// critical_section::with(|cs| count.update(cs, |n| n + 1));
+/// assert_eq!(critical_section::with(|cs| count.get(cs)), 1);
+/// ```
+pub struct ReentrantMutex<T, C> {
+    owner: Cell<Option<usize>>,
+    count: Cell<usize>,
+    value: T,
+    _core: PhantomData<fn() -> C>,
+}
+
+impl<T, C> ReentrantMutex<T, C> {
+    /// Creates a new, unlocked `ReentrantMutex`.
+    #[inline]
+    pub const fn new(value: T) -> Self {
+        ReentrantMutex {
+            owner: Cell::new(None),
+            count: Cell::new(0),
+            value,
+            _core: PhantomData,
+        }
+    }
+
+    /// Returns a mutable reference to the data.
+    ///
+    /// This does not need to take the lock since `&mut self` already guarantees exclusive
+    /// access.
+    #[inline]
+    pub fn get_mut(&mut self) -> &mut T {
+        &mut self.value
+    }
+
+    /// Consumes the `ReentrantMutex`, returning the data.
+    #[inline]
+    pub fn into_inner(self) -> T {
+        self.value
+    }
+}
+
+impl<T, C: CoreId> ReentrantMutex<T, C> {
+    /// Acquires the lock, spinning while another core holds it.
+    ///
+    /// # Panics
+    ///
+    /// Panics if the lock count overflows.
+    #[inline]
+    pub fn lock(&self) -> ReentrantMutexGuard<'_, T, C> {
+        loop {
+            if let Some(guard) = self.try_lock() {
+                return guard;
+            }
+            core::hint::spin_loop();
+        }
+    }
+
+    /// Attempts to acquire the lock, returning `None` if another core holds it.
+    ///
+    /// # Panics
+    ///
+    /// Panics if the lock count overflows.
+    pub fn try_lock(&self) -> Option<ReentrantMutexGuard<'_, T, C>> {
+        let core = C::current();
+        let acquired = crate::with_cs(|_| match self.owner.get() {
+            Some(owner) if owner != core => false,
+            _ => {
+                self.owner.set(Some(core));
+                let count = self.count.get().checked_add(1);
+                self.count.set(count.expect("lock count overflow"));
+                true
+            }
+        });
+        if acquired {
+            Some(ReentrantMutexGuard {
+                lock: self,
+                _not_send: PhantomData,
+            })
+        } else {
+            None
+        }
+    }
+}
+
+impl<T, C> fmt::Debug for ReentrantMutex<T, C> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("ReentrantMutex").finish_non_exhaustive()
+    }
+}
+
+// NOTE the owner and count are only accessed inside critical sections. The data is shared by
+// all guards on the owning core, including ones held by interrupt handlers, so it must be `Sync`
+unsafe impl<T, C> Sync for ReentrantMutex<T, C> where T: Send + Sync {}
+
+/// Shared access to the data in a [`ReentrantMutex`].
+///
+/// The lock is released once all guards of the owning core have been dropped.
+pub struct ReentrantMutexGuard<'a, T, C: CoreId> {
+    lock: &'a ReentrantMutex<T, C>,
+    // the guard must be dropped on the core that acquired it
+    _not_send: PhantomData<*mut ()>,
+}
+
+impl<T, C: CoreId> Deref for ReentrantMutexGuard<'_, T, C> {
+    type Target = T;
+
+    #[inline]
+    fn deref(&self) -> &T {
+        &self.lock.value
+    }
+}
+
+impl<T, C: CoreId> Drop for ReentrantMutexGuard<'_, T, C> {
+    #[inline]
+    fn drop(&mut self) {
+        crate::with_cs(|_| {
+            let count = self.lock.count.get() - 1;
+            self.lock.count.set(count);
+            if count == 0 {
+                self.lock.owner.set(None);
+            }
+        })
+    }
+}
+
+impl<T: fmt::Debug, C: CoreId> fmt::Debug for ReentrantMutexGuard<'_, T, C> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        (**self).fmt(f)
+    }
+}
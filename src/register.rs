@@ -0,0 +1,684 @@
+//! Memory-mapped register wrappers.
+//!
+//! [`RO`], [`WO`] and [`RW`] wrap a [`VolatileCell`] and only expose the accesses that the
+//! register supports, so reading a write-only register or writing a read-only one fails to
+//! compile. All of them are `#[repr(transparent)]`, which makes them suitable as the fields of a
+//! `#[repr(C)]` register block.
+//!
+//! ```
+//! use bare_metal::register::{RO, RW, WO};
+//!
+//! #[repr(C)]
+//! struct Uart {
+//!     data: RW<u32>,
+//!     status: RO<u32>,
+//!     command: WO<u32>,
+//! }
+//! # let uart = Uart { data: RW::new(0), status: RO::new(1), command: WO::new(0) };
+//!
+//! uart.data.write(b'a' as u32);
+//! assert_eq!(uart.data.read(), b'a' as u32);
+//! assert_eq!(uart.status.read(), 1);
+//! uart.command.write(0b11);
+//! ```
+//!
+//! ```compile_fail
+//! use bare_metal::register::WO;
+//!
+//! let command = WO::new(0u32);
+//! command.read();
+//! ```
+//!
+//! [`BitAccess`] sets and clears single bits of a register without racing with other contexts,
+//! using bit-banding where the system supports it. [`MmioBlock`] gives access to a register
+//! block at a fixed address, and [`Field`] reads and writes bit fields of register values.
+//!
+//! For code generators, [`RegisterSpec`], [`Readable`], [`Writable`], [`Resettable`] and
+//! [`FieldSpec`] describe registers and their fields at the type level, and [`Reg`] is the
+//! register wrapper that goes with them.
+
+use core::fmt;
+use core::marker::PhantomData;
+
+use crate::VolatileCell;
+
+/// A read-only register.
+#[repr(transparent)]
+pub struct RO<T> {
+    register: VolatileCell<T>,
+}
+
+impl<T: Copy> RO<T> {
+    /// Creates a register holding `value`.
+    ///
+    /// This is meant for tests and simulations; real registers are reached through a pointer to
+    /// their register block.
+    #[inline]
+    pub const fn new(value: T) -> Self {
+        RO {
+            register: VolatileCell::new(value),
+        }
+    }
+
+    /// Reads the register.
+    #[inline(always)]
+    pub fn read(&self) -> T {
+        self.register.get()
+    }
+}
+
+impl<T> fmt::Debug for RO<T> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("RO").finish_non_exhaustive()
+    }
+}
+
+/// A write-only register.
+#[repr(transparent)]
+pub struct WO<T> {
+    register: VolatileCell<T>,
+}
+
+impl<T: Copy> WO<T> {
+    /// Creates a register holding `value`.
+    ///
+    /// This is meant for tests and simulations; real registers are reached through a pointer to
+    /// their register block.
+    #[inline]
+    pub const fn new(value: T) -> Self {
+        WO {
+            register: VolatileCell::new(value),
+        }
+    }
+
+    /// Writes `value` to the register.
+    #[inline(always)]
+    pub fn write(&self, value: T) {
+        self.register.set(value)
+    }
+}
+
+impl<T> fmt::Debug for WO<T> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("WO").finish_non_exhaustive()
+    }
+}
+
+/// A read-write register.
+#[repr(transparent)]
+pub struct RW<T> {
+    register: VolatileCell<T>,
+}
+
+impl<T: Copy> RW<T> {
+    /// Creates a register holding `value`.
+    ///
+    /// This is meant for tests and simulations; real registers are reached through a pointer to
+    /// their register block.
+    #[inline]
+    pub const fn new(value: T) -> Self {
+        RW {
+            register: VolatileCell::new(value),
+        }
+    }
+
+    /// Reads the register.
+    #[inline(always)]
+    pub fn read(&self) -> T {
+        self.register.get()
+    }
+
+    /// Writes `value` to the register.
+    #[inline(always)]
+    pub fn write(&self, value: T) {
+        self.register.set(value)
+    }
+
+    /// Reads the register, passes the value to `f` and writes back the value it returns.
+    ///
+    /// ```
+    /// use bare_metal::register::RW;
+    ///
+    /// const ENABLE: u32 = 1 << 0;
+    ///
+    /// let control = RW::new(0b100u32);
+    /// control.modify(|r| r | ENABLE);
+    /// assert_eq!(control.read(), 0b101);
+    /// ```
+    ///
+    /// This is not atomic: if another context may modify the register in between, call this
+    /// inside a critical section.
+    #[inline(always)]
+    pub fn modify(&self, f: impl FnOnce(T) -> T) {
+        self.write(f(self.read()))
+    }
+
+    /// Like [`modify`](RW::modify), but passes the value to `f` as the typed view `V`.
+    ///
+    /// `V` is usually a newtype around the raw value with accessors for the fields of the
+    /// register.
+    ///
+    /// ```
+    /// use bare_metal::register::RW;
+    ///
+    /// #[derive(Clone, Copy)]
+    /// struct Control(u32);
+    ///
+    /// impl Control {
+    ///     fn set_prescaler(self, prescaler: u32) -> Self {
+    ///         Control((self.0 & !(0xf << 4)) | ((prescaler & 0xf) << 4))
+    ///     }
+    /// }
+    ///
+    /// impl From<u32> for Control {
+    ///     fn from(raw: u32) -> Self {
+    ///         Control(raw)
+    ///     }
+    /// }
+    ///
+    /// impl From<Control> for u32 {
+    ///     fn from(control: Control) -> Self {
+    ///         control.0
+    ///     }
+    /// }
+    ///
+    /// let control = RW::new(0x1u32);
+    /// control.modify_as(|c: Control| c.set_prescaler(3));
+    /// assert_eq!(control.read(), 0x31);
+    /// ```
+    #[inline(always)]
+    pub fn modify_as<V>(&self, f: impl FnOnce(V) -> V)
+    where
+        V: From<T> + Into<T>,
+    {
+        self.modify(|raw| f(V::from(raw)).into())
+    }
+}
+
+impl<T> fmt::Debug for RW<T> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("RW").finish_non_exhaustive()
+    }
+}
+
+/// A mapping from register bits to bit-band alias addresses, e.g. on ARM Cortex-M3 and M4.
+///
+/// # Safety
+///
+/// If [`alias`](BitBand::alias) returns an address, writing 0 or 1 to it as a `u32` must clear or
+/// set exactly the given bit of the `u32` at `address` atomically, without touching the other
+/// bits, and reading it must return the bit's value.
+pub unsafe trait BitBand {
+    /// Returns the alias address of `bit` of the `u32` at `address`, or `None` if the address is
+    /// outside of the bit-band regions.
+    fn alias(address: usize, bit: u8) -> Option<usize>;
+}
+
+/// A [`BitBand`] mapping for systems without bit-banding.
+#[derive(Debug)]
+pub struct NoBitBand;
+
+unsafe impl BitBand for NoBitBand {
+    #[inline]
+    fn alias(_address: usize, _bit: u8) -> Option<usize> {
+        None
+    }
+}
+
+/// A single bit of a 32-bit register.
+///
+/// [`set`](BitAccess::set), [`clear`](BitAccess::clear) and [`write`](BitAccess::write) change
+/// the bit without racing with other contexts that modify other bits of the same register. If
+/// the backend `B` maps the bit to a bit-band alias, this is a single write to the alias;
+/// otherwise it is a read-modify-write inside a critical section.
+///
+/// ```
+/// use bare_metal::register::{BitAccess, NoBitBand};
+/// use bare_metal::VolatileCell;
+///
+/// let status = VolatileCell::new(0b1000_0000u32);
+///
+/// // SAFETY: `status` outlives `ready`, and isn't modified by anything else
+/// let ready = unsafe { BitAccess::<NoBitBand>::new(status.as_ptr() as usize, 3) };
+/// ready.set();
+/// assert!(ready.read());
+/// assert_eq!(status.get(), 0b1000_1000);
+/// ```
+///
+/// On a Cortex-M3 or M4, the bit-band regions can be described like this:
+///
+/// ```
+/// use bare_metal::register::BitBand;
+///
+/// struct CortexM;
+///
+/// unsafe impl BitBand for CortexM {
+///     fn alias(address: usize, bit: u8) -> Option<usize> {
+///         let (region, alias) = match address {
+///             0x2000_0000..=0x200f_ffff => (0x2000_0000, 0x2200_0000),
+///             0x4000_0000..=0x400f_ffff => (0x4000_0000, 0x4200_0000),
+///             _ => return None,
+///         };
+///         Some(alias + (address - region) * 32 + usize::from(bit) * 4)
+///     }
+/// }
+///
+/// assert_eq!(CortexM::alias(0x4002_0014, 5), Some(0x4240_0294));
+/// ```
+pub struct BitAccess<B = NoBitBand> {
+    address: usize,
+    bit: u8,
+    _backend: PhantomData<fn() -> B>,
+}
+
+impl<B> BitAccess<B> {
+    /// Creates an accessor for `bit` of the `u32` register at `address`.
+    ///
+    /// # Safety
+    ///
+    /// `address` must be valid for volatile reads and writes of a `u32` for as long as the
+    /// accessor is used. Other code may only modify the register inside critical sections, or
+    /// through its bit-band aliases.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `bit` is 32 or more.
+    #[inline]
+    #[track_caller]
+    pub const unsafe fn new(address: usize, bit: u8) -> Self {
+        if bit >= 32 {
+            bit_out_of_range();
+        }
+        BitAccess {
+            address,
+            bit,
+            _backend: PhantomData,
+        }
+    }
+
+    /// Returns the index of the bit.
+    #[inline]
+    pub const fn bit(&self) -> u8 {
+        self.bit
+    }
+}
+
+#[cold]
+#[track_caller]
+const fn bit_out_of_range() -> ! {
+    panic!("bit index out of range for a 32-bit register")
+}
+
+impl<B: BitBand> BitAccess<B> {
+    /// Returns the value of the bit.
+    #[inline]
+    pub fn read(&self) -> bool {
+        let mask = 1 << self.bit;
+        // SAFETY: the contract of `new` guarantees that the address is valid
+        unsafe { (self.address as *const u32).read_volatile() & mask != 0 }
+    }
+
+    /// Sets the bit to `value`.
+    #[inline]
+    pub fn write(&self, value: bool) {
+        if let Some(alias) = B::alias(self.address, self.bit) {
+            // SAFETY: the contract of `BitBand` guarantees that this only changes the bit
+            unsafe { (alias as *mut u32).write_volatile(u32::from(value)) };
+            return;
+        }
+        let register = self.address as *mut u32;
+        let mask = 1 << self.bit;
+        crate::with_cs(|_cs| {
+            // SAFETY: the contract of `new` guarantees that the address is valid, and that
+            // nothing else modifies the register while we are in the critical section
+            unsafe {
+                let current = register.read_volatile();
+                let new = if value {
+                    current | mask
+                } else {
+                    current & !mask
+                };
+                register.write_volatile(new);
+            }
+        })
+    }
+
+    /// Sets the bit.
+    #[inline]
+    pub fn set(&self) {
+        self.write(true)
+    }
+
+    /// Clears the bit.
+    #[inline]
+    pub fn clear(&self) {
+        self.write(false)
+    }
+}
+
+impl<B> fmt::Debug for BitAccess<B> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("BitAccess")
+            .field("address", &(self.address as *const u32))
+            .field("bit", &self.bit)
+            .finish()
+    }
+}
+
+/// A register block at a fixed address.
+///
+/// This is the "peripheral at a fixed address" pattern: `T` is a `#[repr(C)]` struct of
+/// register wrappers that describes the layout of the block, and `MmioBlock<T>` dereferences to
+/// it.
+///
+/// ```
+/// use bare_metal::register::{MmioBlock, RO, RW};
+///
+/// #[repr(C)]
+/// pub struct Timer {
+///     pub control: RW<u32>,
+///     pub count: RO<u32>,
+/// }
+///
+/// # let memory = Timer { control: RW::new(0), count: RO::new(7) };
+/// # let base = &memory as *const Timer as usize;
+/// // On real hardware, `base` would be the address from the reference manual
+/// let timer = unsafe { MmioBlock::<Timer>::new(base) };
+/// timer.control.write(1);
+/// assert_eq!(timer.count.read(), 7);
+/// ```
+pub struct MmioBlock<T> {
+    block: *const T,
+}
+
+impl<T> MmioBlock<T> {
+    /// Creates a handle to the register block at `base`.
+    ///
+    /// # Safety
+    ///
+    /// `base` must be suitably aligned for `T`, and the memory from `base` to `base +
+    /// size_of::<T>()` must be valid for volatile reads and writes as described by `T` for as
+    /// long as the handle, or any reference obtained through it, is used. Any other handle to the
+    /// same block must only be used from the same context, or coordinate with this one.
+    #[inline]
+    pub const unsafe fn new(base: usize) -> Self {
+        MmioBlock {
+            block: base as *const T,
+        }
+    }
+
+    /// Creates a handle to a register block in ordinary memory.
+    ///
+    /// This is meant for host-side and simulator tests, which can back the registers of a driver
+    /// with a leaked `T` or a `static` instead of a fixed address.
+    ///
+    /// ```
+    /// use bare_metal::register::{MmioBlock, RO, RW};
+    ///
+    /// #[repr(C)]
+    /// pub struct Timer {
+    ///     pub control: RW<u32>,
+    ///     pub count: RO<u32>,
+    /// }
+    ///
+    /// let memory = Box::leak(Box::new(Timer {
+    ///     control: RW::new(0),
+    ///     count: RO::new(7),
+    /// }));
+    /// let timer = MmioBlock::from_mut(memory);
+    /// timer.control.write(1);
+    /// assert_eq!(timer.count.read(), 7);
+    /// ```
+    #[inline]
+    pub fn from_mut(block: &'static mut T) -> Self {
+        MmioBlock { block }
+    }
+
+    /// Returns a pointer to the register block.
+    #[inline]
+    pub const fn as_ptr(&self) -> *const T {
+        self.block
+    }
+}
+
+impl<T> core::ops::Deref for MmioBlock<T> {
+    type Target = T;
+
+    #[inline(always)]
+    fn deref(&self) -> &T {
+        // SAFETY: the contract of `new` guarantees that the block is valid
+        unsafe { &*self.block }
+    }
+}
+
+impl<T> fmt::Debug for MmioBlock<T> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_tuple("MmioBlock").field(&self.block).finish()
+    }
+}
+
+// NOTE the handle owns access to the block like an owned `T` would, so it can be moved to
+// another context as long as the registers could
+unsafe impl<T> Send for MmioBlock<T> where T: Send {}
+
+/// A bit field of `WIDTH` bits, starting at bit `OFFSET`, in a register value of type `T`.
+///
+/// This is implemented for `u8`, `u16`, `u32`, `u64` and `usize`. A field that doesn't fit into
+/// `T`, or has a width of zero, fails to compile when it is used.
+///
+/// ```
+/// use bare_metal::register::{Field, RW};
+///
+/// type Prescaler = Field<u32, 4, 3>;
+///
+/// let control = RW::new(0x0000_0001u32);
+/// Prescaler::write_to(&control, 0b101);
+/// assert_eq!(control.read(), 0x0000_0051);
+/// assert_eq!(Prescaler::read_from(&control), 0b101);
+///
+/// Prescaler::modify_in(&control, |prescaler| prescaler - 1);
+/// assert_eq!(Prescaler::read_from(&control), 0b100);
+/// ```
+///
+/// ```compile_fail
+/// use bare_metal::register::Field;
+///
+/// let _ = Field::<u8, 6, 3>::extract(0xff);
+/// ```
+pub struct Field<T, const OFFSET: u8, const WIDTH: u8> {
+    _value: PhantomData<fn() -> T>,
+}
+
+macro_rules! impl_field {
+    ($($t:ty),*) => {$(
+        impl<const OFFSET: u8, const WIDTH: u8> Field<$t, OFFSET, WIDTH> {
+            /// The bits of the field, in place.
+            pub const MASK: $t = {
+                assert!(
+                    WIDTH > 0 && OFFSET as u32 + WIDTH as u32 <= <$t>::BITS,
+                    "the field doesn't fit into the register"
+                );
+                (<$t>::MAX >> (<$t>::BITS - WIDTH as u32)) << OFFSET
+            };
+
+            /// Returns the value of the field in the register value `raw`.
+            #[inline]
+            pub const fn extract(raw: $t) -> $t {
+                (raw & Self::MASK) >> OFFSET
+            }
+
+            /// Returns `raw` with the field set to `value`.
+            ///
+            /// Bits of `value` that don't fit into the field are ignored.
+            #[inline]
+            pub const fn insert(raw: $t, value: $t) -> $t {
+                (raw & !Self::MASK) | ((value << OFFSET) & Self::MASK)
+            }
+
+            /// Reads the value of the field from `register`.
+            #[inline]
+            pub fn read_from(register: &RW<$t>) -> $t {
+                Self::extract(register.read())
+            }
+
+            /// Sets the field in `register` to `value`, leaving the other bits unchanged.
+            ///
+            /// This is a read-modify-write, see [`RW::modify`]. Bits of `value` that don't fit
+            /// into the field are ignored.
+            #[inline]
+            pub fn write_to(register: &RW<$t>, value: $t) {
+                register.modify(|raw| Self::insert(raw, value))
+            }
+
+            /// Reads the field from `register`, passes its value to `f` and writes back the
+            /// value it returns, leaving the other bits unchanged.
+            #[inline]
+            pub fn modify_in(register: &RW<$t>, f: impl FnOnce($t) -> $t) {
+                register.modify(|raw| Self::insert(raw, f(Self::extract(raw))))
+            }
+        }
+    )*};
+}
+
+impl_field!(u8, u16, u32, u64, usize);
+
+/// Describes a register for [`Reg`], by naming the type of its raw value.
+///
+/// Code generators emit one marker type per register and implement this trait, plus whichever
+/// of [`Readable`], [`Writable`] and [`Resettable`] apply to it. Generic driver code can then be
+/// written against these traits.
+pub trait RegisterSpec {
+    /// The raw value of the register, e.g. `u32`.
+    type Ux: Copy;
+}
+
+/// Marks a register that can be read.
+pub trait Readable: RegisterSpec {}
+
+/// Marks a register that can be written.
+pub trait Writable: RegisterSpec {}
+
+/// A register with a known value after reset.
+pub trait Resettable: RegisterSpec {
+    /// The value of the register after reset.
+    const RESET_VALUE: Self::Ux;
+}
+
+/// Describes a bit field of a register value.
+///
+/// This is implemented by [`Field`].
+pub trait FieldSpec {
+    /// The raw value of the register that contains the field.
+    type Ux: Copy;
+    /// The position of the first bit of the field.
+    const OFFSET: u8;
+    /// The number of bits of the field.
+    const WIDTH: u8;
+}
+
+macro_rules! impl_field_spec {
+    ($($t:ty),*) => {$(
+        impl<const OFFSET: u8, const WIDTH: u8> FieldSpec for Field<$t, OFFSET, WIDTH> {
+            type Ux = $t;
+            const OFFSET: u8 = OFFSET;
+            const WIDTH: u8 = WIDTH;
+        }
+    )*};
+}
+
+impl_field_spec!(u8, u16, u32, u64, usize);
+
+/// A register described by the marker type `REG`.
+///
+/// The accesses that are available depend on which of [`Readable`], [`Writable`] and
+/// [`Resettable`] the marker implements.
+///
+/// ```
+/// use bare_metal::register::{Readable, Reg, RegisterSpec, Resettable, Writable};
+///
+/// pub struct CtrlSpec;
+///
+/// impl RegisterSpec for CtrlSpec {
+///     type Ux = u32;
+/// }
+///
+/// impl Readable for CtrlSpec {}
+/// impl Writable for CtrlSpec {}
+/// impl Resettable for CtrlSpec {
+///     const RESET_VALUE: u32 = 0x0000_0010;
+/// }
+///
+/// fn enable<R: Readable + Writable<Ux = u32>>(register: &Reg<R>) {
+///     register.modify(|r| r | 1);
+/// }
+///
+/// let ctrl = Reg::<CtrlSpec>::new(0);
+/// ctrl.reset();
+/// enable(&ctrl);
+/// assert_eq!(ctrl.read(), 0x0000_0011);
+/// ```
+#[repr(transparent)]
+pub struct Reg<REG: RegisterSpec> {
+    register: VolatileCell<REG::Ux>,
+    _spec: PhantomData<fn() -> REG>,
+}
+
+impl<REG: RegisterSpec> Reg<REG> {
+    /// Creates a register holding `value`.
+    ///
+    /// This is meant for tests and simulations; real registers are reached through a pointer to
+    /// their register block.
+    #[inline]
+    pub const fn new(value: REG::Ux) -> Self {
+        Reg {
+            register: VolatileCell::new(value),
+            _spec: PhantomData,
+        }
+    }
+
+    /// Returns a raw pointer to the register.
+    #[inline]
+    pub const fn as_ptr(&self) -> *mut REG::Ux {
+        self.register.as_ptr()
+    }
+}
+
+impl<REG: Readable> Reg<REG> {
+    /// Reads the register.
+    #[inline(always)]
+    pub fn read(&self) -> REG::Ux {
+        self.register.get()
+    }
+}
+
+impl<REG: Writable> Reg<REG> {
+    /// Writes `value` to the register.
+    #[inline(always)]
+    pub fn write(&self, value: REG::Ux) {
+        self.register.set(value)
+    }
+}
+
+impl<REG: Readable + Writable> Reg<REG> {
+    /// Reads the register, passes the value to `f` and writes back the value it returns.
+    ///
+    /// This is not atomic, see [`RW::modify`].
+    #[inline(always)]
+    pub fn modify(&self, f: impl FnOnce(REG::Ux) -> REG::Ux) {
+        self.write(f(self.read()))
+    }
+}
+
+impl<REG: Resettable + Writable> Reg<REG> {
+    /// Writes the reset value to the register.
+    #[inline(always)]
+    pub fn reset(&self) {
+        self.write(REG::RESET_VALUE)
+    }
+}
+
+impl<REG: RegisterSpec> fmt::Debug for Reg<REG> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("Reg").finish_non_exhaustive()
+    }
+}
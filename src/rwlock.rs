@@ -0,0 +1,223 @@
+use core::cell::UnsafeCell;
+use core::fmt;
+use core::marker::PhantomData;
+use core::ops::{Deref, DerefMut};
+
+use critical_section::CriticalSection;
+
+use crate::atomic::{AtomicUsize, Ordering};
+
+const WRITER: usize = usize::MAX;
+
+/// A reader-writer lock based on critical sections and an atomic reader count.
+///
+/// Taking the lock requires a [`CriticalSection`] token, which serializes readers and writers
+/// that try to take it. A read guard only borrows the lock, not the critical section: it can be
+/// kept after the critical section ends, and releasing it just decrements the atomic reader
+/// count. Readers on several cores can therefore use the data at the same time, without holding
+/// the critical section. A write guard borrows the critical section token, so a writer always
+/// has the critical section, and releases the lock before leaving it.
+///
+/// Taking the lock fails instead of waiting when it is held in a conflicting way. A writer that
+/// finds readers on other cores can retry from a new critical section once they are done.
+///
+/// On targets without atomic read-modify-write operations, the reader count is a
+/// [`CsAtomicUsize`](crate::CsAtomicUsize), so releasing a read guard takes a short critical
+/// section there.
+///
+/// ```
+/// use bare_metal::RwLock;
+///
+/// static CONFIG: RwLock<[u8; 4]> = RwLock::new([0; 4]);
+///
+/// // The read guards outlive the critical sections they were taken in
+/// let a = critical_section::with(|cs| CONFIG.read(cs));
+/// let b = critical_section::with(|cs| CONFIG.read(cs));
+/// assert_eq!(a[0], b[0]);
+/// // No writer while there are readers
+/// critical_section::with(|cs| assert!(CONFIG.try_write(cs).is_none()));
+/// drop((a, b));
+///
+/// critical_section::with(|cs| CONFIG.write(cs)[0] = 1);
+/// assert_eq!(critical_section::with(|cs| CONFIG.read(cs))[0], 1);
+/// ```
+pub struct RwLock<T> {
+    // number of readers, or `WRITER` if the lock is held for writing
+    state: AtomicUsize,
+    value: UnsafeCell<T>,
+}
+
+impl<T> RwLock<T> {
+    const_fn! {
+        /// Creates a new, unlocked `RwLock`.
+        #[inline]
+        pub const fn new(value: T) -> Self {
+            RwLock {
+                state: AtomicUsize::new(0),
+                value: UnsafeCell::new(value),
+            }
+        }
+    }
+
+    /// Locks the data for reading, until the returned guard is dropped.
+    ///
+    /// # Panics
+    ///
+    /// Panics if the lock is held for writing.
+    #[inline]
+    #[track_caller]
+    pub fn read(&self, cs: CriticalSection<'_>) -> RwLockReadGuard<'_, T> {
+        match self.try_read(cs) {
+            Some(guard) => guard,
+            None => panic!("RwLock is locked for writing"),
+        }
+    }
+
+    /// Locks the data for reading, returning `None` if it is held for writing.
+    #[inline]
+    pub fn try_read(&self, _cs: CriticalSection<'_>) -> Option<RwLockReadGuard<'_, T>> {
+        // only readers that are done can change the count outside of the critical section, and
+        // they only decrease it
+        if self.state.load(Ordering::Relaxed) >= WRITER - 1 {
+            // locked for writing, or too many readers
+            None
+        } else {
+            self.state.fetch_add(1, Ordering::Acquire);
+            Some(RwLockReadGuard { lock: self })
+        }
+    }
+
+    /// Locks the data for writing.
+    ///
+    /// # Panics
+    ///
+    /// Panics if the lock is held by any reader or writer, including readers on other cores.
+    #[inline]
+    #[track_caller]
+    pub fn write<'cs>(&'cs self, cs: CriticalSection<'cs>) -> RwLockWriteGuard<'cs, T> {
+        match self.try_write(cs) {
+            Some(guard) => guard,
+            None => panic!("RwLock is already locked"),
+        }
+    }
+
+    /// Locks the data for writing, returning `None` if it is held by any reader or writer.
+    #[inline]
+    pub fn try_write<'cs>(
+        &'cs self,
+        _cs: CriticalSection<'cs>,
+    ) -> Option<RwLockWriteGuard<'cs, T>> {
+        if self
+            .state
+            .compare_exchange(0, WRITER, Ordering::Acquire, Ordering::Relaxed)
+            .is_ok()
+        {
+            Some(RwLockWriteGuard {
+                lock: self,
+                _not_send: PhantomData,
+            })
+        } else {
+            None
+        }
+    }
+
+    /// Returns a mutable reference to the data.
+    ///
+    /// This does not require a critical section since `&mut self` already guarantees exclusive
+    /// access.
+    #[inline]
+    pub fn get_mut(&mut self) -> &mut T {
+        self.value.get_mut()
+    }
+
+    /// Consumes the `RwLock`, returning the data.
+    #[inline]
+    pub fn into_inner(self) -> T {
+        self.value.into_inner()
+    }
+}
+
+impl<T: Default> Default for RwLock<T> {
+    fn default() -> Self {
+        RwLock::new(T::default())
+    }
+}
+
+impl<T> fmt::Debug for RwLock<T> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("RwLock").finish_non_exhaustive()
+    }
+}
+
+// NOTE readers on different contexts share `&T`, hence `Sync`; a writer may hand the data over
+// to another context, hence `Send`. The reader count synchronizes the readers with the writers.
+unsafe impl<T> Sync for RwLock<T> where T: Send + Sync {}
+
+/// Shared access to the data in a [`RwLock`].
+///
+/// The guard may outlive the critical section it was taken in. The lock is released when it is
+/// dropped.
+pub struct RwLockReadGuard<'a, T> {
+    lock: &'a RwLock<T>,
+}
+
+impl<T> Deref for RwLockReadGuard<'_, T> {
+    type Target = T;
+
+    #[inline]
+    fn deref(&self) -> &T {
+        unsafe { &*self.lock.value.get() }
+    }
+}
+
+impl<T> Drop for RwLockReadGuard<'_, T> {
+    #[inline]
+    fn drop(&mut self) {
+        self.lock.state.fetch_sub(1, Ordering::Release);
+    }
+}
+
+impl<T: fmt::Debug> fmt::Debug for RwLockReadGuard<'_, T> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        (**self).fmt(f)
+    }
+}
+
+/// Exclusive access to the data in a [`RwLock`].
+///
+/// The guard borrows the critical section token, so the lock is released before the critical
+/// section ends.
+pub struct RwLockWriteGuard<'cs, T> {
+    lock: &'cs RwLock<T>,
+    // the guard must be dropped in the context that holds the critical section
+    _not_send: PhantomData<*mut ()>,
+}
+
+impl<T> Deref for RwLockWriteGuard<'_, T> {
+    type Target = T;
+
+    #[inline]
+    fn deref(&self) -> &T {
+        unsafe { &*self.lock.value.get() }
+    }
+}
+
+impl<T> DerefMut for RwLockWriteGuard<'_, T> {
+    #[inline]
+    fn deref_mut(&mut self) -> &mut T {
+        unsafe { &mut *self.lock.value.get() }
+    }
+}
+
+impl<T> Drop for RwLockWriteGuard<'_, T> {
+    #[inline]
+    fn drop(&mut self) {
+        self.lock.state.store(0, Ordering::Release);
+    }
+}
+
+impl<T: fmt::Debug> fmt::Debug for RwLockWriteGuard<'_, T> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        (**self).fmt(f)
+    }
+}
@@ -0,0 +1,88 @@
+use critical_section::CriticalSection;
+
+use crate::CsCell;
+
+/// A counting semaphore with up to `MAX` permits.
+///
+/// Permits are acquired and released inside critical sections, so both can be done from
+/// interrupt handlers, e.g. to hand out DMA descriptors or buffer slots.
+///
+/// ```
+/// use bare_metal::Semaphore;
+/// use critical_section::CriticalSection;
+///
+/// static SLOTS: Semaphore<2> = Semaphore::new(2);
+///
+/// let cs = unsafe { CriticalSection::new() };
+/// assert!(SLOTS.try_acquire(cs));
+/// assert!(SLOTS.try_acquire(cs));
+/// assert!(!SLOTS.try_acquire(cs));
+///
+/// assert!(SLOTS.release(cs));
+/// assert_eq!(SLOTS.available(cs), 1);
+/// ```
+#[derive(Debug)]
+pub struct Semaphore<const MAX: usize> {
+    permits: CsCell<usize>,
+}
+
+impl<const MAX: usize> Semaphore<MAX> {
+    /// Creates a new semaphore with `permits` available permits.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `permits` is greater than `MAX`.
+    #[inline]
+    pub const fn new(permits: usize) -> Self {
+        assert!(permits <= MAX, "more permits than the maximum");
+        Semaphore {
+            permits: CsCell::new(permits),
+        }
+    }
+
+    /// Acquires a permit, returning `false` if none is available.
+    #[inline]
+    pub fn try_acquire(&self, cs: CriticalSection<'_>) -> bool {
+        self.try_acquire_many(cs, 1)
+    }
+
+    /// Acquires `n` permits at once, returning `false` if fewer are available.
+    ///
+    /// Either all `n` permits are acquired or none.
+    #[inline]
+    pub fn try_acquire_many(&self, cs: CriticalSection<'_>, n: usize) -> bool {
+        match self.permits.get(cs).checked_sub(n) {
+            Some(permits) => {
+                self.permits.set(cs, permits);
+                true
+            }
+            None => false,
+        }
+    }
+
+    /// Releases a permit, returning `false` if all `MAX` permits are already available.
+    #[inline]
+    pub fn release(&self, cs: CriticalSection<'_>) -> bool {
+        self.release_many(cs, 1)
+    }
+
+    /// Releases `n` permits at once, returning `false` if that would exceed `MAX` permits.
+    ///
+    /// Either all `n` permits are released or none.
+    #[inline]
+    pub fn release_many(&self, cs: CriticalSection<'_>, n: usize) -> bool {
+        match self.permits.get(cs).checked_add(n) {
+            Some(permits) if permits <= MAX => {
+                self.permits.set(cs, permits);
+                true
+            }
+            _ => false,
+        }
+    }
+
+    /// Returns the number of available permits.
+    #[inline]
+    pub fn available(&self, cs: CriticalSection<'_>) -> usize {
+        self.permits.get(cs)
+    }
+}
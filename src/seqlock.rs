@@ -0,0 +1,135 @@
+use core::cell::UnsafeCell;
+use core::fmt;
+use core::mem::MaybeUninit;
+
+use critical_section::CriticalSection;
+
+use crate::atomic::native::{fence, spin_loop, AtomicUsize};
+use crate::atomic::Ordering;
+
+/// A sequence lock for small `Copy` data that is read much more often than it is written.
+///
+/// Readers never block the writer and don't need a critical section: [`read`](SeqLock::read)
+/// copies the data and retries if a write happened in the meantime. Writes take a
+/// [`CriticalSection`] token, which serializes the writers and ensures that a reader on the same
+/// core never preempts, and then waits for, a write in progress.
+///
+/// This is a good fit for e.g. a timestamp and counter pair updated by a high-rate interrupt.
+/// The lock only uses atomic loads and stores, so it also works on targets without atomic
+/// compare-and-swap operations.
+///
+/// ```
+/// use bare_metal::SeqLock;
+/// use critical_section::CriticalSection;
+///
+/// static TIME: SeqLock<(u32, u32)> = SeqLock::new((0, 0));
+///
+/// // In the interrupt handler
+/// let cs = unsafe { CriticalSection::new() };
+/// TIME.write(cs, (1, 1000));
+///
+/// // Anywhere else
+/// assert_eq!(TIME.read(), (1, 1000));
+/// ```
+pub struct SeqLock<T> {
+    // odd while a write is in progress
+    seq: AtomicUsize,
+    value: UnsafeCell<T>,
+}
+
+impl<T: Copy> SeqLock<T> {
+    const_fn! {
+        /// Creates a new `SeqLock`.
+        #[inline]
+        pub const fn new(value: T) -> Self {
+            SeqLock {
+                seq: AtomicUsize::new(0),
+                value: UnsafeCell::new(value),
+            }
+        }
+    }
+
+    /// Returns a copy of the data, retrying until it wasn't modified while being copied.
+    #[inline]
+    pub fn read(&self) -> T {
+        loop {
+            if let Some(value) = self.try_read() {
+                return value;
+            }
+            spin_loop();
+        }
+    }
+
+    /// Returns a copy of the data, or `None` if it was modified while being copied.
+    ///
+    /// A copy that raced with a write is never turned into a `T`, so this is sound for types
+    /// with invalid bit patterns too.
+    ///
+    /// ```
+    /// use bare_metal::SeqLock;
+    /// use core::num::NonZeroU32;
+    /// use critical_section::CriticalSection;
+    ///
+    /// static DEADLINE: SeqLock<Option<NonZeroU32>> = SeqLock::new(None);
+    ///
+    /// assert_eq!(DEADLINE.try_read(), Some(None));
+    /// let cs = unsafe { CriticalSection::new() };
+    /// DEADLINE.write(cs, NonZeroU32::new(1000));
+    /// assert_eq!(DEADLINE.try_read(), Some(NonZeroU32::new(1000)));
+    /// ```
+    #[inline]
+    pub fn try_read(&self) -> Option<T> {
+        let before = self.seq.load(Ordering::Acquire);
+        if before % 2 == 1 {
+            return None;
+        }
+        // NOTE this may race with a write, so the copy may be torn and is kept as `MaybeUninit`
+        // until the sequence number shows that no write happened. The volatile read keeps the
+        // compiler from making assumptions about the racing value.
+        let value = unsafe { self.value.get().cast::<MaybeUninit<T>>().read_volatile() };
+        fence(Ordering::Acquire);
+        if self.seq.load(Ordering::Relaxed) == before {
+            // SAFETY: no write overlapped the copy, so it holds the value of the last write
+            Some(unsafe { value.assume_init() })
+        } else {
+            None
+        }
+    }
+
+    /// Replaces the data.
+    #[inline]
+    pub fn write(&self, _cs: CriticalSection<'_>, value: T) {
+        let seq = self.seq.load(Ordering::Relaxed);
+        self.seq.store(seq.wrapping_add(1), Ordering::Relaxed);
+        fence(Ordering::Release);
+        // SAFETY: writers are serialized by the critical section
+        unsafe { self.value.get().write_volatile(value) };
+        self.seq.store(seq.wrapping_add(2), Ordering::Release);
+    }
+
+    /// Returns a mutable reference to the data.
+    ///
+    /// This does not need to take the lock since `&mut self` already guarantees exclusive
+    /// access.
+    #[inline]
+    pub fn get_mut(&mut self) -> &mut T {
+        self.value.get_mut()
+    }
+}
+
+impl<T: Copy + Default> Default for SeqLock<T> {
+    fn default() -> Self {
+        SeqLock::new(T::default())
+    }
+}
+
+impl<T: Copy + fmt::Debug> fmt::Debug for SeqLock<T> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("SeqLock")
+            .field("value", &self.read())
+            .finish()
+    }
+}
+
+// NOTE readers only ever obtain copies of the data, which may be created in any context
+unsafe impl<T> Sync for SeqLock<T> where T: Copy + Send {}
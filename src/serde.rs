@@ -0,0 +1,87 @@
+//! Serde support for data protected by a [`Mutex`].
+//!
+//! [`Mutex`] is defined in the `critical-section` crate, so it can't implement `Serialize` and
+//! `Deserialize` here. Instead, the modules below are meant to be used with serde's `with` field
+//! attribute. They (de)serialize the protected value as if the `Mutex` wasn't there. For example,
+//! a field of a `#[derive(Serialize, Deserialize)]` struct is annotated like this:
+//!
+//! ``` text
+//! #[serde(with = "bare_metal::serde::cell")]
+//! brightness: Mutex<Cell<u8>>,
+//! ```
+//!
+//! Serializing enters a critical section to read the protected value.
+//!
+//! ```
+//! use core::cell::Cell;
+//! use critical_section::{CriticalSection, Mutex};
+//! use serde::de::{value::Error, IntoDeserializer};
+//!
+//! let brightness: Mutex<Cell<u8>> =
+//!     bare_metal::serde::cell::deserialize(IntoDeserializer::<Error>::into_deserializer(3u8))
+//!         .unwrap();
+//! let cs = unsafe { CriticalSection::new() };
+//! assert_eq!(brightness.borrow(cs).get(), 3);
+//! ```
+//!
+//! This module is only available when the `serde` feature is enabled.
+//!
+//! [`Mutex`]: critical_section::Mutex
+
+/// (De)serialize a `Mutex<Cell<T>>` as `T`.
+pub mod cell {
+    use core::cell::Cell;
+
+    use ::serde::{Deserialize, Deserializer, Serialize, Serializer};
+    use critical_section::Mutex;
+
+    /// Serialize a copy of the protected value.
+    pub fn serialize<T, S>(mutex: &Mutex<Cell<T>>, serializer: S) -> Result<S::Ok, S::Error>
+    where
+        T: Copy + Serialize,
+        S: Serializer,
+    {
+        crate::with_cs(|cs| mutex.borrow(cs).get()).serialize(serializer)
+    }
+
+    /// Deserialize a `T` and wrap it in a new `Mutex<Cell<T>>`.
+    pub fn deserialize<'de, T, D>(deserializer: D) -> Result<Mutex<Cell<T>>, D::Error>
+    where
+        T: Deserialize<'de>,
+        D: Deserializer<'de>,
+    {
+        T::deserialize(deserializer).map(|value| Mutex::new(Cell::new(value)))
+    }
+}
+
+/// (De)serialize a `Mutex<RefCell<T>>` as `T`.
+pub mod ref_cell {
+    use core::cell::RefCell;
+
+    use ::serde::{Deserialize, Deserializer, Serialize, Serializer};
+    use critical_section::Mutex;
+
+    /// Serialize the protected value.
+    ///
+    /// The whole serialization runs inside the critical section.
+    ///
+    /// # Panics
+    ///
+    /// Panics if the value is currently mutably borrowed.
+    pub fn serialize<T, S>(mutex: &Mutex<RefCell<T>>, serializer: S) -> Result<S::Ok, S::Error>
+    where
+        T: Serialize,
+        S: Serializer,
+    {
+        crate::with_cs(|cs| mutex.borrow(cs).borrow().serialize(serializer))
+    }
+
+    /// Deserialize a `T` and wrap it in a new `Mutex<RefCell<T>>`.
+    pub fn deserialize<'de, T, D>(deserializer: D) -> Result<Mutex<RefCell<T>>, D::Error>
+    where
+        T: Deserialize<'de>,
+        D: Deserializer<'de>,
+    {
+        T::deserialize(deserializer).map(|value| Mutex::new(RefCell::new(value)))
+    }
+}
@@ -0,0 +1,64 @@
+use crate::atomic::{AtomicBool, Ordering};
+
+/// A flag for notifying the main loop from an interrupt handler.
+///
+/// The interrupt handler calls [`signal`](Signal::signal), and the main loop polls
+/// [`check_and_clear`](Signal::check_and_clear) to find out whether that happened since it last
+/// checked.
+///
+/// On targets without atomic compare-and-swap operations, such as `thumbv6m`, the flag is only
+/// accessed inside short critical sections, unless the `portable-atomic` feature is enabled.
+///
+/// ```
+/// use bare_metal::Signal;
+///
+/// static DATA_READY: Signal = Signal::new();
+///
+/// // In the interrupt handler
+/// DATA_READY.signal();
+///
+/// // In the main loop
+/// assert!(DATA_READY.is_signaled());
+/// assert!(DATA_READY.check_and_clear());
+/// assert!(!DATA_READY.check_and_clear());
+/// ```
+#[derive(Debug, Default)]
+pub struct Signal {
+    flag: AtomicBool,
+}
+
+impl Signal {
+    const_fn! {
+        /// Creates a new `Signal` that is not signaled.
+        #[inline]
+        pub const fn new() -> Self {
+            Signal {
+                flag: AtomicBool::new(false),
+            }
+        }
+    }
+
+    /// Sets the flag.
+    #[inline]
+    pub fn signal(&self) {
+        self.flag.store(true, Ordering::Release);
+    }
+
+    /// Returns `true` if the flag is set, without clearing it.
+    #[inline]
+    pub fn is_signaled(&self) -> bool {
+        self.flag.load(Ordering::Acquire)
+    }
+
+    /// Clears the flag and returns whether it was set.
+    #[inline]
+    pub fn check_and_clear(&self) -> bool {
+        self.flag.swap(false, Ordering::AcqRel)
+    }
+
+    /// Clears the flag.
+    #[inline]
+    pub fn clear(&self) {
+        self.flag.store(false, Ordering::Release);
+    }
+}
@@ -0,0 +1,50 @@
+//! Safe access to critical section tokens in programs with a single execution context.
+//!
+//! Enabling the `single-context` feature is a promise made by the final application: the program
+//! has exactly one execution context. There are no interrupt handlers, signal handlers, threads or
+//! other cores that could observe the data protected by a critical section. Host-side simulators
+//! and simple bootloaders that run with interrupts disabled are typical examples.
+//!
+//! Under that promise, a [`CriticalSection`] token doesn't need to exclude anything, so the
+//! functions in this module hand one out without `unsafe` code. This lets such programs use
+//! `#![forbid(unsafe_code)]`.
+//!
+//! ```
+//! use core::cell::Cell;
+//! use critical_section::Mutex;
+//!
+//! static COUNTER: Mutex<Cell<u32>> = Mutex::new(Cell::new(0));
+//!
+//! let cs = bare_metal::single_context::critical_section();
+//! COUNTER.borrow(cs).set(1);
+//! ```
+//!
+//! # Soundness
+//!
+//! Libraries must never enable this feature. Only the application knows whether it has a single
+//! execution context, and since Cargo features are additive, a library enabling it would make
+//! the promise on behalf of every program that depends on it.
+//!
+//! This module is only available when the `single-context` feature is enabled.
+
+use critical_section::CriticalSection;
+
+use crate::lock::SingleContextPolicy;
+
+/// Returns a critical section token.
+///
+/// See the [module-level documentation](self) for when this is sound.
+#[inline]
+pub fn critical_section() -> CriticalSection<'static> {
+    // SAFETY: the application promised to have a single execution context
+    unsafe { CriticalSection::new() }
+}
+
+/// Returns a [`SingleContextPolicy`] for a [`lock::Mutex`](crate::lock::Mutex).
+///
+/// See the [module-level documentation](self) for when this is sound.
+#[inline]
+pub const fn policy() -> SingleContextPolicy {
+    // SAFETY: the application promised to have a single execution context
+    unsafe { SingleContextPolicy::new() }
+}
@@ -0,0 +1,302 @@
+use core::cell::UnsafeCell;
+use core::fmt;
+use core::marker::PhantomData;
+use core::mem::{ManuallyDrop, MaybeUninit};
+
+use crate::CsCell;
+
+/// A value that can be taken out only once, like the peripherals of a device.
+///
+/// This is the pattern behind `Peripherals::take()` in device crates: the singleton hands out
+/// its value the first time [`take`](Singleton::take) is called and `None` afterwards, so owning
+/// the value proves exclusive access to whatever it represents. [`steal`](Singleton::steal)
+/// bypasses the check.
+///
+/// ```
+/// use bare_metal::Singleton;
+///
+/// pub struct Gpioa {
+///     _private: (),
+/// }
+///
+/// static GPIOA: Singleton<Gpioa> = Singleton::new(Gpioa { _private: () });
+///
+/// let gpioa = GPIOA.take().unwrap();
+/// assert!(GPIOA.take().is_none());
+/// # drop(gpioa);
+/// ```
+pub struct Singleton<T> {
+    taken: CsCell<bool>,
+    value: ManuallyDrop<T>,
+}
+
+impl<T> Singleton<T> {
+    /// Creates a singleton whose value hasn't been taken yet.
+    #[inline]
+    pub const fn new(value: T) -> Self {
+        Singleton {
+            taken: CsCell::new(false),
+            value: ManuallyDrop::new(value),
+        }
+    }
+
+    /// Takes the value, or returns `None` if it has already been taken.
+    ///
+    /// The check is done inside a critical section.
+    #[inline]
+    pub fn take(&self) -> Option<T> {
+        if crate::with_cs(|cs| self.taken.replace(cs, true)) {
+            None
+        } else {
+            // SAFETY: the flag ensures that the value is only moved out once
+            Some(unsafe { self.read() })
+        }
+    }
+
+    /// Takes the value together with a [`Taken`] token proving that it was taken legitimately,
+    /// or returns `None` if it has already been taken.
+    #[inline]
+    pub fn take_with_proof(&self) -> Option<(T, Taken<T>)> {
+        self.take().map(|value| {
+            (
+                value,
+                Taken {
+                    _singleton: PhantomData,
+                },
+            )
+        })
+    }
+
+    /// Takes the value regardless of whether it has already been taken.
+    ///
+    /// # Safety
+    ///
+    /// This creates a bitwise copy of the value, which may duplicate one that was taken
+    /// before. The caller must ensure that this doesn't break any invariant of `T`, e.g. by
+    /// not using both copies at the same time.
+    #[inline]
+    pub unsafe fn steal(&self) -> T {
+        crate::with_cs(|cs| self.taken.set(cs, true));
+        self.read()
+    }
+
+    /// Returns `true` if the value has been taken or stolen.
+    #[inline]
+    pub fn is_taken(&self) -> bool {
+        crate::with_cs(|cs| self.taken.get(cs))
+    }
+
+    unsafe fn read(&self) -> T {
+        core::ptr::addr_of!(self.value).cast::<T>().read()
+    }
+}
+
+impl<T> fmt::Debug for Singleton<T> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("Singleton").finish_non_exhaustive()
+    }
+}
+
+impl<T> Drop for Singleton<T> {
+    fn drop(&mut self) {
+        if !*self.taken.get_mut() {
+            // SAFETY: the value was never moved out
+            unsafe { ManuallyDrop::drop(&mut self.value) }
+        }
+    }
+}
+
+// NOTE the value can be moved to whichever context takes it, and is never accessed through a
+// shared reference
+unsafe impl<T> Sync for Singleton<T> where T: Send {}
+
+/// Proof that the value of a [`Singleton<T>`] was taken with
+/// [`take_with_proof`](Singleton::take_with_proof) rather than stolen.
+///
+/// Constructors can require this token by value to express that a driver may only be built
+/// from the legitimately taken peripheral. The token can't be copied, so each successful take
+/// allows building one driver. [`new_unchecked`](Taken::new_unchecked) is the explicit bypass.
+///
+/// ```
+/// use bare_metal::{Singleton, Taken};
+///
+/// pub struct Tim2 {
+///     _private: (),
+/// }
+///
+/// pub struct Timer {
+///     _tim2: Tim2,
+/// }
+///
+/// impl Timer {
+///     pub fn new(tim2: Tim2, _proof: Taken<Tim2>) -> Self {
+///         Timer { _tim2: tim2 }
+///     }
+/// }
+///
+/// static TIM2: Singleton<Tim2> = Singleton::new(Tim2 { _private: () });
+///
+/// let (tim2, proof) = TIM2.take_with_proof().unwrap();
+/// let _timer = Timer::new(tim2, proof);
+/// ```
+pub struct Taken<T> {
+    _singleton: PhantomData<fn() -> T>,
+}
+
+impl<T> Taken<T> {
+    /// Creates a token without taking the singleton.
+    ///
+    /// # Safety
+    ///
+    /// Code that requires the token may rely on it having been obtained from
+    /// [`take_with_proof`](Singleton::take_with_proof), e.g. on there being no other token for
+    /// the same singleton. The caller must uphold whatever that code relies on.
+    #[inline]
+    pub const unsafe fn new_unchecked() -> Self {
+        Taken {
+            _singleton: PhantomData,
+        }
+    }
+}
+
+impl<T> fmt::Debug for Taken<T> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str("Taken")
+    }
+}
+
+/// Allocates a `static` of type `$ty` and returns a `&'static mut` reference to it, the first
+/// time it is executed. Returns `None` on every later execution.
+///
+/// This is an architecture-neutral version of `cortex_m::singleton!`, built on [`InitStatic`].
+/// The check is done inside a critical section, and `$expr` is only evaluated when it passes.
+/// The static can be given a name, which then shows up in the symbol table.
+///
+/// ```
+/// use bare_metal::singleton;
+///
+/// fn dma_buffer() -> Option<&'static mut [u8; 64]> {
+///     singleton!(DMA_BUFFER: [u8; 64] = [0; 64])
+/// }
+///
+/// let buffer: &'static mut [u8; 64] = dma_buffer().unwrap();
+/// buffer[0] = 1;
+/// assert!(dma_buffer().is_none());
+///
+/// let counter = singleton!(: u32 = 0).unwrap();
+/// *counter += 1;
+/// ```
+#[macro_export]
+macro_rules! singleton {
+    ($name:ident: $ty:ty = $expr:expr) => {{
+        static $name: $crate::InitStatic<$ty> = $crate::InitStatic::new();
+        $name.try_init_with(|| $expr)
+    }};
+    (: $ty:ty = $expr:expr) => {
+        $crate::singleton!(VAR: $ty = $expr)
+    };
+}
+
+/// A `static` that is initialized at run time, handing out a `&'static mut` reference to its
+/// value.
+///
+/// This lets `main` move a resource it has constructed, e.g. a driver, into a `static` exactly
+/// once. The returned `&'static mut T` can then be handed to an interrupt handler or a task,
+/// without wrapping the resource in an `Option` that has to be unwrapped on every use. The
+/// check whether the static has already been initialized is done inside a critical section.
+///
+/// ```
+/// use bare_metal::InitStatic;
+///
+/// struct Uart {
+///     sent: usize,
+/// }
+///
+/// static UART: InitStatic<Uart> = InitStatic::new();
+///
+/// let uart: &'static mut Uart = UART.init(Uart { sent: 0 });
+/// uart.sent += 1;
+///
+/// assert!(UART.try_init(Uart { sent: 0 }).is_err());
+/// ```
+pub struct InitStatic<T> {
+    initialized: CsCell<bool>,
+    value: UnsafeCell<MaybeUninit<T>>,
+}
+
+impl<T> InitStatic<T> {
+    /// Creates a new, uninitialized static.
+    #[inline]
+    pub const fn new() -> Self {
+        InitStatic {
+            initialized: CsCell::new(false),
+            value: UnsafeCell::new(MaybeUninit::uninit()),
+        }
+    }
+
+    /// Initializes the static with `value` and returns a reference to it.
+    ///
+    /// # Panics
+    ///
+    /// Panics if the static is already initialized.
+    #[inline]
+    #[track_caller]
+    pub fn init(&'static self, value: T) -> &'static mut T {
+        match self.try_init(value) {
+            Ok(value) => value,
+            Err(_) => already_initialized(),
+        }
+    }
+
+    /// Initializes the static with `value` and returns a reference to it, or gives `value` back
+    /// if the static is already initialized.
+    #[inline]
+    pub fn try_init(&'static self, value: T) -> Result<&'static mut T, T> {
+        let mut value = Some(value);
+        match self.try_init_with(|| value.take().unwrap()) {
+            Some(value) => Ok(value),
+            None => Err(value.take().unwrap()),
+        }
+    }
+
+    /// Initializes the static with the value returned by `f` and returns a reference to it, or
+    /// returns `None` without calling `f` if the static is already initialized.
+    #[inline]
+    #[allow(clippy::mut_from_ref)]
+    pub fn try_init_with(&'static self, f: impl FnOnce() -> T) -> Option<&'static mut T> {
+        if crate::with_cs(|cs| self.initialized.replace(cs, true)) {
+            return None;
+        }
+        // SAFETY: the flag ensures that only one caller gets past this point, so this is the
+        // only reference to the value
+        Some(unsafe { (*self.value.get()).write(f()) })
+    }
+
+    /// Returns `true` if the static has been initialized.
+    #[inline]
+    pub fn is_initialized(&self) -> bool {
+        crate::with_cs(|cs| self.initialized.get(cs))
+    }
+}
+
+#[cold]
+#[track_caller]
+fn already_initialized() -> ! {
+    panic!("InitStatic already initialized")
+}
+
+impl<T> Default for InitStatic<T> {
+    fn default() -> Self {
+        InitStatic::new()
+    }
+}
+
+impl<T> fmt::Debug for InitStatic<T> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("InitStatic").finish_non_exhaustive()
+    }
+}
+
+// NOTE the value is only referenced by the context that initialized it, and whomever it hands
+// the reference to
+unsafe impl<T> Sync for InitStatic<T> where T: Send {}
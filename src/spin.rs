@@ -0,0 +1,169 @@
+use core::fmt;
+use core::marker::PhantomData;
+use core::ops::{Deref, DerefMut};
+
+use crate::atomic::native::{spin_loop, AtomicBool};
+use crate::atomic::{Ordering, UnsafeCell};
+
+/// A spin lock for data shared between cores.
+///
+/// Disabling interrupts only protects data from code running on the same core. A `SpinLock`
+/// instead protects data from code running on other cores by busy-waiting until the lock is
+/// free.
+///
+/// A `SpinLock` does not mask interrupts. If the data is also accessed from an interrupt
+/// handler on the same core, the lock must only be taken inside a critical section, or the
+/// handler may spin forever on a lock held by the code it interrupted.
+///
+/// This type is only available on targets that support atomic compare-and-swap operations.
+///
+/// ```
+/// use bare_metal::SpinLock;
+///
+/// static SHARED: SpinLock<u32> = SpinLock::new(0);
+///
+/// *SHARED.lock() += 1;
+/// let guard = SHARED.lock();
+/// assert!(SHARED.try_lock().is_none());
+/// assert_eq!(*guard, 1);
+/// ```
+pub struct SpinLock<T> {
+    locked: AtomicBool,
+    value: UnsafeCell<T>,
+}
+
+impl<T> SpinLock<T> {
+    const_fn! {
+        /// Creates a new, unlocked `SpinLock`.
+        #[inline]
+        pub const fn new(value: T) -> Self {
+            SpinLock {
+                locked: AtomicBool::new(false),
+                value: UnsafeCell::new(value),
+            }
+        }
+    }
+
+    /// Acquires the lock, spinning until it is available.
+    #[inline]
+    #[track_caller]
+    pub fn lock(&self) -> SpinLockGuard<'_, T> {
+        loop {
+            if let Some(guard) = self.try_lock() {
+                return guard;
+            }
+            #[cfg(feature = "deadlock-detection")]
+            crate::deadlock::wait_spin(self.id());
+            // wait until the lock looks free before trying to take it again; this keeps the
+            // cache line in the shared state while spinning
+            while self.locked.load(Ordering::Relaxed) {
+                spin_loop();
+            }
+        }
+    }
+
+    /// Attempts to acquire the lock without spinning, returning `None` if it is held.
+    #[inline]
+    pub fn try_lock(&self) -> Option<SpinLockGuard<'_, T>> {
+        if self
+            .locked
+            .compare_exchange(false, true, Ordering::Acquire, Ordering::Relaxed)
+            .is_ok()
+        {
+            #[cfg(feature = "deadlock-detection")]
+            crate::deadlock::acquired_spin(self.id());
+            Some(SpinLockGuard {
+                lock: self,
+                _marker: PhantomData,
+            })
+        } else {
+            None
+        }
+    }
+
+    #[cfg(feature = "deadlock-detection")]
+    fn id(&self) -> *const () {
+        self as *const Self as *const ()
+    }
+
+    /// Returns `true` if the lock is currently held.
+    ///
+    /// The result may already be outdated when this method returns.
+    #[inline]
+    pub fn is_locked(&self) -> bool {
+        self.locked.load(Ordering::Relaxed)
+    }
+
+    /// Returns a mutable reference to the data.
+    ///
+    /// This does not need to take the lock since `&mut self` already guarantees exclusive
+    /// access.
+    #[inline]
+    pub fn get_mut(&mut self) -> &mut T {
+        // SAFETY: `&mut self` guarantees exclusive access
+        self.value.with_mut(|value| unsafe { &mut *value })
+    }
+
+    /// Consumes the `SpinLock`, returning the data.
+    #[inline]
+    pub fn into_inner(self) -> T {
+        self.value.into_inner()
+    }
+}
+
+impl<T: Default> Default for SpinLock<T> {
+    fn default() -> Self {
+        SpinLock::new(T::default())
+    }
+}
+
+impl<T> fmt::Debug for SpinLock<T> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("SpinLock")
+            .field("locked", &self.is_locked())
+            .finish_non_exhaustive()
+    }
+}
+
+// NOTE the lock hands out `&mut T` to one core at a time, so the data only needs to be `Send`
+unsafe impl<T> Sync for SpinLock<T> where T: Send {}
+
+/// Exclusive access to the data in a [`SpinLock`].
+///
+/// The lock is released when this guard is dropped.
+pub struct SpinLockGuard<'a, T> {
+    lock: &'a SpinLock<T>,
+    // the guard behaves like a `&mut T` with respect to `Send` and `Sync`
+    _marker: PhantomData<&'a mut T>,
+}
+
+impl<T> Deref for SpinLockGuard<'_, T> {
+    type Target = T;
+
+    #[inline]
+    fn deref(&self) -> &T {
+        self.lock.value.with(|value| unsafe { &*value })
+    }
+}
+
+impl<T> DerefMut for SpinLockGuard<'_, T> {
+    #[inline]
+    fn deref_mut(&mut self) -> &mut T {
+        self.lock.value.with_mut(|value| unsafe { &mut *value })
+    }
+}
+
+impl<T> Drop for SpinLockGuard<'_, T> {
+    #[inline]
+    fn drop(&mut self) {
+        #[cfg(feature = "deadlock-detection")]
+        crate::deadlock::released_spin(self.lock.id());
+        self.lock.locked.store(false, Ordering::Release);
+    }
+}
+
+impl<T: fmt::Debug> fmt::Debug for SpinLockGuard<'_, T> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        (**self).fmt(f)
+    }
+}
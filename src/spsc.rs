@@ -0,0 +1,350 @@
+//! A lock-free single-producer single-consumer queue.
+//!
+//! The producer and the consumer can run in different contexts, e.g. an interrupt handler and
+//! the main loop, without any critical section. Enqueueing and dequeueing only use atomic loads
+//! and stores, so they also work on targets without atomic compare-and-swap operations.
+//!
+//! ```
+//! use bare_metal::spsc::Queue;
+//!
+//! let mut queue: Queue<u8, 4> = Queue::new();
+//! let (mut producer, mut consumer) = queue.split();
+//!
+//! // In the interrupt handler
+//! producer.enqueue(1).unwrap();
+//! producer.enqueue(2).unwrap();
+//!
+//! // In the main loop
+//! assert_eq!(consumer.dequeue(), Some(1));
+//! assert_eq!(consumer.dequeue(), Some(2));
+//! assert_eq!(consumer.dequeue(), None);
+//! ```
+//!
+//! An async task can also wait for elements with [`Consumer::recv`], which the producer wakes
+//! when it enqueues one. The task is registered and woken through a [`WakerSlot`], so on targets
+//! without compare-and-swap, waiting in `recv` and enqueueing while it waits take a short critical
+//! section, unless the `portable-atomic` feature is enabled.
+
+use core::fmt;
+use core::future::Future;
+use core::marker::PhantomData;
+use core::mem::MaybeUninit;
+use core::pin::Pin;
+use core::task::{Context, Poll};
+
+use crate::atomic::native::{fence, AtomicBool, AtomicUsize};
+use crate::atomic::{Ordering, UnsafeCell};
+use crate::WakerSlot;
+
+/// A lock-free single-producer single-consumer queue that can hold `N - 1` elements.
+///
+/// One slot of the buffer is always left empty to tell a full queue from an empty one.
+pub struct Queue<T, const N: usize> {
+    // index of the next element to dequeue; only written by the consumer
+    head: AtomicUsize,
+    // index of the next free slot; only written by the producer
+    tail: AtomicUsize,
+    slots: [UnsafeCell<MaybeUninit<T>>; N],
+    // set while the consumer waits in `recv`; only written by the consumer
+    waiting: AtomicBool,
+    waker: WakerSlot,
+}
+
+impl<T, const N: usize> Queue<T, N> {
+    const_fn! {
+        /// Creates an empty queue.
+        ///
+        /// # Panics
+        ///
+        /// Panics if `N` is 0.
+        #[inline]
+        pub const fn new() -> Self {
+            assert!(N > 0, "the queue needs at least one slot");
+            Queue {
+                head: AtomicUsize::new(0),
+                tail: AtomicUsize::new(0),
+                slots: crate::atomic::uninit_cells(),
+                waiting: AtomicBool::new(false),
+                waker: WakerSlot::new(),
+            }
+        }
+    }
+
+    /// Returns the maximum number of elements the queue can hold.
+    #[inline]
+    pub const fn capacity(&self) -> usize {
+        N - 1
+    }
+
+    /// Returns the number of elements in the queue.
+    #[inline]
+    pub fn len(&self) -> usize {
+        let head = self.head.load(Ordering::Relaxed);
+        let tail = self.tail.load(Ordering::Relaxed);
+        if tail >= head {
+            tail - head
+        } else {
+            N - head + tail
+        }
+    }
+
+    /// Returns `true` if the queue is empty.
+    #[inline]
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    /// Returns `true` if the queue is full.
+    #[inline]
+    pub fn is_full(&self) -> bool {
+        self.len() == self.capacity()
+    }
+
+    /// Adds an element to the back of the queue, returning it back if the queue is full.
+    #[inline]
+    pub fn enqueue(&mut self, val: T) -> Result<(), T> {
+        unsafe { self.inner_enqueue(val) }
+    }
+
+    /// Removes the element at the front of the queue, returning `None` if it is empty.
+    #[inline]
+    pub fn dequeue(&mut self) -> Option<T> {
+        unsafe { self.inner_dequeue() }
+    }
+
+    /// Splits the queue into its producer and consumer halves.
+    #[inline]
+    pub fn split(&mut self) -> (Producer<'_, T, N>, Consumer<'_, T, N>) {
+        (
+            Producer {
+                queue: self,
+                _not_sync: PhantomData,
+            },
+            Consumer {
+                queue: self,
+                _not_sync: PhantomData,
+            },
+        )
+    }
+
+    #[inline]
+    const fn increment(index: usize) -> usize {
+        if index + 1 == N {
+            0
+        } else {
+            index + 1
+        }
+    }
+
+    // SAFETY: must only be called by the single producer
+    unsafe fn inner_enqueue(&self, val: T) -> Result<(), T> {
+        let tail = self.tail.load(Ordering::Relaxed);
+        let next = Self::increment(tail);
+        if next == self.head.load(Ordering::Acquire) {
+            return Err(val);
+        }
+        self.slots[tail].with_mut(|slot| (*slot).write(val));
+        self.tail.store(next, Ordering::Release);
+        // pairs with the fence in `Recv::poll`: either we see that the consumer is waiting, or
+        // it sees the new element
+        fence(Ordering::SeqCst);
+        if self.waiting.load(Ordering::Relaxed) {
+            self.waker.wake();
+        }
+        Ok(())
+    }
+
+    // SAFETY: must only be called by the single consumer
+    unsafe fn inner_dequeue(&self) -> Option<T> {
+        let head = self.head.load(Ordering::Relaxed);
+        if head == self.tail.load(Ordering::Acquire) {
+            return None;
+        }
+        let val = self.slots[head].with(|slot| (*slot).assume_init_read());
+        self.head.store(Self::increment(head), Ordering::Release);
+        Some(val)
+    }
+
+    // SAFETY: must only be called by the single consumer
+    unsafe fn inner_peek(&self) -> Option<&T> {
+        let head = self.head.load(Ordering::Relaxed);
+        if head == self.tail.load(Ordering::Acquire) {
+            None
+        } else {
+            Some(self.slots[head].with(|slot| (*slot).assume_init_ref()))
+        }
+    }
+}
+
+impl<T, const N: usize> Default for Queue<T, N> {
+    fn default() -> Self {
+        Queue::new()
+    }
+}
+
+impl<T, const N: usize> Drop for Queue<T, N> {
+    fn drop(&mut self) {
+        while self.dequeue().is_some() {}
+    }
+}
+
+impl<T, const N: usize> fmt::Debug for Queue<T, N> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("Queue")
+            .field("len", &self.len())
+            .finish_non_exhaustive()
+    }
+}
+
+/// The producer half of a [`Queue`].
+pub struct Producer<'a, T, const N: usize> {
+    queue: &'a Queue<T, N>,
+    _not_sync: PhantomData<*mut ()>,
+}
+
+impl<T, const N: usize> Producer<'_, T, N> {
+    /// Adds an element to the back of the queue, returning it back if the queue is full.
+    #[inline]
+    pub fn enqueue(&mut self, val: T) -> Result<(), T> {
+        // SAFETY: there is only one producer, and it is borrowed mutably
+        unsafe { self.queue.inner_enqueue(val) }
+    }
+
+    /// Returns `true` if there is room for at least one more element.
+    #[inline]
+    pub fn ready(&self) -> bool {
+        !self.queue.is_full()
+    }
+
+    /// Returns the number of elements in the queue.
+    #[inline]
+    pub fn len(&self) -> usize {
+        self.queue.len()
+    }
+
+    /// Returns `true` if the queue is empty.
+    #[inline]
+    pub fn is_empty(&self) -> bool {
+        self.queue.is_empty()
+    }
+}
+
+impl<T, const N: usize> fmt::Debug for Producer<'_, T, N> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("Producer").finish_non_exhaustive()
+    }
+}
+
+// NOTE the producer moves elements into the queue, which the consumer may move out in another
+// context
+unsafe impl<T, const N: usize> Send for Producer<'_, T, N> where T: Send {}
+
+/// The consumer half of a [`Queue`].
+pub struct Consumer<'a, T, const N: usize> {
+    queue: &'a Queue<T, N>,
+    _not_sync: PhantomData<*mut ()>,
+}
+
+impl<'a, T, const N: usize> Consumer<'a, T, N> {
+    /// Removes the element at the front of the queue, returning `None` if it is empty.
+    #[inline]
+    pub fn dequeue(&mut self) -> Option<T> {
+        // SAFETY: there is only one consumer, and it is borrowed mutably
+        unsafe { self.queue.inner_dequeue() }
+    }
+
+    /// Returns a reference to the element at the front of the queue without removing it.
+    #[inline]
+    pub fn peek(&self) -> Option<&T> {
+        // SAFETY: there is only one consumer, and the element can only be removed through
+        // `dequeue`, which borrows it mutably
+        unsafe { self.queue.inner_peek() }
+    }
+
+    /// Returns `true` if there is at least one element in the queue.
+    #[inline]
+    pub fn ready(&self) -> bool {
+        !self.queue.is_empty()
+    }
+
+    /// Returns a future that removes the element at the front of the queue, waiting for one to
+    /// be enqueued if the queue is empty.
+    ///
+    /// While the future waits, every [`enqueue`](Producer::enqueue) wakes its task. When nobody
+    /// waits, enqueueing only costs an extra memory fence and load.
+    ///
+    /// ```
+    /// use bare_metal::spsc::{Consumer, Queue};
+    ///
+    /// async fn log_task(mut bytes: Consumer<'_, u8, 64>) {
+    ///     loop {
+    ///         let byte = bytes.recv().await;
+    ///         // write `byte` to the log
+    ///         # let _ = byte;
+    ///     }
+    /// }
+    /// ```
+    #[inline]
+    pub fn recv(&mut self) -> Recv<'_, 'a, T, N> {
+        Recv { consumer: self }
+    }
+
+    /// Returns the number of elements in the queue.
+    #[inline]
+    pub fn len(&self) -> usize {
+        self.queue.len()
+    }
+
+    /// Returns `true` if the queue is empty.
+    #[inline]
+    pub fn is_empty(&self) -> bool {
+        self.queue.is_empty()
+    }
+}
+
+impl<T, const N: usize> fmt::Debug for Consumer<'_, T, N> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("Consumer").finish_non_exhaustive()
+    }
+}
+
+// NOTE see `Producer`
+unsafe impl<T, const N: usize> Send for Consumer<'_, T, N> where T: Send {}
+
+/// The future returned by [`Consumer::recv`].
+#[must_use = "futures do nothing unless polled"]
+pub struct Recv<'c, 'a, T, const N: usize> {
+    consumer: &'c mut Consumer<'a, T, N>,
+}
+
+impl<T, const N: usize> Future for Recv<'_, '_, T, N> {
+    type Output = T;
+
+    fn poll(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<T> {
+        let this = self.get_mut();
+        let queue = this.consumer.queue;
+        queue.waiting.store(true, Ordering::Relaxed);
+        queue.waker.register(cx.waker());
+        // pairs with the fence in `enqueue`
+        fence(Ordering::SeqCst);
+        match this.consumer.dequeue() {
+            Some(val) => {
+                queue.waiting.store(false, Ordering::Relaxed);
+                Poll::Ready(val)
+            }
+            None => Poll::Pending,
+        }
+    }
+}
+
+impl<T, const N: usize> Drop for Recv<'_, '_, T, N> {
+    fn drop(&mut self) {
+        self.consumer.queue.waiting.store(false, Ordering::Relaxed);
+    }
+}
+
+impl<T, const N: usize> fmt::Debug for Recv<'_, '_, T, N> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("Recv").finish_non_exhaustive()
+    }
+}
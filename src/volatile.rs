@@ -0,0 +1,92 @@
+use core::cell::UnsafeCell;
+use core::fmt;
+
+/// A memory location that is only accessed with volatile reads and writes.
+///
+/// This is the building block for memory-mapped registers: every [`get`](VolatileCell::get) and
+/// [`set`](VolatileCell::set) is a single volatile access through a raw pointer, so the compiler
+/// never merges, reorders or elides them with respect to other volatile accesses.
+///
+/// The type is `#[repr(transparent)]`, so a `#[repr(C)]` struct of `VolatileCell`s has the same
+/// layout as the struct of the plain values, which is how register blocks are described.
+///
+/// ```
+/// use bare_metal::VolatileCell;
+///
+/// let status = VolatileCell::new(0u32);
+/// status.set(0b101);
+/// assert_eq!(status.get(), 0b101);
+/// ```
+///
+/// # Memory-mapped registers
+///
+/// A `&VolatileCell<T>` is still a Rust reference, which the compiler is allowed to assume
+/// points to ordinary, dereferenceable memory. For registers whose reads have side effects, e.g.
+/// reading a data register that pops a FIFO, create the reference only right before the access,
+/// or access the register through [`as_ptr`](VolatileCell::as_ptr).
+#[repr(transparent)]
+pub struct VolatileCell<T> {
+    value: UnsafeCell<T>,
+}
+
+impl<T> VolatileCell<T> {
+    /// Creates a new `VolatileCell`.
+    #[inline]
+    pub const fn new(value: T) -> Self {
+        VolatileCell {
+            value: UnsafeCell::new(value),
+        }
+    }
+
+    /// Returns a raw pointer to the value.
+    #[inline]
+    pub const fn as_ptr(&self) -> *mut T {
+        self.value.get()
+    }
+
+    /// Returns a mutable reference to the value.
+    ///
+    /// This is not a volatile access.
+    #[inline]
+    pub fn get_mut(&mut self) -> &mut T {
+        self.value.get_mut()
+    }
+
+    /// Consumes the cell, returning the value.
+    #[inline]
+    pub fn into_inner(self) -> T {
+        self.value.into_inner()
+    }
+}
+
+impl<T: Copy> VolatileCell<T> {
+    /// Reads the value with a volatile read.
+    #[inline(always)]
+    pub fn get(&self) -> T {
+        // SAFETY: the pointer comes from a reference, so it is valid and aligned
+        unsafe { self.value.get().read_volatile() }
+    }
+
+    /// Writes `value` with a volatile write.
+    #[inline(always)]
+    pub fn set(&self, value: T) {
+        // SAFETY: the pointer comes from a reference, so it is valid and aligned
+        unsafe { self.value.get().write_volatile(value) }
+    }
+}
+
+impl<T: Copy + Default> Default for VolatileCell<T> {
+    fn default() -> Self {
+        VolatileCell::new(T::default())
+    }
+}
+
+// doesn't read the value, like the register types: a read can have side effects
+impl<T> fmt::Debug for VolatileCell<T> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("VolatileCell").finish_non_exhaustive()
+    }
+}
+
+// NOTE there is no `Sync` impl: concurrent volatile accesses to ordinary memory are data races.
+// Register abstractions built on this type decide for themselves when sharing is sound.
@@ -0,0 +1,80 @@
+use core::task::Waker;
+
+/// A slot of a [`WaiterList`].
+pub(crate) enum Waiter {
+    Free,
+    Waiting(Waker),
+    // woken, but hasn't been polled since
+    Woken,
+}
+
+/// A fixed-size list of the wakers of tasks waiting for an async primitive.
+///
+/// Every waiting future remembers the index of its slot. The list must only be accessed inside
+/// a critical section; the wakers it hands out should be dropped or woken after leaving it.
+pub(crate) struct WaiterList<const N: usize> {
+    waiters: [Waiter; N],
+    // where to start looking for the next waiter to wake, so that all slots get their turn
+    next: usize,
+}
+
+impl<const N: usize> WaiterList<N> {
+    pub(crate) const fn new() -> Self {
+        const FREE: Waiter = Waiter::Free;
+        WaiterList {
+            waiters: [FREE; N],
+            next: 0,
+        }
+    }
+
+    /// Stores `waker` in `slot`, allocating a slot first if it is `None`.
+    ///
+    /// Returns `false` if the list is full, and the previous waker of the slot, if any.
+    pub(crate) fn register(
+        &mut self,
+        slot: &mut Option<usize>,
+        waker: &Waker,
+    ) -> (bool, Option<Waiter>) {
+        let index = match *slot {
+            Some(index) => index,
+            None => match self
+                .waiters
+                .iter()
+                .position(|waiter| matches!(waiter, Waiter::Free))
+            {
+                Some(index) => index,
+                None => return (false, None),
+            },
+        };
+        *slot = Some(index);
+        let waiter = &mut self.waiters[index];
+        match waiter {
+            Waiter::Waiting(old) if old.will_wake(waker) => (true, None),
+            _ => {
+                let new = Waiter::Waiting(waker.clone());
+                (true, Some(core::mem::replace(waiter, new)))
+            }
+        }
+    }
+
+    /// Frees `slot`, returning what it held.
+    pub(crate) fn remove(&mut self, slot: usize) -> Waiter {
+        core::mem::replace(&mut self.waiters[slot], Waiter::Free)
+    }
+
+    /// Marks the next waiting slot as woken and returns its waker.
+    pub(crate) fn wake_next(&mut self) -> Option<Waker> {
+        let start = self.next;
+        for i in (start..N).chain(0..start) {
+            if let Waiter::Waiting(_) = self.waiters[i] {
+                self.next = if i + 1 == N { 0 } else { i + 1 };
+                if let Waiter::Waiting(waker) =
+                    core::mem::replace(&mut self.waiters[i], Waiter::Woken)
+                {
+                    return Some(waker);
+                }
+            }
+        }
+        None
+    }
+}
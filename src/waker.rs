@@ -0,0 +1,159 @@
+use core::cell::UnsafeCell;
+use core::fmt;
+use core::task::Waker;
+
+use crate::atomic::{AtomicU8, Ordering};
+
+/// Storage for the [`Waker`] of a future that is woken from an interrupt handler.
+///
+/// The future [`register`](WakerSlot::register)s the waker of its task every time it is polled
+/// and returns `Poll::Pending`, and the interrupt handler calls [`wake`](WakerSlot::wake) once
+/// the event the future is waiting for has happened. Only one waker is stored at a time; a newly
+/// registered waker replaces the previous one.
+///
+/// This uses the same algorithm as `futures::task::AtomicWaker`. It is lock-free on targets with
+/// atomic compare-and-swap operations; on other targets, such as `thumbv6m`, the state is only
+/// accessed inside short critical sections, unless the `portable-atomic` feature is enabled.
+///
+/// ```
+/// use bare_metal::WakerSlot;
+/// use core::future::Future;
+/// use core::pin::Pin;
+/// use core::sync::atomic::{AtomicBool, Ordering};
+/// use core::task::{Context, Poll};
+///
+/// static DONE: AtomicBool = AtomicBool::new(false);
+/// static WAKER: WakerSlot = WakerSlot::new();
+///
+/// struct Transfer;
+///
+/// impl Future for Transfer {
+///     type Output = ();
+///
+///     fn poll(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<()> {
+///         WAKER.register(cx.waker());
+///         // check after registering, so a wake-up in between isn't missed
+///         if DONE.load(Ordering::Acquire) {
+///             Poll::Ready(())
+///         } else {
+///             Poll::Pending
+///         }
+///     }
+/// }
+///
+/// // In the interrupt handler
+/// fn on_transfer_complete() {
+///     DONE.store(true, Ordering::Release);
+///     WAKER.wake();
+/// }
+/// # on_transfer_complete();
+/// ```
+pub struct WakerSlot {
+    state: AtomicU8,
+    waker: UnsafeCell<Option<Waker>>,
+}
+
+// states of the lock-free implementation
+const WAITING: u8 = 0;
+const REGISTERING: u8 = 0b01;
+const WAKING: u8 = 0b10;
+
+impl WakerSlot {
+    const_fn! {
+        /// Creates an empty `WakerSlot`.
+        #[inline]
+        pub const fn new() -> Self {
+            WakerSlot {
+                state: AtomicU8::new(WAITING),
+                waker: UnsafeCell::new(None),
+            }
+        }
+    }
+
+    /// Stores `waker`, replacing any previously registered waker.
+    ///
+    /// If [`wake`](WakerSlot::wake) is called at the same time, `waker` is woken right away.
+    /// Calling `register` from several contexts at the same time is not supported; one of the
+    /// wakers is lost in that case.
+    pub fn register(&self, waker: &Waker) {
+        match self
+            .state
+            .compare_exchange(WAITING, REGISTERING, Ordering::Acquire, Ordering::Acquire)
+            .unwrap_or_else(|state| state)
+        {
+            WAITING => {
+                // SAFETY: the `REGISTERING` state gives us exclusive access to the waker
+                unsafe {
+                    let slot = &mut *self.waker.get();
+                    match slot {
+                        Some(old) if old.will_wake(waker) => {}
+                        _ => *slot = Some(waker.clone()),
+                    }
+                }
+
+                if self
+                    .state
+                    .compare_exchange(REGISTERING, WAITING, Ordering::AcqRel, Ordering::Acquire)
+                    .is_err()
+                {
+                    // `wake` was called while we were registering and left the wake-up to us.
+                    // SAFETY: `wake` doesn't touch the waker while `REGISTERING` is set
+                    let waker = unsafe { (*self.waker.get()).take() };
+                    self.state.swap(WAITING, Ordering::AcqRel);
+                    if let Some(waker) = waker {
+                        waker.wake();
+                    }
+                }
+            }
+            WAKING => {
+                // `wake` is taking out the old waker right now; wake the new one instead
+                waker.wake_by_ref();
+                core::hint::spin_loop();
+            }
+            _ => {
+                // another `register` call is in progress
+            }
+        }
+    }
+
+    /// Wakes the registered waker, if any, and removes it.
+    #[inline]
+    pub fn wake(&self) {
+        if let Some(waker) = self.take() {
+            waker.wake();
+        }
+    }
+
+    /// Removes the registered waker, if any, and returns it.
+    pub fn take(&self) -> Option<Waker> {
+        match self.state.fetch_or(WAKING, Ordering::AcqRel) {
+            WAITING => {
+                // SAFETY: the `WAKING` state gives us exclusive access to the waker
+                let waker = unsafe { (*self.waker.get()).take() };
+                self.state.fetch_and(!WAKING, Ordering::Release);
+                waker
+            }
+            _ => {
+                // either `register` is running and will wake the new waker itself, or another
+                // `take` is in progress
+                None
+            }
+        }
+    }
+}
+
+impl Default for WakerSlot {
+    fn default() -> Self {
+        WakerSlot::new()
+    }
+}
+
+impl fmt::Debug for WakerSlot {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("WakerSlot").finish_non_exhaustive()
+    }
+}
+
+// NOTE the waker is only accessed by whoever holds the `REGISTERING` or `WAKING` state
+unsafe impl Sync for WakerSlot {}
+unsafe impl Send for WakerSlot {}
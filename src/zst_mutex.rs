@@ -0,0 +1,106 @@
+use core::cell::UnsafeCell;
+use core::fmt;
+
+use critical_section::CriticalSection;
+
+/// A mutex for zero-sized types, like capability tokens, that compiles to nothing.
+///
+/// `Mutex<RefCell<T>>` stores and checks a borrow flag even if `T` has no data at all. For a
+/// zero-sized `T`, this mutex has neither: [`borrow`](ZstMutex::borrow) hands out `&T` for the
+/// duration of the critical section, which is all that tokens whose methods take `&self` need,
+/// and [`borrow_mut_unchecked`](ZstMutex::borrow_mut_unchecked) hands out `&mut T` with the
+/// uniqueness left to the caller. Using a type that isn't zero-sized fails to compile.
+///
+/// ```
+/// use bare_metal::ZstMutex;
+/// use critical_section::CriticalSection;
+///
+/// pub struct AdcToken {
+///     _private: (),
+/// }
+///
+/// impl AdcToken {
+///     pub fn start_conversion(&self) {}
+/// }
+///
+/// static ADC: ZstMutex<AdcToken> = ZstMutex::new(AdcToken { _private: () });
+/// assert_eq!(core::mem::size_of_val(&ADC), 0);
+///
+/// let cs = unsafe { CriticalSection::new() };
+/// ADC.borrow(cs).start_conversion();
+/// ```
+///
+/// ```compile_fail
+/// use bare_metal::ZstMutex;
+///
+/// static COUNTER: ZstMutex<u32> = ZstMutex::new(0);
+/// ```
+pub struct ZstMutex<T> {
+    value: UnsafeCell<T>,
+}
+
+impl<T> ZstMutex<T> {
+    const ZERO_SIZED: () = assert!(
+        core::mem::size_of::<T>() == 0,
+        "`ZstMutex` only supports zero-sized types"
+    );
+
+    /// Creates a new `ZstMutex`.
+    #[inline]
+    pub const fn new(value: T) -> Self {
+        #[allow(clippy::let_unit_value)]
+        let () = Self::ZERO_SIZED;
+        ZstMutex {
+            value: UnsafeCell::new(value),
+        }
+    }
+
+    /// Borrows the data for the duration of the critical section.
+    #[inline]
+    pub fn borrow<'cs>(&'cs self, _cs: CriticalSection<'cs>) -> &'cs T {
+        // SAFETY: mutable references are only created through `get_mut` and
+        // `borrow_mut_unchecked`, whose callers ensure that they don't alias this one
+        unsafe { &*self.value.get() }
+    }
+
+    /// Mutably borrows the data for the duration of the critical section, without any check.
+    ///
+    /// # Safety
+    ///
+    /// For as long as the returned reference is alive, the caller must ensure that the data is
+    /// not accessed in any other way, e.g. through another call of this method in a nested
+    /// critical section.
+    #[inline]
+    #[allow(clippy::mut_from_ref)]
+    pub unsafe fn borrow_mut_unchecked<'cs>(&'cs self, _cs: CriticalSection<'cs>) -> &'cs mut T {
+        &mut *self.value.get()
+    }
+
+    /// Returns a mutable reference to the data.
+    #[inline]
+    pub fn get_mut(&mut self) -> &mut T {
+        self.value.get_mut()
+    }
+
+    /// Consumes the mutex, returning the data.
+    #[inline]
+    pub fn into_inner(self) -> T {
+        self.value.into_inner()
+    }
+}
+
+impl<T: Default> Default for ZstMutex<T> {
+    fn default() -> Self {
+        ZstMutex::new(T::default())
+    }
+}
+
+impl<T> fmt::Debug for ZstMutex<T> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("ZstMutex").finish_non_exhaustive()
+    }
+}
+
+// NOTE like `Mutex`, the data can only be borrowed inside a critical section, which serializes
+// all accesses, and must be `Send` because it can be accessed from a different context
+unsafe impl<T> Sync for ZstMutex<T> where T: Send {}
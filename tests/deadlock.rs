@@ -0,0 +1,37 @@
+#![cfg(feature = "deadlock-detection")]
+
+use std::cell::Cell;
+use std::sync::mpsc;
+use std::thread;
+use std::time::Duration;
+
+use bare_metal::{deadlock, SpinLock};
+
+std::thread_local! {
+    static CORE: Cell<usize> = const { Cell::new(0) };
+}
+
+fn core_id() -> usize {
+    CORE.with(Cell::get)
+}
+
+// waiting for a lock held by a core that doesn't wait for anything is contention, not a deadlock
+#[test]
+fn contention_is_not_a_deadlock() {
+    static SHARED: SpinLock<u32> = SpinLock::new(0);
+
+    deadlock::set_core_id(core_id);
+    let (locked, wait_locked) = mpsc::channel();
+    let core1 = thread::spawn(move || {
+        CORE.with(|core| core.set(1));
+        let mut guard = SHARED.lock();
+        locked.send(()).unwrap();
+        thread::sleep(Duration::from_millis(50));
+        *guard += 1;
+    });
+
+    wait_locked.recv().unwrap();
+    *SHARED.lock() += 1;
+    core1.join().unwrap();
+    assert_eq!(*SHARED.lock(), 2);
+}
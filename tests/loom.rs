@@ -0,0 +1,150 @@
+//! Model checks of the lock-free primitives.
+//!
+//! Run with `RUSTFLAGS="--cfg loom" cargo test --release --test loom`.
+
+#![cfg(loom)]
+
+use bare_metal::{bip, double_buffer, mpmc, spsc, SeqLock, SpinLock};
+use critical_section::CriticalSection;
+use loom::sync::Arc;
+use loom::thread;
+
+#[test]
+fn spin_lock() {
+    loom::model(|| {
+        let lock = Arc::new(SpinLock::new(0));
+        let other = lock.clone();
+        let t = thread::spawn(move || *other.lock() += 1);
+        *lock.lock() += 1;
+        t.join().unwrap();
+        assert_eq!(*lock.lock(), 2);
+    });
+}
+
+#[test]
+fn spsc_queue() {
+    loom::model(|| {
+        let queue: &'static mut spsc::Queue<u32, 2> = Box::leak(Box::new(spsc::Queue::new()));
+        let (mut producer, mut consumer) = queue.split();
+        let t = thread::spawn(move || {
+            producer.enqueue(1).unwrap();
+            while producer.enqueue(2).is_err() {
+                thread::yield_now();
+            }
+        });
+        for expected in 1..=2 {
+            loop {
+                if let Some(value) = consumer.dequeue() {
+                    assert_eq!(value, expected);
+                    break;
+                }
+                thread::yield_now();
+            }
+        }
+        t.join().unwrap();
+    });
+}
+
+#[test]
+fn mpmc_queue() {
+    loom::model(|| {
+        let queue = Arc::new(mpmc::Queue::<u32, 2>::new());
+        let producers: Vec<_> = (1..=2)
+            .map(|value| {
+                let queue = queue.clone();
+                thread::spawn(move || queue.enqueue(value).unwrap())
+            })
+            .collect();
+        let mut sum = 0;
+        for _ in 0..2 {
+            loop {
+                if let Some(value) = queue.dequeue() {
+                    sum += value;
+                    break;
+                }
+                thread::yield_now();
+            }
+        }
+        for producer in producers {
+            producer.join().unwrap();
+        }
+        assert_eq!(sum, 3);
+    });
+}
+
+#[test]
+fn bip_buffer_wraparound() {
+    loom::model(|| {
+        let buffer: &'static mut bip::BipBuffer<8> = Box::leak(Box::new(bip::BipBuffer::new()));
+        let (mut producer, mut consumer) = buffer.split();
+        let t = thread::spawn(move || {
+            let mut grant = producer.grant_exact(6).unwrap();
+            grant.copy_from_slice(b"abcdef");
+            grant.commit(6);
+            // doesn't fit at the end, so it wraps around once the consumer released the bytes
+            loop {
+                if let Some(mut grant) = producer.grant_exact(4) {
+                    grant.copy_from_slice(b"ghij");
+                    grant.commit(4);
+                    break;
+                }
+                thread::yield_now();
+            }
+        });
+        for expected in [&b"abcdef"[..], b"ghij"] {
+            loop {
+                if let Some(grant) = consumer.read() {
+                    assert_eq!(&*grant, expected);
+                    grant.release(expected.len());
+                    break;
+                }
+                thread::yield_now();
+            }
+        }
+        t.join().unwrap();
+    });
+}
+
+#[test]
+fn double_buffer() {
+    loom::model(|| {
+        let buffer: &'static mut double_buffer::DoubleBuffer<[u32; 2]> =
+            Box::leak(Box::new(double_buffer::DoubleBuffer::new([0; 2], [0; 2])));
+        let (mut writer, mut reader) = buffer.split();
+        let t = thread::spawn(move || {
+            for frame in 1..=2 {
+                writer.write(|buffer| *buffer = [frame; 2]);
+            }
+        });
+        let mut last = 0;
+        if let Some(front) = reader.read() {
+            assert_eq!(front[0], front[1]);
+            last = front[0];
+        }
+        t.join().unwrap();
+        if let Some(front) = reader.read() {
+            assert_eq!(front[0], front[1]);
+            last = front[0];
+        }
+        // the newest frame is never lost
+        assert_eq!(last, 2);
+    });
+}
+
+#[test]
+fn seq_lock() {
+    loom::model(|| {
+        let lock = Arc::new(SeqLock::new((0, 0)));
+        let other = lock.clone();
+        let t = thread::spawn(move || {
+            // SAFETY: this is the only writer
+            let cs = unsafe { CriticalSection::new() };
+            other.write(cs, (1, 1));
+            other.write(cs, (2, 2));
+        });
+        let (a, b) = lock.read();
+        assert_eq!(a, b);
+        t.join().unwrap();
+        assert_eq!(lock.read(), (2, 2));
+    });
+}